@@ -0,0 +1,28 @@
+extern crate rzdb;
+
+use rzdb::{Db, Storage};
+
+/// Converts the tables of a db between csv and binary files:
+/// `cargo run --example convert_storage -- <db name> <db dir> csv|binary`
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() != 3 {
+        eprintln!("usage: convert_storage <db name> <db dir> csv|binary");
+        std::process::exit(2);
+    }
+    let storage = match args[2].as_str() {
+        "csv" => Storage::Csv,
+        "binary" => Storage::Binary,
+        other => {
+            eprintln!("unknown storage {}, expected csv or binary", other);
+            std::process::exit(2);
+        }
+    };
+    match Db::convert_storage(&args[0], &args[1], storage) {
+        Ok(()) => println!("converted {} to {:?}", args[0], storage),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,230 @@
+//! `#[derive(RzdbTable)]` for structs with named fields, see `rzdb::RzdbTable`.
+//!
+//! The table is named after the struct in snake case and has a column for every field,
+//! both can be renamed with `#[rzdb(table = "...")]` and `#[rzdb(column = "...")]`. Every
+//! field gets a constant with its column name, named after the field in upper case.
+
+use proc_macro::{Delimiter, TokenStream, TokenTree};
+
+#[proc_macro_derive(RzdbTable, attributes(rzdb))]
+pub fn derive_rzdb_table(input: TokenStream) -> TokenStream {
+    let code = match parse_struct(input) {
+        Ok(table) => table.generate(),
+        Err(message) => format!("compile_error!({:?});", message),
+    };
+    code.parse().expect("RzdbTable: generated invalid code")
+}
+
+struct Field {
+    name: String,
+    column: String,
+    ty: String,
+}
+
+struct Table {
+    name: String,
+    table: String,
+    fields: Vec<Field>,
+}
+
+impl Table {
+    fn generate(&self) -> String {
+        let mut constants = String::new();
+        let mut columns = String::new();
+        let mut to_row = String::new();
+        let mut from_row = String::new();
+        for field in &self.fields {
+            let name = field.name.trim_start_matches("r#");
+            constants.push_str(&format!(
+                "pub const {}: &'static str = {:?};\n",
+                name.to_uppercase(),
+                field.column
+            ));
+            columns.push_str(&format!(
+                "({:?}, <{} as ::rzdb::FieldType>::COLUMN_TYPE),\n",
+                field.column, field.ty
+            ));
+            to_row.push_str(&format!(
+                "::rzdb::FieldType::to_data(&self.{}),\n",
+                field.name
+            ));
+            from_row.push_str(&format!(
+                "{}: ::rzdb::FieldType::from_column(values.next(), {:?}, {:?})?,\n",
+                field.name, self.table, field.column
+            ));
+        }
+        format!(
+            "impl {name} {{
+                {constants}
+            }}
+            impl ::rzdb::RzdbTable for {name} {{
+                const TABLE_NAME: &'static str = {table:?};
+                const COLUMNS: &'static [(&'static str, ::rzdb::ColumnType)] = &[
+                    {columns}
+                ];
+                fn to_row(&self) -> ::std::vec::Vec<::rzdb::Data> {{
+                    vec![{to_row}]
+                }}
+                fn from_row(
+                    row: ::std::vec::Vec<::rzdb::Data>,
+                ) -> ::std::result::Result<Self, ::rzdb::Error> {{
+                    let mut values = row.into_iter();
+                    Ok({name} {{
+                        {from_row}
+                    }})
+                }}
+            }}",
+            name = self.name,
+            table = self.table,
+        )
+    }
+}
+
+fn parse_struct(input: TokenStream) -> Result<Table, String> {
+    let mut tokens = input.into_iter().peekable();
+    let mut table = None;
+    let mut name = None;
+    let mut body = None;
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Punct(p) if p.as_char() == '#' => {
+                if let Some(TokenTree::Group(group)) = tokens.next() {
+                    if let Some(value) = rzdb_attribute(group.stream(), "table")? {
+                        table = Some(value);
+                    }
+                }
+            }
+            TokenTree::Ident(ident)
+                if ident.to_string() == "enum" || ident.to_string() == "union" =>
+            {
+                return Err("RzdbTable can only be derived for structs".to_string());
+            }
+            TokenTree::Ident(ident) if ident.to_string() == "struct" => {
+                name = match tokens.next() {
+                    Some(TokenTree::Ident(ident)) => Some(ident.to_string()),
+                    _ => return Err("RzdbTable: expected the name of the struct".to_string()),
+                };
+            }
+            TokenTree::Punct(p) if p.as_char() == '<' && name.is_some() => {
+                return Err("RzdbTable can not be derived for generic structs".to_string());
+            }
+            TokenTree::Group(group) if name.is_some() => {
+                if group.delimiter() != Delimiter::Brace {
+                    return Err("RzdbTable needs a struct with named fields".to_string());
+                }
+                body = Some(group.stream());
+                break;
+            }
+            _ => {}
+        }
+    }
+    let (Some(name), Some(body)) = (name, body) else {
+        return Err("RzdbTable needs a struct with named fields".to_string());
+    };
+    Ok(Table {
+        table: table.unwrap_or_else(|| snake_case(&name)),
+        name,
+        fields: parse_fields(body)?,
+    })
+}
+
+fn parse_fields(body: TokenStream) -> Result<Vec<Field>, String> {
+    let mut fields = vec![];
+    let mut tokens = body.into_iter().peekable();
+    while tokens.peek().is_some() {
+        let mut column = None;
+        // attributes and visibility
+        let name = loop {
+            match tokens.next() {
+                Some(TokenTree::Punct(p)) if p.as_char() == '#' => {
+                    if let Some(TokenTree::Group(group)) = tokens.next() {
+                        if let Some(value) = rzdb_attribute(group.stream(), "column")? {
+                            column = Some(value);
+                        }
+                    }
+                }
+                Some(TokenTree::Ident(ident)) if ident.to_string() == "pub" => {
+                    if let Some(TokenTree::Group(group)) = tokens.peek() {
+                        if group.delimiter() == Delimiter::Parenthesis {
+                            tokens.next();
+                        }
+                    }
+                }
+                Some(TokenTree::Ident(ident)) => break ident.to_string(),
+                _ => return Err("RzdbTable: expected a field name".to_string()),
+            }
+        };
+        match tokens.next() {
+            Some(TokenTree::Punct(p)) if p.as_char() == ':' => {}
+            _ => return Err(format!("RzdbTable: expected ':' after {}", name)),
+        }
+        // the type ends at a comma outside of angle brackets
+        let mut ty = TokenStream::new();
+        let mut depth = 0;
+        for token in tokens.by_ref() {
+            if let TokenTree::Punct(p) = &token {
+                match p.as_char() {
+                    '<' => depth += 1,
+                    '>' if depth > 0 => depth -= 1,
+                    ',' if depth == 0 => break,
+                    _ => {}
+                }
+            }
+            ty.extend([token]);
+        }
+        fields.push(Field {
+            column: column.unwrap_or_else(|| name.trim_start_matches("r#").to_string()),
+            name,
+            ty: ty.to_string(),
+        });
+    }
+    if fields.is_empty() {
+        return Err("RzdbTable needs at least one field".to_string());
+    }
+    Ok(fields)
+}
+
+/// the value of `key` in an attribute `rzdb(key = "value")`, None for other attributes
+fn rzdb_attribute(attribute: TokenStream, key: &str) -> Result<Option<String>, String> {
+    let mut tokens = attribute.into_iter();
+    match tokens.next() {
+        Some(TokenTree::Ident(ident)) if ident.to_string() == "rzdb" => {}
+        _ => return Ok(None),
+    }
+    let Some(TokenTree::Group(group)) = tokens.next() else {
+        return Err("RzdbTable: expected #[rzdb(key = \"value\")]".to_string());
+    };
+    let tokens: Vec<TokenTree> = group.stream().into_iter().collect();
+    match tokens.as_slice() {
+        [TokenTree::Ident(ident), TokenTree::Punct(eq), TokenTree::Literal(value)]
+            if eq.as_char() == '=' =>
+        {
+            let value = value.to_string();
+            if ident.to_string() != key {
+                return Err(format!("RzdbTable: unknown attribute {}", ident));
+            }
+            match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+                Some(value) if !value.is_empty() && !value.contains(['\\', '"']) => {
+                    Ok(Some(value.to_string()))
+                }
+                _ => Err(format!("RzdbTable: {} must be a plain string", key)),
+            }
+        }
+        _ => Err("RzdbTable: expected #[rzdb(key = \"value\")]".to_string()),
+    }
+}
+
+fn snake_case(name: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                out.push('_');
+            }
+            out.extend(c.to_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
@@ -0,0 +1,179 @@
+use crate::data::Data;
+use crate::decimal::Decimal;
+use crate::error::Error;
+use crate::time::Duration;
+use std::cmp::Ordering;
+
+/// Aggregate functions over the values of a column. Empty values are skipped.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Aggregate {
+    /// number of non-empty values
+    Count,
+    /// number of values that are `Data::Bool(true)`
+    CountTrue,
+    /// true if all values are `Data::Bool(true)`
+    All,
+    /// true if any value is `Data::Bool(true)`
+    Any,
+    /// sum of numbers, or of durations; exact if there are decimals
+    Sum,
+    /// average of numbers as float, of decimals rounded to their largest scale, or of
+    /// durations rounded to seconds
+    Avg,
+    Min,
+    Max,
+}
+
+fn gen_error(aggregate: Aggregate, datum: &Data) -> Error {
+    Error::invalid(format!(
+        "Aggregate::{:?}: unsupported value {}",
+        aggregate, datum
+    ))
+}
+
+impl Aggregate {
+    /// ```
+    /// use rzdb::{Aggregate, ColumnType, Data};
+    /// let worked: Vec<Data> = ["37:30", "", "1d 2h 30m"]
+    ///     .iter()
+    ///     .map(|s| ColumnType::Duration.parse_value(s).unwrap())
+    ///     .collect();
+    /// assert_eq!(Aggregate::Sum.apply(&worked).unwrap().to_string(), "64:00:00");
+    /// assert_eq!(Aggregate::Avg.apply(&worked).unwrap().to_string(), "32:00:00");
+    /// assert_eq!(Aggregate::Max.apply(&worked).unwrap().to_string(), "37:30:00");
+    /// assert!(Aggregate::Sum.apply(&[worked[0].clone(), Data::Int(1)]).is_err());
+    /// let long = ColumnType::Duration.parse_value("2562047788015215:00").unwrap();
+    /// assert!(Aggregate::Avg.apply(&[long.clone(), long]).is_err());
+    ///
+    /// let amounts: Vec<Data> = ["0.10", "0.20", "1"]
+    ///     .iter()
+    ///     .map(|s| ColumnType::Decimal.parse_value(s).unwrap())
+    ///     .collect();
+    /// assert_eq!(Aggregate::Sum.apply(&amounts).unwrap().to_string(), "1.30");
+    /// assert_eq!(Aggregate::Avg.apply(&amounts).unwrap().to_string(), "0.43");
+    /// assert_eq!(Aggregate::Min.apply(&amounts).unwrap().to_string(), "0.10");
+    /// assert!(Aggregate::Sum.apply(&[amounts[0].clone(), Data::Float(1.0)]).is_err());
+    ///
+    /// let big = [Data::Int(i64::MAX), Data::Int(1)];
+    /// assert!(Aggregate::Sum.apply(&big).is_err());
+    /// assert!(Aggregate::Avg.apply(&big).is_ok());
+    /// ```
+    pub fn apply(&self, values: &[Data]) -> Result<Data, Error> {
+        let values: Vec<&Data> = values
+            .iter()
+            .filter(|datum| **datum != Data::Empty)
+            .collect();
+        match self {
+            Aggregate::Count => Ok(Data::Int(values.len() as i64)),
+            Aggregate::CountTrue | Aggregate::All | Aggregate::Any => {
+                let mut count = 0;
+                for datum in &values {
+                    match datum {
+                        Data::Bool(true) => count += 1,
+                        Data::Bool(false) => {}
+                        _ => return Err(gen_error(*self, datum)),
+                    }
+                }
+                Ok(match self {
+                    Aggregate::CountTrue => Data::Int(count),
+                    Aggregate::All => Data::Bool(count == values.len() as i64),
+                    _ => Data::Bool(count > 0),
+                })
+            }
+            Aggregate::Sum | Aggregate::Avg
+                if matches!(values.first(), Some(Data::Duration(_))) =>
+            {
+                let mut sum: i64 = 0;
+                for datum in &values {
+                    match datum {
+                        Data::Duration(d) => {
+                            sum = sum
+                                .checked_add(d.seconds())
+                                .ok_or_else(|| gen_error(*self, datum))?
+                        }
+                        _ => return Err(gen_error(*self, datum)),
+                    }
+                }
+                if *self == Aggregate::Avg {
+                    let avg = (sum as f64 / values.len() as f64).round() as i64;
+                    Ok(Data::Duration(Duration::from_seconds(avg)))
+                } else {
+                    Ok(Data::Duration(Duration::from_seconds(sum)))
+                }
+            }
+            Aggregate::Sum | Aggregate::Avg
+                if values.iter().any(|datum| matches!(datum, Data::Decimal(_))) =>
+            {
+                // exact: ints are added as decimals, floats would lose the exactness
+                let mut sum = Decimal::from_int(0);
+                for datum in &values {
+                    let n = match datum {
+                        Data::Decimal(n) => *n,
+                        Data::Int(n) => Decimal::from_int(*n),
+                        _ => return Err(gen_error(*self, datum)),
+                    };
+                    sum = sum.checked_add(&n).ok_or_else(|| gen_error(*self, datum))?;
+                }
+                if *self == Aggregate::Avg {
+                    match sum.div_rounded(values.len() as i64) {
+                        Some(avg) => Ok(Data::Decimal(avg)),
+                        None => Ok(Data::Empty),
+                    }
+                } else {
+                    Ok(Data::Decimal(sum))
+                }
+            }
+            Aggregate::Sum | Aggregate::Avg => {
+                // the int sum is only needed for a sum without floats
+                let mut int_sum: Result<i64, &Data> = Ok(0);
+                let mut float_sum = 0.0;
+                let mut is_float = false;
+                for datum in &values {
+                    match datum {
+                        Data::Int(n) => {
+                            int_sum = int_sum.and_then(|sum| sum.checked_add(*n).ok_or(*datum));
+                            float_sum += *n as f64;
+                        }
+                        Data::Float(n) => {
+                            is_float = true;
+                            float_sum += n;
+                        }
+                        _ => return Err(gen_error(*self, datum)),
+                    }
+                }
+                if *self == Aggregate::Avg {
+                    if values.is_empty() {
+                        Ok(Data::Empty)
+                    } else {
+                        Ok(Data::Float(float_sum / values.len() as f64))
+                    }
+                } else if is_float {
+                    Ok(Data::Float(float_sum))
+                } else {
+                    int_sum
+                        .map(Data::Int)
+                        .map_err(|datum| gen_error(*self, datum))
+                }
+            }
+            Aggregate::Min | Aggregate::Max => {
+                let wanted = if *self == Aggregate::Min {
+                    Ordering::Less
+                } else {
+                    Ordering::Greater
+                };
+                let mut result: Option<&Data> = None;
+                for datum in values {
+                    result = match result {
+                        None => Some(datum),
+                        Some(current) => match datum.compare(current) {
+                            Some(ordering) if ordering == wanted => Some(datum),
+                            Some(_) => Some(current),
+                            None => return Err(gen_error(*self, datum)),
+                        },
+                    };
+                }
+                Ok(result.cloned().unwrap_or(Data::Empty))
+            }
+        }
+    }
+}
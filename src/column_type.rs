@@ -0,0 +1,120 @@
+use crate::data::Data;
+use crate::decimal::Decimal;
+use crate::error::Error;
+use crate::time::{DateFormat, DateTime, Duration, Time};
+
+/// The declared type of a column. Values inserted as text into a typed column are parsed
+/// as that type only, values of other types are rejected. `Any` columns guess the type
+/// of every value with `Data::parse()`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ColumnType {
+    Any,
+    String,
+    Int,
+    Float,
+    Decimal,
+    Bool,
+    Date,
+    Time,
+    DateTime,
+    Duration,
+}
+
+impl ColumnType {
+    pub fn parse(s: &str) -> Result<ColumnType, Error> {
+        match s {
+            "" | "any" => Ok(ColumnType::Any),
+            "string" => Ok(ColumnType::String),
+            "int" => Ok(ColumnType::Int),
+            "float" => Ok(ColumnType::Float),
+            "decimal" => Ok(ColumnType::Decimal),
+            "bool" => Ok(ColumnType::Bool),
+            "date" => Ok(ColumnType::Date),
+            "time" => Ok(ColumnType::Time),
+            "datetime" => Ok(ColumnType::DateTime),
+            "duration" => Ok(ColumnType::Duration),
+            _ => Err(Error::parse(format!("Unknown column type: {}", s))),
+        }
+    }
+
+    /// parses a value for a column of this type
+    /// ```
+    /// use rzdb::{ColumnType, Data};
+    /// assert_eq!(ColumnType::Bool.parse_value("true").unwrap(), Data::Bool(true));
+    /// assert_eq!(ColumnType::Bool.parse_value("No").unwrap(), Data::Bool(false));
+    /// assert_eq!(ColumnType::Any.parse_value("true").unwrap(), Data::String("true".to_string()));
+    /// assert_eq!(ColumnType::String.parse_value("12:30").unwrap(), Data::String("12:30".to_string()));
+    /// assert!(ColumnType::Int.parse_value("twelve").is_err());
+    /// assert_eq!(ColumnType::Decimal.parse_value("0.10").unwrap().to_string(), "0.10");
+    /// assert_eq!(ColumnType::Duration.parse_value("1d 2h").unwrap().to_string(), "26:00:00");
+    /// ```
+    pub fn parse_value(&self, s: &str) -> Result<Data, Error> {
+        self.parse_value_with(s, &DateFormat::default())
+    }
+
+    /// parses a value for a column of this type, reading dates with the given format
+    pub fn parse_value_with(&self, s: &str, date_format: &DateFormat) -> Result<Data, Error> {
+        if s.is_empty() {
+            return Ok(Data::Empty);
+        }
+        Ok(match self {
+            ColumnType::Any => Data::parse_with(s, date_format),
+            ColumnType::String => Data::String(s.to_string()),
+            ColumnType::Int => Data::Int(s.parse::<i64>()?),
+            ColumnType::Float => Data::Float(s.parse::<f64>()?),
+            ColumnType::Decimal => Data::Decimal(Decimal::parse(s)?),
+            ColumnType::Bool => Data::Bool(ColumnType::parse_bool(s)?),
+            ColumnType::Date => Data::Date(date_format.parse(s)?),
+            ColumnType::Time => Data::Time(Time::parse(s)?),
+            ColumnType::DateTime => Data::DateTime(DateTime::parse(s)?),
+            ColumnType::Duration => Data::Duration(Duration::parse(s)?),
+        })
+    }
+
+    fn parse_bool(s: &str) -> Result<bool, Error> {
+        match s.to_lowercase().as_str() {
+            "true" | "yes" => Ok(true),
+            "false" | "no" => Ok(false),
+            _ => Err(Error::parse(format!("Not a bool: {}", s))),
+        }
+    }
+
+    /// returns true if a value can be stored in a column of this type
+    pub fn accepts(&self, data: &Data) -> bool {
+        matches!(
+            (self, data),
+            (ColumnType::Any, _)
+                | (_, Data::Empty)
+                | (ColumnType::String, Data::String(_))
+                | (ColumnType::Int, Data::Int(_))
+                | (ColumnType::Float, Data::Float(_))
+                | (ColumnType::Decimal, Data::Decimal(_))
+                | (ColumnType::Bool, Data::Bool(_))
+                | (ColumnType::Date, Data::Date(_))
+                | (ColumnType::Time, Data::Time(_))
+                | (ColumnType::DateTime, Data::DateTime(_))
+                | (ColumnType::Duration, Data::Duration(_))
+        )
+    }
+}
+
+impl std::fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ColumnType::Any => "any",
+                ColumnType::String => "string",
+                ColumnType::Int => "int",
+                ColumnType::Float => "float",
+                ColumnType::Decimal => "decimal",
+                ColumnType::Bool => "bool",
+                ColumnType::Date => "date",
+                ColumnType::Time => "time",
+                ColumnType::DateTime => "datetime",
+                ColumnType::Duration => "duration",
+            }
+        )
+    }
+}
@@ -1,17 +1,66 @@
 use crate::data::Data;
+use crate::error::Error;
+use crate::expr::Expr;
+use std::cmp::Ordering;
 
 pub struct Condition<'a> {
     pub column: &'a str,
     pub data: Data,
     pub condition: ConditionType,
+    /// if set, the row matches if the expression evaluates to true; column, data and
+    /// condition are ignored
+    pub expr: Option<Expr>,
 }
 
 impl Condition<'_> {
-    pub fn new(column: &str, data: Data, condition: ConditionType) -> Condition {
+    pub fn new(column: &str, data: Data, condition: ConditionType) -> Condition<'_> {
         Condition {
             column,
             data,
             condition,
+            expr: None,
+        }
+    }
+
+    /// a condition that matches the rows for which the expression is true
+    pub fn from_expr(expr: Expr) -> Condition<'static> {
+        Condition {
+            column: "",
+            data: Data::Empty,
+            condition: ConditionType::Equal,
+            expr: Some(expr),
+        }
+    }
+
+    /// parses an expression condition such as `age >= 18 and upper(city) = 'BERLIN'`
+    /// ```
+    /// use rzdb::{Condition, Data};
+    /// let condition = Condition::parse("price * quantity > 100").unwrap();
+    /// let row = |name: &str| match name {
+    ///     "price" => Some(Data::Int(30)),
+    ///     "quantity" => Some(Data::Int(4)),
+    ///     _ => None,
+    /// };
+    /// assert!(condition.matches_row(&row).unwrap());
+    /// assert!(Condition::parse("price >").is_err());
+    /// ```
+    pub fn parse(s: &str) -> Result<Condition<'static>, Error> {
+        Ok(Condition::from_expr(Expr::parse(s)?))
+    }
+
+    /// evaluates the condition for a row whose values are looked up by column name
+    pub fn matches_row(&self, column: &dyn Fn(&str) -> Option<Data>) -> Result<bool, Error> {
+        match &self.expr {
+            Some(expr) => Ok(expr.eval(column)? == Data::Bool(true)),
+            None => Ok(self.matches(&column(self.column).unwrap_or(Data::Empty))),
+        }
+    }
+
+    /// the columns the condition needs
+    pub fn columns(&self) -> Vec<String> {
+        match &self.expr {
+            Some(expr) => expr.columns(),
+            None => vec![self.column.to_string()],
         }
     }
 
@@ -19,18 +68,33 @@ impl Condition<'_> {
         Condition::new(column, Data::String(data.to_string()), ConditionType::Equal)
     }
 
-    pub fn equal_int(column: &str, data: i64) -> Condition {
+    pub fn equal_int(column: &str, data: i64) -> Condition<'_> {
         Condition::new(column, Data::Int(data), ConditionType::Equal)
     }
 
-    pub fn not_null(column: &str) -> Condition {
+    pub fn equal_bool(column: &str, data: bool) -> Condition<'_> {
+        Condition::new(column, Data::Bool(data), ConditionType::Equal)
+    }
+
+    pub fn not_null(column: &str) -> Condition<'_> {
         Condition::new(column, Data::Empty, ConditionType::NotEqual)
     }
 
+    /// compares the column's value with the condition's value, e.g. for LessThan the
+    /// column's value must be less than the condition's value
     pub fn matches(&self, data: &Data) -> bool {
+        let ordering = data.compare(&self.data);
         match self.condition {
             ConditionType::Equal => self.data == *data,
-            _ => unimplemented!(),
+            ConditionType::NotEqual => self.data != *data,
+            ConditionType::LessThan => ordering == Some(Ordering::Less),
+            ConditionType::LessThanOrEqual => {
+                matches!(ordering, Some(Ordering::Less | Ordering::Equal))
+            }
+            ConditionType::GreaterThan => ordering == Some(Ordering::Greater),
+            ConditionType::GreaterThanOrEqual => {
+                matches!(ordering, Some(Ordering::Greater | Ordering::Equal))
+            }
         }
     }
 }
@@ -0,0 +1,410 @@
+use crate::error::Error;
+use std::io::BufRead;
+
+/// A streaming reader of RFC 4180 csv: fields are separated by commas, records by LF or
+/// CRLF. Quoted fields may contain commas, line breaks and doubled quotes `""`. A byte
+/// order mark at the start is skipped and a blank line is a record without fields.
+/// ```
+/// use rzdb::CsvReader;
+/// let input = "\u{feff}name,note\r\nAnn,\"two\r\nlines\"\r\n\"say \"\"hi\"\"\",\n";
+/// let records: Vec<Vec<String>> = CsvReader::new(input.as_bytes())
+///     .collect::<Result<_, _>>()
+///     .unwrap();
+/// assert_eq!(records, vec![
+///     vec!["name", "note"],
+///     vec!["Ann", "two\r\nlines"],
+///     vec!["say \"hi\"", ""],
+/// ]);
+/// ```
+pub struct CsvReader<R> {
+    reader: R,
+    dialect: Dialect,
+    /// the number of lines read so far
+    line: usize,
+    /// the line where the last record started
+    record_line: usize,
+}
+
+/// How fields are separated and quoted in a csv file. The default is RFC 4180: comma
+/// separated, quoted with `"` and quotes inside quoted fields doubled.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Dialect {
+    pub delimiter: char,
+    /// None if quotes have no special meaning
+    pub quote: Option<char>,
+    pub escape: EscapeStyle,
+}
+
+/// how a quote is written inside a quoted field
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EscapeStyle {
+    /// `""`, the quote doubled
+    Doubled,
+    /// `\"`, also `\n`, `\r`, `\t` and `\\`, inside and outside of quotes
+    Backslash,
+}
+
+impl Default for Dialect {
+    fn default() -> Dialect {
+        Dialect {
+            delimiter: ',',
+            quote: Some('"'),
+            escape: EscapeStyle::Doubled,
+        }
+    }
+}
+
+impl Dialect {
+    /// Guesses the dialect of csv text, e.g. the first lines of a file: the delimiter
+    /// (comma, semicolon, tab or pipe), quote and escape style that split the most
+    /// records into the same number of fields without errors.
+    /// ```
+    /// use rzdb::{Dialect, EscapeStyle};
+    /// let dialect = Dialect::sniff("name;note\nAnn;'it''s; here'\nBob;fine\n");
+    /// assert_eq!(dialect.delimiter, ';');
+    /// assert_eq!(dialect.quote, Some('\''));
+    /// let dialect = Dialect::sniff("a|b\n\"say \\\"hi\\\"\"|2\n");
+    /// assert_eq!((dialect.delimiter, dialect.escape), ('|', EscapeStyle::Backslash));
+    /// assert_eq!(Dialect::sniff("single column\n"), Dialect::default());
+    /// ```
+    pub fn sniff(sample: &str) -> Dialect {
+        const RECORDS: usize = 50;
+        let mut best = Dialect::default();
+        let mut best_score = (0, false, 0);
+        for delimiter in [',', ';', '\t', '|'] {
+            for quote in ['"', '\''] {
+                for escape in [EscapeStyle::Doubled, EscapeStyle::Backslash] {
+                    let dialect = Dialect {
+                        delimiter,
+                        quote: Some(quote),
+                        escape,
+                    };
+                    let mut counts = vec![];
+                    let mut failed = false;
+                    for record in CsvReader::with_dialect(sample.as_bytes(), dialect).take(RECORDS)
+                    {
+                        match record {
+                            Ok(fields) if fields.is_empty() => {}
+                            Ok(fields) => counts.push(fields.len()),
+                            Err(_) => {
+                                failed = true;
+                                break;
+                            }
+                        }
+                    }
+                    // the most frequent number of fields and how often it occurs
+                    let (fields, frequency) = counts
+                        .iter()
+                        .map(|n| (*n, counts.iter().filter(|m| *m == n).count()))
+                        .max_by_key(|(n, frequency)| (*frequency, *n))
+                        .unwrap_or((0, 0));
+                    let score = if fields > 1 {
+                        (frequency, !failed, fields)
+                    } else {
+                        (0, !failed, fields)
+                    };
+                    if score > best_score {
+                        best = dialect;
+                        best_score = score;
+                    }
+                }
+            }
+        }
+        best
+    }
+}
+
+enum State {
+    /// at the start of a field
+    Start,
+    Unquoted,
+    /// inside quotes opened at the given line and column
+    Quoted(usize, usize),
+    /// after the closing quote of a field
+    Closed,
+}
+
+impl<R: BufRead> CsvReader<R> {
+    pub fn new(reader: R) -> CsvReader<R> {
+        CsvReader::with_dialect(reader, Dialect::default())
+    }
+
+    pub fn with_dialect(reader: R, dialect: Dialect) -> CsvReader<R> {
+        CsvReader {
+            reader,
+            dialect,
+            line: 0,
+            record_line: 0,
+        }
+    }
+
+    /// a reader whose first line has the given number, for input that continues after
+    /// lines that were read before
+    pub(crate) fn starting_at_line(reader: R, line: usize) -> CsvReader<R> {
+        CsvReader {
+            reader,
+            dialect: Dialect::default(),
+            line: line - 1,
+            record_line: 0,
+        }
+    }
+
+    /// the line, counting from 1, where the last record returned by next() started
+    pub fn record_line(&self) -> usize {
+        self.record_line
+    }
+
+    fn error(line: usize, column: usize, message: &str) -> Error {
+        Error::ParseError {
+            line: Some(line),
+            column: Some(column),
+            message: message.to_string(),
+        }
+    }
+
+    /// reads the next line including its line break, None at the end of the input
+    fn read_line(&mut self) -> Result<Option<Vec<char>>, Error> {
+        let mut bytes = vec![];
+        if self.reader.read_until(b'\n', &mut bytes)? == 0 {
+            return Ok(None);
+        }
+        let first = self.line == 0;
+        self.line += 1;
+        let text = String::from_utf8(bytes).map_err(|e| Error::ParseError {
+            line: Some(self.line),
+            column: None,
+            message: format!("invalid UTF-8: {}", e),
+        })?;
+        let text = match text.strip_prefix('\u{feff}') {
+            Some(rest) if first => rest,
+            _ => &text,
+        };
+        Ok(Some(text.chars().collect()))
+    }
+
+    fn read_record(&mut self) -> Result<Option<Vec<String>>, Error> {
+        let Some(mut chars) = self.read_line()? else {
+            return Ok(None);
+        };
+        self.record_line = self.line;
+        if chars.iter().all(|c| *c == '\r' || *c == '\n') {
+            return Ok(Some(vec![]));
+        }
+        let mut fields = vec![];
+        let mut field = String::new();
+        let mut state = State::Start;
+        let mut idx = 0;
+        loop {
+            let Some(&ch) = chars.get(idx) else {
+                if let State::Quoted(line, column) = state {
+                    // a quoted line break, the field continues on the next line
+                    chars = self
+                        .read_line()?
+                        .ok_or_else(|| Self::error(line, column, "unterminated quote"))?;
+                    idx = 0;
+                    continue;
+                }
+                break;
+            };
+            let column = idx + 1;
+            idx += 1;
+            let line_break =
+                ch == '\n' || (ch == '\r' && matches!(chars.get(idx), None | Some('\n')));
+            let Dialect {
+                delimiter,
+                quote,
+                escape,
+            } = self.dialect;
+            if ch == '\\'
+                && escape == EscapeStyle::Backslash
+                && !line_break
+                && !matches!(state, State::Closed)
+            {
+                if let Some(&escaped) = chars.get(idx) {
+                    idx += 1;
+                    field.push(match escaped {
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        _ => escaped,
+                    });
+                    if escaped == '\r' && chars.get(idx) == Some(&'\n') {
+                        field.push('\n');
+                        idx += 1;
+                    }
+                    if let State::Start = state {
+                        state = State::Unquoted;
+                    }
+                    // an escaped line break, the field continues on the next line
+                    if idx == chars.len() && matches!(escaped, '\r' | '\n') {
+                        if let Some(next) = self.read_line()? {
+                            chars = next;
+                            idx = 0;
+                        }
+                    }
+                    continue;
+                }
+            }
+            match state {
+                State::Quoted(..) => {
+                    if Some(ch) == quote {
+                        if chars.get(idx) == Some(&ch) {
+                            field.push(ch);
+                            idx += 1;
+                        } else {
+                            state = State::Closed;
+                        }
+                    } else {
+                        field.push(ch);
+                    }
+                }
+                _ if line_break => break,
+                _ if ch == delimiter => {
+                    fields.push(std::mem::take(&mut field));
+                    state = State::Start;
+                }
+                State::Start if Some(ch) == quote => state = State::Quoted(self.line, column),
+                State::Start | State::Unquoted if Some(ch) == quote => {
+                    return Err(Self::error(self.line, column, "quote in an unquoted field"));
+                }
+                State::Start | State::Unquoted => {
+                    field.push(ch);
+                    state = State::Unquoted;
+                }
+                State::Closed => {
+                    return Err(Self::error(
+                        self.line,
+                        column,
+                        &format!("expected {:?} after the closing quote", delimiter),
+                    ));
+                }
+            }
+        }
+        fields.push(field);
+        Ok(Some(fields))
+    }
+}
+
+impl<R: BufRead> Iterator for CsvReader<R> {
+    type Item = Result<Vec<String>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+/// quotes a field if necessary, doubling the quotes inside
+pub(crate) fn quote_field(s: &str) -> String {
+    if s.contains([',', '"', '\r', '\n']) || s.starts_with([' ', '\t']) || s.ends_with([' ', '\t'])
+    {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// splits a single record, e.g. the items of a list
+pub(crate) fn split_record(s: &str) -> Result<Vec<String>, Error> {
+    let mut reader = CsvReader::new(s.as_bytes());
+    let fields = reader.next().transpose()?.unwrap_or_default();
+    if reader.next().is_some() {
+        return Err(Error::parse(format!("more than one csv record: {}", s)));
+    }
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(s: &str) -> Result<Vec<Vec<String>>, Error> {
+        CsvReader::new(s.as_bytes()).collect()
+    }
+
+    #[test]
+    fn test_read() {
+        assert_eq!(read("").unwrap(), Vec::<Vec<String>>::new());
+        assert_eq!(read("a,b").unwrap(), [["a", "b"]]);
+        assert_eq!(
+            read("a,,\n\n,\r\n").unwrap(),
+            vec![vec!["a", "", ""], vec![], vec!["", ""]]
+        );
+        assert_eq!(
+            read("\"a,b\",\"\"\"\",\"\"\n").unwrap(),
+            [["a,b", "\"", ""]]
+        );
+        assert_eq!(
+            read("\"1\n2\r\n\n3\",x\ny\n").unwrap(),
+            vec![vec!["1\n2\r\n\n3", "x"], vec!["y"]]
+        );
+        assert_eq!(
+            read("back\\slash,a\rb\n").unwrap(),
+            [["back\\slash", "a\rb"]]
+        );
+        assert_eq!(read("\u{feff}\u{feff}x").unwrap(), [["\u{feff}x"]]);
+        assert_eq!(read("[a,b]").unwrap(), [["[a", "b]"]]);
+    }
+
+    #[test]
+    fn test_read_dialects() {
+        let read_with = |s: &str, dialect: Dialect| -> Vec<Vec<String>> {
+            CsvReader::with_dialect(s.as_bytes(), dialect)
+                .collect::<Result<_, _>>()
+                .unwrap()
+        };
+        let dialect = Dialect {
+            delimiter: '\t',
+            quote: None,
+            escape: EscapeStyle::Backslash,
+        };
+        assert_eq!(
+            read_with("a\\tb\t\"c,d\"\te\\\\\n", dialect),
+            [["a\tb", "\"c,d\"", "e\\"]]
+        );
+        let dialect = Dialect {
+            delimiter: ';',
+            quote: Some('\''),
+            escape: EscapeStyle::Backslash,
+        };
+        assert_eq!(
+            read_with("'it\\'s';'a''b';x\\\ny\n", dialect),
+            [["it's", "a'b", "x\ny"]]
+        );
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let position = |s: &str| match read(s) {
+            Err(Error::ParseError { line, column, .. }) => (line, column),
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(position("a,b\nc,d\"e\n"), (Some(2), Some(4)));
+        assert_eq!(position("a\n\"x\"y,z"), (Some(2), Some(4)));
+        assert_eq!(position("a\nb,\"open\n\n"), (Some(2), Some(3)));
+        let mut reader = CsvReader::new(&b"ok\n\"\n\"\n\xff\n"[..]);
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().is_ok());
+        assert_eq!(reader.record_line(), 2);
+        match reader.next().unwrap() {
+            Err(Error::ParseError { line, .. }) => assert_eq!(line, Some(4)),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_quote_round_trip() {
+        let fields = [
+            "plain",
+            "",
+            " padded ",
+            "a,b",
+            "say \"hi\"",
+            "two\r\nlines",
+            "\\n",
+        ];
+        let line: Vec<String> = fields.iter().map(|f| quote_field(f)).collect();
+        assert_eq!(split_record(&line.join(",")).unwrap(), fields);
+        assert_eq!(split_record("").unwrap(), Vec::<String>::new());
+        assert!(split_record("a\nb").is_err());
+    }
+}
@@ -1,7 +1,12 @@
+use crate::csv::{quote_field, split_record};
+use crate::error::Error;
 use crate::{
+    blob::Blob,
+    decimal::Decimal,
     join::Join,
-    time::{Date, Time},
+    time::{Date, DateError, DateFormat, DateTime, Duration, Time},
 };
+use std::cmp::Ordering;
 
 #[derive(Clone, PartialEq)]
 pub enum Data {
@@ -9,14 +14,23 @@ pub enum Data {
     Empty,
     Float(f64),
     Int(i64),
+    /// exact fixed-point number, only stored in decimal columns
+    Decimal(Decimal),
+    Bool(bool),
     Date(Date),
     Time(Time),
+    DateTime(DateTime),
+    Duration(Duration),
     Join(Join),
+    List(Vec<Data>),
+    /// binary content stored in the blobs directory of the db, see `Db::store_blob()`
+    Blob(Blob),
 }
 
-fn encode_for_csv(s: &str) -> String {
+pub(crate) fn encode_for_csv(s: &str) -> String {
     let mut out = String::new();
     if s.contains('\n')
+        || s.contains('\\')
         || s.contains('"')
         || s.contains(',')
         || s.contains('\t')
@@ -32,6 +46,7 @@ fn encode_for_csv(s: &str) -> String {
                 '\r' => out.push_str("\\r"),
                 '\t' => out.push_str("\\t"),
                 '"' => out.push_str("\"\""),
+                '\\' => out.push_str("\\\\"),
                 _ => out.push(c),
             }
         }
@@ -42,13 +57,24 @@ fn encode_for_csv(s: &str) -> String {
 
     out
 }
+/// splits a csv line into its unquoted and unescaped fields
+pub(crate) fn split_csv_line(s: &str) -> Vec<String> {
+    CsvIterator::new(s).collect()
+}
+
 struct CsvIterator<'a> {
     s: &'a str,
     pos: usize,
+    // the last field ended with a comma, so there is one more (empty) field
+    trailing_field: bool,
 }
 impl<'a> CsvIterator<'a> {
     fn new(s: &'a str) -> CsvIterator<'a> {
-        CsvIterator { s, pos: 0 }
+        CsvIterator {
+            s,
+            pos: 0,
+            trailing_field: false,
+        }
     }
 }
 impl<'a> Iterator for CsvIterator<'a> {
@@ -56,30 +82,38 @@ impl<'a> Iterator for CsvIterator<'a> {
 
     fn next(&mut self) -> Option<String> {
         if self.pos >= self.s.len() {
+            if self.trailing_field {
+                self.trailing_field = false;
+                return Some(String::new());
+            }
             return None;
         }
+        self.trailing_field = false;
         let mut out = String::new();
         let mut in_quote = false;
         let mut in_brackets = false;
         let mut in_escape = false;
-        let mut old_char = 'x';
-        let mut num_quotes = 0;
+        let mut skip_quote = false;
 
         for ch in self.s[self.pos..].chars() {
             self.pos += ch.len_utf8();
-            if in_escape {
+            if skip_quote {
+                skip_quote = false;
+            } else if in_escape {
                 match ch {
                     'n' => out.push('\n'),
                     'r' => out.push('\r'),
                     't' => out.push('\t'),
                     '"' => out.push('"'),
+                    // keep the escape so that decode_field() can tell a string from a list
+                    '{' => out.push_str("\\{"),
                     _ => out.push(ch),
                 }
                 in_escape = false;
             } else {
                 match ch {
                     '[' => {
-                        in_brackets = true;
+                        in_brackets = !in_quote;
                         out.push(ch);
                     }
                     ']' => {
@@ -87,9 +121,10 @@ impl<'a> Iterator for CsvIterator<'a> {
                         out.push(ch);
                     }
                     '"' => {
-                        num_quotes += 1;
-                        if old_char == '"' && num_quotes % 2 == 1 {
+                        // a doubled quote inside quotes is a literal quote
+                        if in_quote && self.s[self.pos..].starts_with('"') {
                             out.push('"');
+                            skip_quote = true;
                         } else {
                             in_quote = !in_quote;
                         }
@@ -115,6 +150,7 @@ impl<'a> Iterator for CsvIterator<'a> {
                         if in_quote || in_brackets {
                             out.push(ch);
                         } else {
+                            self.trailing_field = true;
                             return Some(out);
                         }
                     }
@@ -123,7 +159,6 @@ impl<'a> Iterator for CsvIterator<'a> {
                     }
                 }
             }
-            old_char = ch;
         }
         self.pos += out.len();
         Some(out)
@@ -132,11 +167,29 @@ impl<'a> Iterator for CsvIterator<'a> {
 
 impl Data {
     pub fn parse(s: &str) -> Data {
+        Data::parse_dates(s, Date::parse)
+    }
+
+    /// Like parse(), but dates are read with the given date format.
+    /// ```
+    /// use rzdb::Data;
+    /// use rzdb::time::{Date, DateFormat};
+    /// let format = DateFormat::new(&["%d/%m/%Y"], "%d/%m/%Y").unwrap();
+    /// assert_eq!(Data::parse_with("13/01/2024", &format), Data::Date(Date::new(2024, 1, 13).unwrap()));
+    /// assert_eq!(Data::parse_with("2024-01-13", &format), Data::String("2024-01-13".to_string()));
+    /// ```
+    pub fn parse_with(s: &str, date_format: &DateFormat) -> Data {
+        Data::parse_dates(s, |s| date_format.parse(s))
+    }
+
+    fn parse_dates(s: &str, parse_date: impl Fn(&str) -> Result<Date, DateError>) -> Data {
         if s.is_empty() {
             Data::Empty
         } else if let Ok(n) = Join::parse(s) {
             Data::Join(n)
-        } else if let Ok(n) = Date::parse(s) {
+        } else if let Ok(n) = DateTime::parse(s) {
+            Data::DateTime(n)
+        } else if let Ok(n) = parse_date(s) {
             Data::Date(n)
         } else if let Ok(n) = Time::parse(s) {
             Data::Time(n)
@@ -160,14 +213,168 @@ impl Data {
         }
     }
 
+    pub fn as_list(&self) -> Option<&Vec<Data>> {
+        match self {
+            Data::List(l) => Some(l),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Data::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// Compares two values of the same type; ints, floats and decimals compare numerically.
+    /// Returns None for values that have no order, e.g. a string and a date.
+    /// ```
+    /// use rzdb::Data;
+    /// use std::cmp::Ordering;
+    /// assert_eq!(Data::Int(1).compare(&Data::Float(1.5)), Some(Ordering::Less));
+    /// assert_eq!(Data::Bool(true).compare(&Data::Bool(false)), Some(Ordering::Greater));
+    /// assert_eq!(Data::Int(1).compare(&Data::parse("a")), None);
+    /// ```
+    pub fn compare(&self, other: &Data) -> Option<Ordering> {
+        match (self, other) {
+            (Data::String(a), Data::String(b)) => a.partial_cmp(b),
+            (Data::Int(a), Data::Int(b)) => a.partial_cmp(b),
+            (Data::Float(a), Data::Float(b)) => a.partial_cmp(b),
+            (Data::Int(a), Data::Float(b)) => (*a as f64).partial_cmp(b),
+            (Data::Float(a), Data::Int(b)) => a.partial_cmp(&(*b as f64)),
+            (Data::Decimal(a), Data::Decimal(b)) => a.partial_cmp(b),
+            (Data::Decimal(a), Data::Int(b)) => a.partial_cmp(&Decimal::from_int(*b)),
+            (Data::Int(a), Data::Decimal(b)) => Decimal::from_int(*a).partial_cmp(b),
+            (Data::Decimal(a), Data::Float(b)) => a.to_f64().partial_cmp(b),
+            (Data::Float(a), Data::Decimal(b)) => a.partial_cmp(&b.to_f64()),
+            (Data::Bool(a), Data::Bool(b)) => a.partial_cmp(b),
+            (Data::Date(a), Data::Date(b)) => a.partial_cmp(b),
+            (Data::Time(a), Data::Time(b)) => a.partial_cmp(b),
+            (Data::DateTime(a), Data::DateTime(b)) => a.partial_cmp(b),
+            (Data::Duration(a), Data::Duration(b)) => a.partial_cmp(b),
+            (Data::Empty, Data::Empty) => Some(Ordering::Equal),
+            _ => None,
+        }
+    }
+
+    /// a total order for sorting: values that can not be compared are ordered by their
+    /// kind, numbers first and NaN after all other numbers
+    pub(crate) fn sort_cmp(&self, other: &Data) -> Ordering {
+        let rank = |data: &Data| match data {
+            Data::Float(f) if f.is_nan() => 1,
+            Data::Int(_) | Data::Float(_) | Data::Decimal(_) => 0,
+            Data::Bool(_) => 2,
+            Data::Date(_) => 3,
+            Data::Time(_) => 4,
+            Data::DateTime(_) => 5,
+            Data::Duration(_) => 6,
+            Data::String(_) => 7,
+            Data::Join(_) => 8,
+            Data::List(_) => 9,
+            Data::Blob(_) => 10,
+            Data::Empty => 11,
+        };
+        rank(self)
+            .cmp(&rank(other))
+            .then_with(|| self.compare(other).unwrap_or(Ordering::Equal))
+    }
+
     pub fn decode_line(s: &str) -> Vec<Data> {
         let mut out = vec![];
         for s in CsvIterator::new(s) {
-            out.push(Data::parse(&s));
+            out.push(Data::decode_field(&s));
         }
         out
     }
 
+    /// decodes a single csv field as written by encode_for_csv(), including lists
+    pub(crate) fn decode_field(s: &str) -> Data {
+        if let Some(escaped) = s.strip_prefix('\\').filter(|rest| rest.starts_with('{')) {
+            Data::String(escaped.to_string())
+        } else if s.starts_with('{') && s.ends_with('}') {
+            Data::List(Data::decode_line(&s[1..s.len() - 1]))
+        } else {
+            Data::parse(s)
+        }
+    }
+
+    /// Encodes the value as a csv field of the typed file format: every non-empty value is
+    /// prefixed with a type tag (`s:`, `i:`, `f:`, `dec:`, `b:`, `d:`, `t:`, `dt:`, `du:`,
+    /// `bl:`, `j:`, `l:`), so that decode_typed() restores exactly the same variant.
+    pub fn encode_typed(&self) -> String {
+        let typed = match self {
+            Data::Empty => return "".to_string(),
+            Data::String(s) => format!("s:{}", s),
+            Data::Int(n) => format!("i:{}", n),
+            Data::Float(n) => format!("f:{}", n),
+            Data::Decimal(n) => format!("dec:{}", n),
+            Data::Bool(b) => format!("b:{}", b),
+            Data::Date(n) => format!("d:{}", n),
+            Data::Time(n) => format!("t:{}", n),
+            Data::DateTime(n) => format!("dt:{}", n),
+            Data::Duration(n) => format!("du:{}", n),
+            Data::Blob(n) => format!("bl:{}", n),
+            Data::Join(n) => format!("j:{}", n),
+            Data::List(l) => {
+                let items: Vec<String> = l
+                    .iter()
+                    .map(|datum| match datum {
+                        Data::Empty => "\"\"".to_string(),
+                        _ => datum.encode_typed(),
+                    })
+                    .collect();
+                format!("l:{{{}}}", items.join(","))
+            }
+        };
+        quote_field(&typed)
+    }
+
+    /// Decodes a single unquoted field written by encode_typed().
+    pub fn decode_typed(s: &str) -> Result<Data, Error> {
+        if s.is_empty() {
+            return Ok(Data::Empty);
+        }
+        let invalid = || -> Error {
+            Error::parse(format!("Data::decode_typed(): invalid typed value: {}", s))
+        };
+        let (tag, value) = s.split_once(':').ok_or_else(invalid)?;
+        Ok(match tag {
+            "s" => Data::String(value.to_string()),
+            "i" => Data::Int(value.parse::<i64>()?),
+            "f" => Data::Float(value.parse::<f64>()?),
+            "dec" => Data::Decimal(Decimal::parse(value)?),
+            "b" => Data::Bool(value.parse::<bool>()?),
+            "d" => Data::Date(Date::parse_iso(value)?),
+            "t" => Data::Time(Time::parse(value)?),
+            "dt" => Data::DateTime(DateTime::parse(value)?),
+            "du" => Data::Duration(Duration::parse(value)?),
+            "bl" => Data::Blob(Blob::parse(value)?),
+            "j" => Data::Join(Join::parse(value)?),
+            "l" => {
+                let inner = value
+                    .strip_prefix('{')
+                    .and_then(|v| v.strip_suffix('}'))
+                    .ok_or_else(invalid)?;
+                Data::List(
+                    split_record(inner)?
+                        .iter()
+                        .map(|item| Data::decode_typed(item))
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            _ => return Err(invalid()),
+        })
+    }
+
+    /// Decodes a csv line written with encode_typed().
+    pub fn decode_typed_line(s: &str) -> Result<Vec<Data>, Error> {
+        split_record(s)?
+            .iter()
+            .map(|field| Data::decode_typed(field))
+            .collect()
+    }
+
     pub fn no_time_seconds(&self) -> String {
         match self {
             Data::Time(n) => n.to_string().chars().take(5).collect(),
@@ -178,18 +385,38 @@ impl Data {
     pub fn item_count(&self) -> usize {
         match self {
             Data::Join(j) => j.ids.len(),
+            Data::List(l) => l.len(),
             _ => 1,
         }
     }
 
+    /// Lists are written as `{item,item,...}` with every item encoded recursively, so the
+    /// list nests with the regular csv quoting. Strings starting with `{` get a leading
+    /// backslash to keep them apart from lists.
     pub fn encode_for_csv(&self) -> String {
         match self {
+            Data::String(s) if s.starts_with('{') => encode_for_csv(&format!("\\{}", s)),
             Data::String(s) => encode_for_csv(s),
             Data::Int(n) => n.to_string(),
             Data::Float(n) => n.to_string(),
+            Data::Decimal(n) => n.to_string(),
+            Data::Bool(b) => b.to_string(),
             Data::Date(n) => n.to_string(),
             Data::Time(n) => n.to_string(),
+            Data::DateTime(n) => n.to_string(),
+            Data::Duration(n) => n.to_string(),
+            Data::Blob(n) => n.to_string(),
             Data::Join(n) => n.to_string(),
+            Data::List(l) => {
+                let items: Vec<String> = l
+                    .iter()
+                    .map(|datum| match datum {
+                        Data::Empty => "\"\"".to_string(),
+                        _ => datum.encode_for_csv(),
+                    })
+                    .collect();
+                encode_for_csv(&format!("{{{}}}", items.join(",")))
+            }
             Data::Empty => "".to_string(),
         }
     }
@@ -204,9 +431,21 @@ impl std::fmt::Display for Data {
                 Data::String(s) => s.to_string(),
                 Data::Int(n) => n.to_string(),
                 Data::Float(n) => n.to_string(),
+                Data::Decimal(n) => n.to_string(),
+                Data::Bool(b) => b.to_string(),
                 Data::Date(n) => n.to_string(),
                 Data::Time(n) => n.to_string(),
+                Data::DateTime(n) => n.to_string(),
+                Data::Duration(n) => n.to_string(),
+                Data::Blob(n) => n.to_string(),
                 Data::Join(n) => n.to_string(),
+                Data::List(l) => format!(
+                    "{{{}}}",
+                    l.iter()
+                        .map(|datum| datum.to_string())
+                        .collect::<Vec<String>>()
+                        .join(",")
+                ),
                 Data::Empty => "".to_string(),
             }
         )
@@ -220,7 +459,7 @@ impl std::fmt::Debug for Data {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
 
     #[test]
@@ -230,6 +469,7 @@ mod tests {
         assert_eq!(encode_for_csv("a,b"), "\"a,b\"".to_string());
         assert_eq!(encode_for_csv("a\nb"), "\"a\\nb\"".to_string());
         assert_eq!(encode_for_csv("a\"b"), "\"a\"\"b\"".to_string());
+        assert_eq!(encode_for_csv("a\\nb"), "\"a\\\\nb\"".to_string());
     }
 
     #[test]
@@ -251,18 +491,29 @@ mod tests {
 
     #[test]
     fn test_parse() {
-        for d in vec![
+        for d in [
             ("1.1", Data::Float(1.1)),
             ("1", Data::Int(1)),
             ("12:24:00", Data::Time(Time::new(12 * 3600 + 24 * 60))),
             ("12:24", Data::Time(Time::new(12 * 3600 + 24 * 60))),
             ("02:24:00", Data::Time(Time::new(2 * 3600 + 24 * 60))),
             ("02:04", Data::Time(Time::new(2 * 3600 + 4 * 60))),
-            ("2024-01-01", Data::Date(Date::new(2024, 1, 1))),
-            ("1.1.23", Data::Date(Date::new(2023, 1, 1))),
-            ("1.1.", Data::Date(Date::new(2023, 1, 1))),
+            ("2024-01-01", Data::Date(Date::new(2024, 1, 1).unwrap())),
+            (
+                "2024-01-01T12:00:00+01:00",
+                Data::DateTime(DateTime::from_seconds(1704106800)),
+            ),
+            ("1.1.23", Data::Date(Date::new(2023, 1, 1).unwrap())),
+            (
+                "1.1.",
+                Data::Date(Date::new(Date::today().year(), 1, 1).unwrap()),
+            ),
             ("[1,2,3]", Data::Join(Join::new(vec![1, 2, 3]))),
             ("[1]", Data::Join(Join::new(vec![1]))),
+            // durations are only read in duration columns
+            ("37:30", Data::String("37:30".to_string())),
+            ("-0:30", Data::String("-0:30".to_string())),
+            ("2h", Data::String("2h".to_string())),
         ] {
             let left = Data::parse(d.0);
             let right = d.1;
@@ -272,14 +523,19 @@ mod tests {
 
     #[test]
     fn test_display() {
-        for d in vec![
+        for d in [
             (Data::String("a".to_string()), "a"),
             (Data::Int(1), "1"),
             (Data::Float(1.1), "1.1"),
-            (Data::Date(Date::new(2024, 1, 1)), "2024-01-01"),
+            (Data::Bool(true), "true"),
+            (Data::Date(Date::new(2024, 1, 1).unwrap()), "2024-01-01"),
             (Data::Time(Time::new(2 * 3600 + 4 * 60)), "02:04:00"),
             (Data::Join(Join::new(vec![1, 2, 3])), "[1,2,3]"),
             (Data::Join(Join::new(vec![1])), "[1]"),
+            (
+                Data::List(vec![Data::Int(1), Data::String("a".to_string())]),
+                "{1,a}",
+            ),
             (Data::Empty, ""),
         ] {
             let left = d.0.to_string();
@@ -287,4 +543,162 @@ mod tests {
             assert_eq!(left, right);
         }
     }
+
+    #[test]
+    fn test_list_round_trip() {
+        let s = |a: &str| Data::String(a.to_string());
+        for datum in [
+            Data::List(vec![]),
+            Data::List(vec![Data::Empty]),
+            Data::List(vec![Data::Empty, Data::Empty]),
+            Data::List(vec![Data::Int(1), Data::Float(1.5), s("a")]),
+            Data::List(vec![s("hi, there"), s("say \"hi\""), s("line\nbreak")]),
+            Data::List(vec![s("{not a list}"), s("[a,b]")]),
+            Data::List(vec![
+                Data::List(vec![Data::Int(1), Data::Int(2)]),
+                Data::List(vec![s("{"), Data::List(vec![])]),
+            ]),
+            s("{not a list}"),
+        ] {
+            let line = format!("{},x", datum.encode_for_csv());
+            assert_eq!(Data::decode_line(&line), vec![datum, s("x")]);
+        }
+    }
+
+    #[test]
+    fn test_typed_round_trip() {
+        let s = |a: &str| Data::String(a.to_string());
+        for datum in [
+            Data::Empty,
+            s("12:30"),
+            s("2024-01-01"),
+            s("[1]"),
+            s("1"),
+            s("true"),
+            s("back\\slash\\n, \"quoted\"\n"),
+            s("{x}"),
+            s("i:1"),
+            Data::Int(-3),
+            Data::Float(1.0),
+            Data::Float(0.1),
+            Data::Decimal(Decimal::new(-10, 2)),
+            Data::Decimal(Decimal::new(12345, 0)),
+            Data::Bool(false),
+            Data::Date(Date::new(2024, 2, 29).unwrap()),
+            Data::Time(Time::new(45296)),
+            Data::DateTime(DateTime::from_seconds(-86399)),
+            Data::Duration(Duration::from_seconds(-90061)),
+            Data::Duration(Duration::from_seconds(12 * 3600)),
+            Data::Blob(Blob::from_bytes(b"content")),
+            Data::Join(Join::new(vec![])),
+            Data::Join(Join::new(vec![1, 2])),
+            Data::List(vec![
+                Data::Empty,
+                s("a,b"),
+                Data::List(vec![Data::Float(2.0), s("\\{")]),
+            ]),
+        ] {
+            let line = format!("{},{}", datum.encode_typed(), datum.encode_typed());
+            assert_eq!(
+                Data::decode_typed_line(&line).unwrap(),
+                vec![datum.clone(), datum]
+            );
+        }
+        assert!(Data::decode_typed("x:1").is_err());
+        assert!(Data::decode_typed("untagged").is_err());
+    }
+
+    /// random text built from fragments that reach deep into the parsers
+    pub(crate) fn random_text(seed: &mut u64) -> String {
+        const FRAGMENTS: [&str; 46] = [
+            "0",
+            "1",
+            "9",
+            "12",
+            "2024",
+            "99999999999999999999",
+            "9223372036854775807",
+            "-9223372036854775808",
+            "2562047788015215:00",
+            "-",
+            "+",
+            ".",
+            ":",
+            "/",
+            ",",
+            " ",
+            "\\",
+            "\"",
+            "'",
+            "[",
+            "]",
+            "(",
+            ")",
+            "T",
+            "Z",
+            "d",
+            "h",
+            "m",
+            "s",
+            "e",
+            "ü",
+            "€",
+            "+1é",
+            "abs(",
+            "%",
+            "i:",
+            "f:",
+            "dec:",
+            "dt:",
+            "du:",
+            "l:",
+            "j:",
+            "bl:",
+            "||",
+            "true",
+            "*",
+        ];
+        let mut next = || {
+            *seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (*seed >> 33) as usize
+        };
+        let len = next() % 12;
+        (0..len)
+            .map(|_| FRAGMENTS[next() % FRAGMENTS.len()])
+            .collect()
+    }
+
+    #[test]
+    fn test_no_panics_on_random_input() {
+        let date_format = DateFormat::new(&["%d %b %y", "%-m/%-d/%Y"], "%B %-d, %Y").unwrap();
+        let mut seed = 42;
+        for _ in 0..20000 {
+            let s = random_text(&mut seed);
+            let data = Data::parse(&s);
+            let _ = data.to_string();
+            let _ = Data::decode_typed(&data.encode_typed());
+            let _ = Data::parse_with(&s, &date_format);
+            let _ = Data::decode_typed(&s);
+            let _ = Data::decode_line(&s);
+            let _ = split_csv_line(&s);
+            let _ = Data::decode_typed_line(&s);
+            let _ = crate::csv::CsvReader::new(s.as_bytes()).count();
+            let _ = crate::csv::Dialect::sniff(&s);
+            let _ = crate::json::Json::parse(&s);
+            let _ = date_format.parse(&s);
+            let _ = Date::parse(&s);
+            let _ = DateTime::parse(&s);
+            let _ = Time::parse(&s);
+            let _ = Duration::parse(&s);
+            let _ = Decimal::parse(&s);
+            let _ = Blob::parse(&s);
+            let _ = Join::parse(&s);
+            if let Ok(expr) = crate::expr::Expr::parse(&s) {
+                let _ = expr.to_string();
+                let _ = expr.eval(&|_| Some(data.clone()));
+            }
+        }
+    }
 }
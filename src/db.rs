@@ -1,52 +1,98 @@
-use std::error::Error;
+use crate::error::Error;
+use std::collections::HashMap;
+use std::io::Write;
 
+use crate::aggregate::Aggregate;
+use crate::blob::Blob;
+use crate::column_type::ColumnType;
 use crate::condition::Condition;
+use crate::csv::quote_field;
 use crate::data::Data;
+use crate::export::{self, ExportFormat};
+use crate::expr::Expr;
+use crate::import::{self, ImportOptions};
 use crate::join::Join;
+use crate::json::Json;
+#[cfg(feature = "serde")]
+use crate::record::{self, Field};
 use crate::row::Row;
+use crate::storage::{Storage, StorageBackend};
 use crate::table::Table;
-use crate::time::Timestamp;
+use crate::time::{DateFormat, TimeZone, Timestamp};
+#[cfg(feature = "derive")]
+use crate::typed::RzdbTable;
 
 pub struct Db {
     pub name: String,
     pub db_dir: String,
     tables: Vec<Table>,
+    intern_ids: bool,
+    /// maps the typed encoding of a value in `.ids` to the first id storing it
+    ids_index: HashMap<String, i64>,
+    /// zone used to display date and time values
+    timezone: TimeZone,
+    /// zone of the timestamps in backup file names
+    backup_timezone: TimeZone,
+    /// formats of dates inserted as text, and of dates in display() and export_csv()
+    date_format: DateFormat,
+    /// content of blobs stored since the last save, by hash
+    pending_blobs: HashMap<String, Vec<u8>>,
+    /// reads and writes the table files
+    backend: Box<dyn StorageBackend>,
 }
 
 const IDS_TABLE_ID: usize = 0;
+const IDS_REFERENCES_ID: usize = 1;
 const IDS_COLUMN_ID: usize = 2;
 
 impl Db {
-    fn expand_home_dir(path: &str) -> String {
-        if !path.is_empty() {
-            if path.starts_with('~') {
-                let user_home_dir = std::env::var("HOME").unwrap();
-                format!(
-                    "{}{}",
-                    user_home_dir,
-                    path.to_string().chars().skip(1).collect::<String>(),
-                )
-            } else {
-                path.to_string()
-            }
+    fn expand_home_dir(path: &str) -> Result<String, Error> {
+        if let Some(rest) = path.strip_prefix('~') {
+            let user_home_dir = std::env::var("HOME").map_err(|_| {
+                Error::invalid(format!("Db: can not expand {}, HOME is not set", path))
+            })?;
+            Ok(format!("{}{}", user_home_dir, rest))
         } else {
-            "".to_string()
+            Ok(path.to_string())
         }
     }
-    fn path_names(&self) -> (String, String) {
-        let expanded_db_dir = format!("{}/{}", Db::expand_home_dir(&self.db_dir), self.name);
+    fn path_names(&self) -> Result<(String, String), Error> {
+        let expanded_db_dir = format!("{}/{}", Db::expand_home_dir(&self.db_dir)?, self.name);
         let backup_path = format!("{}/backup", &expanded_db_dir);
-        (expanded_db_dir, backup_path)
+        Ok((expanded_db_dir, backup_path))
     }
-    pub fn get_db_path(&self) -> String {
+    /// the db directory with `~` expanded to the home directory
+    /// ```
+    /// use rzdb::{Db, Error};
+    /// let db = Db::create("test", "~/.local/rzdb").unwrap();
+    /// assert!(db.get_db_path().unwrap().ends_with("/.local/rzdb"));
+    /// std::env::remove_var("HOME");
+    /// assert!(matches!(db.get_db_path(), Err(Error::InvalidValue(_))));
+    /// ```
+    pub fn get_db_path(&self) -> Result<String, Error> {
         Db::expand_home_dir(&self.db_dir)
     }
 
+    /// the name of a directory entry, which must be valid UTF-8
+    fn file_name(path: &std::path::Path) -> Result<String, Error> {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .map(|name| name.to_string())
+            .ok_or_else(|| Error::invalid(format!("Db: invalid file name {}", path.display())))
+    }
+
     fn new(name: &str, db_dir: &str) -> Db {
         let mut db = Db {
             name: name.to_string(),
             db_dir: db_dir.to_string(),
             tables: vec![],
+            intern_ids: false,
+            ids_index: HashMap::new(),
+            timezone: TimeZone::local(),
+            backup_timezone: TimeZone::local(),
+            date_format: DateFormat::default(),
+            pending_blobs: HashMap::new(),
+            backend: Storage::Csv.backend(),
         };
         db.create_table(".ids").unwrap();
         db.create_column(".ids", "id").unwrap();
@@ -54,42 +100,79 @@ impl Db {
         db.create_column(".ids", "content").unwrap();
         db
     }
-    pub fn create(name: &str, db_dir: &str) -> Result<Db, Box<dyn Error>> {
+    pub fn create(name: &str, db_dir: &str) -> Result<Db, Error> {
         Ok(Db::new(name, db_dir))
     }
 
-    fn table_filename(&self, table_name: &str) -> String {
-        let (full_path, _) = self.path_names();
-        format!("{}/{}.csv", full_path, table_name)
+    fn table_filename(&self, table_name: &str) -> Result<String, Error> {
+        let (full_path, _) = self.path_names()?;
+        Ok(format!(
+            "{}/{}.{}",
+            full_path,
+            table_name,
+            self.backend.extension()
+        ))
+    }
+
+    /// Loads a db. Its storage is binary if there is a `.ids.rzt` file, csv otherwise,
+    /// only table files of that storage are loaded.
+    pub fn load(name: &str, db_dir: &str) -> Result<Db, Error> {
+        let ids_filename = format!(
+            "{}/{}/.ids.{}",
+            Db::expand_home_dir(db_dir)?,
+            name,
+            Storage::Binary.extension()
+        );
+        let storage = if std::path::Path::new(&ids_filename).exists() {
+            Storage::Binary
+        } else {
+            Storage::Csv
+        };
+        Db::load_with_backend(name, db_dir, storage.backend())
     }
 
-    pub fn load(name: &str, db_dir: &str) -> Result<Db, Box<dyn Error>> {
+    /// Loads a db with the table files of a storage backend, see `StorageBackend`.
+    pub fn load_with_backend(
+        name: &str,
+        db_dir: &str,
+        backend: Box<dyn StorageBackend>,
+    ) -> Result<Db, Error> {
         let mut db = Db {
             name: name.to_string(),
             db_dir: db_dir.to_string(),
             tables: vec![],
+            intern_ids: false,
+            ids_index: HashMap::new(),
+            timezone: TimeZone::local(),
+            backup_timezone: TimeZone::local(),
+            date_format: DateFormat::default(),
+            pending_blobs: HashMap::new(),
+            backend,
         };
-        let (full_path, _) = db.path_names();
-        let ids_file_name = format!("{}/.ids.csv", &full_path);
+        let (full_path, _) = db.path_names()?;
+        let ids_file_name = db.table_filename(".ids")?;
         if !std::path::Path::new(&ids_file_name).exists() {
-            db.create_table(".ids").unwrap();
-            db.create_column(".ids", "id").unwrap();
-            db.create_column(".ids", "references").unwrap();
-            db.create_column(".ids", "content").unwrap();
+            db.create_table(".ids")?;
+            db.create_column(".ids", "id")?;
+            db.create_column(".ids", "references")?;
+            db.create_column(".ids", "content")?;
         } else {
-            let ids_table = Table::load(&ids_file_name)?;
+            let ids_table = db.backend.load(&ids_file_name)?;
             db.tables.push(ids_table);
         }
+        db.build_ids_index();
 
         // load all other tables
+        let extension = format!(".{}", db.backend.extension());
+        let ids_filename = format!(".ids{}", extension);
         for entry in std::fs::read_dir(&full_path)? {
             let entry = entry?;
             let path = entry.path();
-            let filename = path.file_name().unwrap().to_str().unwrap();
-            if filename != ".ids" {
+            let filename = Db::file_name(&path)?;
+            if filename != ids_filename {
                 let full_filename = format!("{}/{}", &full_path, filename);
-                if filename.ends_with(".csv") {
-                    let table = Table::load(&full_filename)?;
+                if filename.ends_with(&extension) {
+                    let table = db.backend.load(&full_filename)?;
                     db.tables.push(table);
                 }
             }
@@ -97,38 +180,209 @@ impl Db {
         Ok(db)
     }
 
-    pub fn save(&mut self) -> Result<(), Box<dyn Error>> {
-        let (full_path, backup_path) = self.path_names();
-        std::fs::create_dir_all(&full_path).unwrap();
-        std::fs::create_dir_all(&backup_path).unwrap();
+    pub fn save(&mut self) -> Result<(), Error> {
+        let (full_path, backup_path) = self.path_names()?;
+        std::fs::create_dir_all(&full_path)?;
+        std::fs::create_dir_all(&backup_path)?;
 
-        for table in &mut self.tables {
+        // blobs are written before the tables that reference them
+        if !self.pending_blobs.is_empty() {
+            let blobs_path = self.blobs_path()?;
+            std::fs::create_dir_all(&blobs_path)?;
+            for (hash, bytes) in &self.pending_blobs {
+                let filename = format!("{}/{}", blobs_path, hash);
+                if std::fs::metadata(&filename).is_err() {
+                    // write to a temporary file first, a blob file is always complete
+                    let tmp_filename = format!("{}.tmp", filename);
+                    std::fs::write(&tmp_filename, bytes)?;
+                    std::fs::rename(&tmp_filename, &filename)?;
+                }
+            }
+        }
+
+        // `.ids` is saved last, and files in another storage are only moved to the backup
+        // once all tables are written: until then a db is loaded with its previous storage
+        let extension = self.backend.extension();
+        let timestamp = Timestamp::now().to_filename_string_in(&self.backup_timezone);
+        let filename =
+            |name: &str, extension: &str| format!("{}/{}.{}", &full_path, name, extension);
+        let backup_filename = |name: &str, extension: &str| {
+            format!("{}/{}-{}.{}", &backup_path, name, timestamp, extension)
+        };
+        let mut saved = vec![];
+        let (ids, tables) = self.tables.split_at_mut(IDS_TABLE_ID + 1);
+        for table in tables.iter_mut().chain(ids) {
             if table.get_name() != "." && table.is_changed() {
-                let filename = format!("{}/{}.csv", &full_path, table.get_name());
+                let name = table.get_name();
+                if std::fs::metadata(filename(&name, extension)).is_ok() {
+                    std::fs::copy(
+                        filename(&name, extension),
+                        backup_filename(&name, extension),
+                    )?;
+                }
+                self.backend.save(table, &filename(&name, extension))?;
+                table.set_changed(false);
+                saved.push(name);
+            }
+        }
+        for name in saved {
+            for other in Storage::all() {
+                let other = other.extension();
+                if other != extension && std::fs::metadata(filename(&name, other)).is_ok() {
+                    std::fs::rename(filename(&name, other), backup_filename(&name, other))?;
+                }
+            }
+        }
 
-                let timestamp = Timestamp::now().to_filename_string();
-                let backup_filename =
-                    format!("{}/{}-{}.csv", &backup_path, table.get_name(), timestamp);
+        // blobs are only forgotten once the tables referencing them are saved
+        self.pending_blobs.clear();
 
-                if std::fs::metadata(&filename).is_ok() {
-                    std::fs::copy(&filename, &backup_filename)?;
-                }
+        Ok(())
+    }
 
-                table.save(&filename)?;
+    fn blobs_path(&self) -> Result<String, Error> {
+        let (full_path, _) = self.path_names()?;
+        Ok(format!("{}/blobs", full_path))
+    }
+
+    /// Stores binary content as a blob and returns the value referencing it, to be inserted
+    /// with insert_data() or set_at(). The content is written to the `blobs` directory of the
+    /// db on the next save().
+    /// ```
+    /// use rzdb::{Data, Db};
+    /// let mut db = Db::create("test_store_blob", "~/.local/rzdb").unwrap();
+    /// let table_name = "store_blob";
+    /// db.create_or_replace_table(table_name).unwrap();
+    /// db.create_column(table_name, "name").unwrap();
+    /// db.create_column(table_name, "content").unwrap();
+    /// let blob = db.store_blob(vec![0, 159, 146, 150]);
+    /// db.insert_data(table_name, vec![Data::parse("raw"), blob]).unwrap();
+    /// let text = db.store_text("a long document");
+    /// db.insert_data(table_name, vec![Data::parse("doc"), text.clone()]).unwrap();
+    /// db.save().unwrap();
+    ///
+    /// let db = Db::load("test_store_blob", "~/.local/rzdb").unwrap();
+    /// let rows = db.select_from(table_name).unwrap();
+    /// assert_eq!(db.read_blob(&rows[0].select_at(1).unwrap()).unwrap(), vec![0, 159, 146, 150]);
+    /// assert_eq!(db.read_text(&rows[1].select_at(1).unwrap()).unwrap(), "a long document");
+    /// assert_eq!(rows[1].select_at(1).unwrap(), text);
+    /// ```
+    pub fn store_blob(&mut self, bytes: Vec<u8>) -> Data {
+        let blob = Blob::from_bytes(&bytes);
+        self.pending_blobs.insert(blob.hash().to_string(), bytes);
+        Data::Blob(blob)
+    }
+
+    /// stores text as a blob, see store_blob()
+    pub fn store_text(&mut self, text: &str) -> Data {
+        self.store_blob(text.as_bytes().to_vec())
+    }
+
+    /// Reads the content of a blob value. Content read from the blobs directory is checked
+    /// against the hash of the blob.
+    pub fn read_blob(&self, datum: &Data) -> Result<Vec<u8>, Error> {
+        let blob = match datum {
+            Data::Blob(blob) => blob,
+            _ => {
+                return Err(Error::invalid(format!(
+                    "Db::read_blob({}): not a blob",
+                    datum
+                )))
             }
+        };
+        if let Some(bytes) = self.pending_blobs.get(blob.hash()) {
+            return Ok(bytes.clone());
         }
+        let bytes = std::fs::read(format!("{}/{}", self.blobs_path()?, blob.hash()))?;
+        if Blob::from_bytes(&bytes) != *blob {
+            return Err(Error::invalid(format!(
+                "Db::read_blob({}): content does not match its hash",
+                blob
+            )));
+        }
+        Ok(bytes)
+    }
 
-        Ok(())
+    /// reads a blob stored with store_text()
+    pub fn read_text(&self, datum: &Data) -> Result<String, Error> {
+        Ok(String::from_utf8(self.read_blob(datum)?)?)
+    }
+
+    /// Saves the db and deletes the blobs that are not referenced by any table, including
+    /// blobs stored since the last save, and returns how many were deleted. The db is saved
+    /// first so that the saved tables never reference a deleted blob; backups of tables may
+    /// still do.
+    /// ```
+    /// use rzdb::{Data, Db};
+    /// let mut db = Db::create("test_gc_blobs", "~/.local/rzdb").unwrap();
+    /// let table_name = "gc_blobs";
+    /// db.create_or_replace_table(table_name).unwrap();
+    /// db.create_column(table_name, "content").unwrap();
+    /// let kept = db.store_text("kept");
+    /// db.store_text("never used");
+    /// db.insert_data(table_name, vec![Data::List(vec![kept.clone()])]).unwrap();
+    /// let replaced = db.store_text("replaced");
+    /// db.insert_data(table_name, vec![replaced]).unwrap();
+    /// db.save().unwrap();
+    /// db.delete_row_at(table_name, 1).unwrap();
+    /// assert_eq!(db.gc_blobs().unwrap(), 2);
+    /// assert_eq!(db.gc_blobs().unwrap(), 0);
+    /// assert_eq!(db.read_text(&kept).unwrap(), "kept");
+    /// // the deletion of the row was saved with the collection
+    /// let db = Db::load("test_gc_blobs", "~/.local/rzdb").unwrap();
+    /// assert_eq!(db.select_from(table_name).unwrap().len(), 1);
+    /// ```
+    pub fn gc_blobs(&mut self) -> Result<usize, Error> {
+        fn collect(datum: &Data, hashes: &mut std::collections::HashSet<String>) {
+            match datum {
+                Data::Blob(blob) => {
+                    hashes.insert(blob.hash().to_string());
+                }
+                Data::List(list) => list.iter().for_each(|datum| collect(datum, hashes)),
+                _ => {}
+            }
+        }
+        let mut referenced = std::collections::HashSet::new();
+        for table in &self.tables {
+            for row in table.select() {
+                for datum in row.iter() {
+                    collect(datum, &mut referenced);
+                }
+            }
+        }
+
+        let mut count = 0;
+        self.pending_blobs.retain(|hash, _| {
+            let keep = referenced.contains(hash);
+            if !keep {
+                count += 1;
+            }
+            keep
+        });
+        self.save()?;
+        let blobs_path = self.blobs_path()?;
+        if std::path::Path::new(&blobs_path).exists() {
+            for entry in std::fs::read_dir(&blobs_path)? {
+                let path = entry?.path();
+                let filename = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+                if !referenced.contains(filename) && Blob::parse(&format!("{}:0", filename)).is_ok()
+                {
+                    std::fs::remove_file(&path)?;
+                    count += 1;
+                }
+            }
+        }
+        Ok(count)
     }
-    pub fn get_database_names(&self) -> Result<Vec<String>, Box<dyn Error>> {
+    pub fn get_database_names(&self) -> Result<Vec<String>, Error> {
         // list directories in db_dir
         let mut db_names = Vec::new();
-        let db_dir = Db::expand_home_dir(&self.db_dir);
+        let db_dir = Db::expand_home_dir(&self.db_dir)?;
         for entry in std::fs::read_dir(&db_dir)? {
             let entry = entry?;
             let path = entry.path();
             if path.is_dir() {
-                db_names.push(path.file_name().unwrap().to_str().unwrap().to_string());
+                db_names.push(Db::file_name(&path)?);
             }
         }
         Ok(db_names)
@@ -136,27 +390,24 @@ impl Db {
     pub fn get_table_names(&self) -> Vec<String> {
         self.tables.iter().map(|t| t.get_name()).collect()
     }
-    pub fn to_string(&self, table_name: &str) -> Result<String, Box<dyn Error>> {
+    pub fn to_string(&self, table_name: &str) -> Result<String, Error> {
         let id = self.get_table_id(table_name)?;
         Ok(self.tables[id].to_string())
     }
     pub fn get_name(&self) -> String {
         self.name.clone()
     }
-    pub fn create_table(&mut self, table_name: &str) -> Result<(), Box<dyn Error>> {
+    pub fn create_table(&mut self, table_name: &str) -> Result<(), Error> {
         for table in &mut self.tables {
             if table.get_name() == table_name {
-                return Err(Box::new(std::io::Error::new(
-                    std::io::ErrorKind::AlreadyExists,
-                    "Table already exists",
-                )));
+                return Err(Error::TableExists(table_name.to_string()));
             }
         }
         self.tables.push(Table::create(table_name));
         Ok(())
     }
 
-    pub fn create_or_replace_table(&mut self, table_name: &str) -> Result<(), Box<dyn Error>> {
+    pub fn create_or_replace_table(&mut self, table_name: &str) -> Result<(), Error> {
         for table in &mut self.tables {
             if table.get_name() == table_name {
                 table.delete_all();
@@ -168,9 +419,9 @@ impl Db {
     }
 
     /// saves the table to backup, removes the table from the database in memory and deletes the file
-    pub fn drop_table(&mut self, table_name: &str) -> Result<(), Box<dyn Error>> {
+    pub fn drop_table(&mut self, table_name: &str) -> Result<(), Error> {
         // save database, ignore error if it fails
-        if self.save().is_err() {}
+        let _ = self.save();
 
         for (idx, table) in self.tables.iter().enumerate() {
             if table.get_name() == table_name {
@@ -180,18 +431,98 @@ impl Db {
         }
 
         // remove filename
-        let filename = self.table_filename(table_name);
+        let filename = self.table_filename(table_name)?;
         std::fs::remove_file(&filename)?;
         Ok(())
     }
 
-    pub fn create_column(
+    pub fn create_column(&mut self, table_name: &str, column_name: &str) -> Result<(), Error> {
+        let id = self.get_table_id(table_name)?;
+        self.tables[id].create_column(column_name)
+    }
+
+    /// creates a column that only accepts values of the given type
+    /// ```
+    /// use rzdb::{ColumnType, Condition, Data, Db, Error};
+    /// let mut db = Db::create("test", "~/.local/rzdb").unwrap();
+    /// let table_name = "create_typed_column";
+    /// db.create_or_replace_table(table_name).unwrap();
+    /// db.create_column(table_name, "task").unwrap();
+    /// db.create_typed_column(table_name, "done", ColumnType::Bool).unwrap();
+    /// db.insert(table_name, vec!["write docs", "yes"]).unwrap();
+    /// db.insert(table_name, vec!["fix bug", "false"]).unwrap();
+    /// match db.insert(table_name, vec!["review", "maybe"]) {
+    ///     Err(Error::TypeMismatch { column, value, .. }) => assert_eq!((column, value), ("done".into(), "maybe".into())),
+    ///     other => panic!("{:?}", other),
+    /// }
+    /// let rows = db.select_where(table_name, &[Condition::equal_bool("done", true)]).unwrap();
+    /// assert_eq!(rows.len(), 1);
+    /// assert_eq!(rows[0].select_at(1).unwrap(), Data::Bool(true));
+    /// ```
+    pub fn create_typed_column(
         &mut self,
         table_name: &str,
         column_name: &str,
-    ) -> Result<(), Box<dyn Error>> {
+        column_type: ColumnType,
+    ) -> Result<(), Error> {
         let id = self.get_table_id(table_name)?;
-        self.tables[id].create_column(column_name)
+        self.tables[id].create_typed_column(column_name, column_type)
+    }
+
+    /// Adds a column computed from the other columns of a row by an expression, see `Expr`.
+    /// Virtual columns are evaluated whenever rows are selected, e.g. for `today()`.
+    /// Materialized columns store their values, which are recomputed whenever the row
+    /// changes. Computed columns can not be set, values inserted for them must be empty.
+    /// ```
+    /// use rzdb::{Data, Db, Decimal};
+    /// let mut db = Db::create("test", "~/.local/rzdb").unwrap();
+    /// let table_name = "create_computed_column";
+    /// db.create_or_replace_table(table_name).unwrap();
+    /// db.create_column(table_name, "qty").unwrap();
+    /// db.create_column(table_name, "price").unwrap();
+    /// db.create_computed_column(table_name, "total", "qty * price", true).unwrap();
+    /// db.create_computed_column(table_name, "label", "qty || ' x ' || price", false).unwrap();
+    /// db.insert(table_name, vec!["3", "4", "", ""]).unwrap();
+    /// let rows = db.select_from(table_name).unwrap();
+    /// assert_eq!(rows[0].select_at(2).unwrap(), Data::Int(12));
+    /// assert_eq!(rows[0].select_at(3).unwrap(), Data::String("3 x 4".to_string()));
+    ///
+    /// db.set_at(table_name, 0, 0, Data::Int(5)).unwrap();
+    /// let rows = db.select_from(table_name).unwrap();
+    /// assert_eq!(rows[0].select_at(2).unwrap(), Data::Int(20));
+    /// assert_eq!(rows[0].select_at(3).unwrap(), Data::String("5 x 4".to_string()));
+    /// assert!(db.set_at(table_name, 0, 2, Data::Int(1)).is_err());
+    /// assert!(db.insert(table_name, vec!["1", "2", "3", ""]).is_err());
+    /// assert!(db.create_computed_column(table_name, "bad", "missing + 1", false).is_err());
+    /// ```
+    pub fn create_computed_column(
+        &mut self,
+        table_name: &str,
+        column_name: &str,
+        expression: &str,
+        materialized: bool,
+    ) -> Result<(), Error> {
+        let id = self.get_table_id(table_name)?;
+        let expr = Expr::parse(expression)?;
+        self.tables[id].create_computed_column(column_name, expr, materialized)
+    }
+
+    /// the expression of a computed column as text, None for other columns
+    pub fn get_column_expression(
+        &self,
+        table_name: &str,
+        column_name: &str,
+    ) -> Result<Option<String>, Error> {
+        let id = self.get_table_id(table_name)?;
+        let column_idx = self.tables[id].get_column_idx_result(column_name)?;
+        Ok(self.tables[id]
+            .get_column_expression(column_idx)
+            .map(|expr| expr.to_string()))
+    }
+
+    pub fn get_column_types(&self, table_name: &str) -> Result<Vec<ColumnType>, Error> {
+        let id = self.get_table_id(table_name)?;
+        Ok(self.tables[id].get_column_types())
     }
 
     pub fn rename_column(
@@ -199,14 +530,14 @@ impl Db {
         table_name: &str,
         old_name: &str,
         new_name: &str,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), Error> {
         let id = self.get_table_id(table_name)?;
         self.tables[id].rename_column(old_name, new_name)
     }
 
-    pub fn insert(&mut self, table_name: &str, values: Vec<&str>) -> Result<(), Box<dyn Error>> {
+    pub fn insert(&mut self, table_name: &str, values: Vec<&str>) -> Result<(), Error> {
         let id = self.get_table_id(table_name)?;
-        self.tables[id].insert(values)
+        self.tables[id].insert(values, &self.date_format)
     }
 
     /// insert a row at the specified index
@@ -231,16 +562,12 @@ impl Db {
         table_name: &str,
         values: Vec<&str>,
         idx: usize,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), Error> {
         let id = self.get_table_id(table_name)?;
-        self.tables[id].insert_at(values, idx)
+        self.tables[id].insert_at(values, idx, &self.date_format)
     }
 
-    pub fn insert_data(
-        &mut self,
-        table_name: &str,
-        values: Vec<Data>,
-    ) -> Result<(), Box<dyn Error>> {
+    pub fn insert_data(&mut self, table_name: &str, values: Vec<Data>) -> Result<(), Error> {
         let id = self.get_table_id(table_name)?;
         self.tables[id].insert_data(values)
     }
@@ -250,30 +577,23 @@ impl Db {
         table_name: &str,
         column_name: &str,
         index: usize,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), Error> {
         let id = self.get_table_id(table_name)?;
-        self.tables[id].insert_column_at(column_name, index);
-        Ok(())
+        self.tables[id].insert_column_at(column_name, index)
     }
 
-    pub fn insert_empty_row_at(
-        &mut self,
-        table_name: &str,
-        index: usize,
-    ) -> Result<(), Box<dyn Error>> {
+    pub fn insert_empty_row_at(&mut self, table_name: &str, index: usize) -> Result<(), Error> {
         let id = self.get_table_id(table_name)?;
-        self.tables[id].insert_empty_row_at(index);
-        Ok(())
+        self.tables[id].insert_empty_row_at(index)
     }
     pub fn insert_rows_at(
         &mut self,
         table_name: &str,
         index: usize,
         rows: Vec<Row>,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), Error> {
         let id = self.get_table_id(table_name)?;
-        self.tables[id].insert_rows_at(index, rows);
-        Ok(())
+        self.tables[id].insert_rows_at(index, rows)
     }
     /// insert all of source_table's rows into dest_table at index
     pub fn insert_into_at(
@@ -281,13 +601,12 @@ impl Db {
         source_table: &str,
         dest_table: &str,
         row_idx: usize,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), Error> {
         let source_id = self.get_table_id(source_table)?;
         let dest_id = self.get_table_id(dest_table)?;
         let source_table = &self.tables[source_id];
         let rows = source_table.select();
-        self.tables[dest_id].insert_into_at(row_idx, rows);
-        Ok(())
+        self.tables[dest_id].insert_into_at(row_idx, rows)
     }
     /// insert all of source_table's columns into dest_table at index
     /// all columns must be unique, no duplicates allowed
@@ -296,7 +615,7 @@ impl Db {
         source_table: &str,
         dest_table: &str,
         col_idx: usize,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), Error> {
         let source_id = self.get_table_id(source_table)?;
         let dest_id = self.get_table_id(dest_table)?;
         let (source_table, dest_table) = if source_id < dest_id {
@@ -339,9 +658,47 @@ impl Db {
         table_name: &str,
         values: Vec<&str>,
         conditions: &[Condition],
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), Error> {
         let id = self.get_table_id(table_name)?;
-        self.tables[id].insert_update_where(values, conditions)
+        self.tables[id].insert_update_where(values, conditions, &self.date_format)
+    }
+
+    /// sets columns of all rows that match the conditions, leaving the other columns
+    /// untouched; returns the number of updated rows. A constant is set with
+    /// `Expr::Literal`.
+    /// ```
+    /// use rzdb::{Db,Condition,Data,Expr};
+    /// let mut db = Db::create("test", "~/.local/rzdb").unwrap();
+    /// let table_name = "update_where";
+    /// db.create_or_replace_table(table_name).unwrap();
+    /// db.create_column(table_name, "name").unwrap();
+    /// db.create_column(table_name, "stock").unwrap();
+    /// db.create_column(table_name, "note").unwrap();
+    /// db.insert(table_name, vec!["apple", "3", ""]).unwrap();
+    /// db.insert(table_name, vec!["pear", "0", ""]).unwrap();
+    /// db.insert(table_name, vec!["plum", "8", ""]).unwrap();
+    /// let assignments = [
+    ///     ("stock", Expr::parse("stock + 10").unwrap()),
+    ///     ("note", Expr::Literal(Data::String("reordered".into()))),
+    /// ];
+    /// let cond = Condition::parse("stock < 5").unwrap();
+    /// assert_eq!(db.update_where(table_name, &assignments, &[cond]).unwrap(), 2);
+    /// let r = db.select_from(table_name).unwrap();
+    /// assert_eq!(r[0].select_at(1).unwrap(), Data::Int(13));
+    /// assert_eq!(r[1].select_at(2).unwrap(), Data::String("reordered".into()));
+    /// assert_eq!(r[2].select_at(1).unwrap(), Data::Int(8));
+    /// assert_eq!(r[2].select_at(2).unwrap(), Data::Empty);
+    /// let cond = Condition::equal_string("name", "kiwi");
+    /// assert_eq!(db.update_where(table_name, &assignments, &[cond]).unwrap(), 0);
+    /// ```
+    pub fn update_where(
+        &mut self,
+        table_name: &str,
+        assignments: &[(&str, Expr)],
+        conditions: &[Condition],
+    ) -> Result<usize, Error> {
+        let id = self.get_table_id(table_name)?;
+        self.tables[id].update_where(assignments, conditions)
     }
 
     /// delete all rows that match the conditions
@@ -367,27 +724,18 @@ impl Db {
         &mut self,
         table_name: &str,
         conditions: &[Condition],
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), Error> {
         let id = self.get_table_id(table_name)?;
         self.tables[id].delete_where(conditions)?;
         Ok(())
     }
 
-    pub fn delete_row_at(
-        &mut self,
-        table_name: &str,
-        row_idx: usize,
-    ) -> Result<(), Box<dyn Error>> {
+    pub fn delete_row_at(&mut self, table_name: &str, row_idx: usize) -> Result<(), Error> {
         let id = self.get_table_id(table_name)?;
-        self.tables[id].delete_row(row_idx);
-        Ok(())
+        self.tables[id].delete_row(row_idx)
     }
 
-    pub fn delete_column(
-        &mut self,
-        table_name: &str,
-        column_name: &str,
-    ) -> Result<(), Box<dyn Error>> {
+    pub fn delete_column(&mut self, table_name: &str, column_name: &str) -> Result<(), Error> {
         let id = self.get_table_id(table_name)?;
         self.tables[id].delete_column(column_name)
     }
@@ -396,32 +744,349 @@ impl Db {
         self.get_table_id(table_name).is_ok()
     }
 
-    pub fn get_table_id(&self, name: &str) -> Result<usize, Box<dyn Error>> {
+    pub fn get_table_id(&self, name: &str) -> Result<usize, Error> {
         for (idx, table) in self.tables.iter().enumerate() {
             if table.get_name() == name {
                 return Ok(idx);
             }
         }
-        Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::NotFound,
-            format!("Table {} not found", name),
-        )))
+        Err(Error::TableNotFound(name.to_string()))
+    }
+
+    /// Enables or disables interning of values stored with `store_ids`.
+    /// With interning enabled, storing a value whose content already exists in `.ids`
+    /// reuses the existing id and increments its reference count instead of adding a new entry.
+    /// ```
+    /// use rzdb::Db;
+    /// let mut db = Db::create("test", "~/.local/rzdb").unwrap();
+    /// db.set_intern_ids(true);
+    /// let first = db.store_ids(vec!["urgent", "later"]).unwrap();
+    /// let second = db.store_ids(vec!["urgent"]).unwrap();
+    /// assert_eq!(first.as_join().unwrap().ids[0], second.as_join().unwrap().ids[0]);
+    /// assert_eq!(db.get_row_count(".ids").unwrap(), 2);
+    /// ```
+    pub fn set_intern_ids(&mut self, intern_ids: bool) {
+        self.intern_ids = intern_ids;
+    }
+
+    pub fn is_interning_ids(&self) -> bool {
+        self.intern_ids
+    }
+
+    /// Sets the file format of the tables. Changing it marks all tables as changed, so the
+    /// next save() writes them in the new format and moves the files in the old format to
+    /// the backup directory.
+    /// ```
+    /// use rzdb::{Data, Db, Storage};
+    /// let mut db = Db::create("test_set_storage", "~/.local/rzdb").unwrap();
+    /// let table_name = "set_storage";
+    /// db.create_or_replace_table(table_name).unwrap();
+    /// db.create_column(table_name, "name").unwrap();
+    /// db.create_column(table_name, "tags").unwrap();
+    /// let tags = db.store_ids(vec!["a", "b"]).unwrap();
+    /// db.insert_data(table_name, vec![Data::parse("x"), tags]).unwrap();
+    /// db.set_storage(Storage::Binary);
+    /// db.save().unwrap();
+    ///
+    /// let db = Db::load("test_set_storage", "~/.local/rzdb").unwrap();
+    /// assert_eq!(db.get_storage(), Some(Storage::Binary));
+    /// let tags = db.select_at(table_name, 1, 0).unwrap();
+    /// assert_eq!(db.from_ids(tags).unwrap(), vec![Data::parse("a"), Data::parse("b")]);
+    ///
+    /// Db::convert_storage("test_set_storage", "~/.local/rzdb", Storage::Csv).unwrap();
+    /// let db = Db::load("test_set_storage", "~/.local/rzdb").unwrap();
+    /// assert_eq!(db.get_storage(), Some(Storage::Csv));
+    /// assert_eq!(db.select_at(table_name, 0, 0).unwrap(), Data::parse("x"));
+    /// ```
+    pub fn set_storage(&mut self, storage: Storage) {
+        self.set_backend(storage.backend());
+    }
+
+    /// the built-in storage with the file extension of the backend, None for other backends
+    pub fn get_storage(&self) -> Option<Storage> {
+        Storage::all()
+            .into_iter()
+            .find(|storage| storage.extension() == self.backend.extension())
+    }
+
+    /// Sets the backend reading and writing the table files, such as one of
+    /// `Storage::backend()` or an own `StorageBackend`. As with set_storage(), a backend
+    /// with another file extension makes the next save() write all tables.
+    pub fn set_backend(&mut self, backend: Box<dyn StorageBackend>) {
+        if backend.extension() != self.backend.extension() {
+            for table in &mut self.tables {
+                table.set_changed(true);
+            }
+        }
+        self.backend = backend;
+    }
+
+    /// Converts the tables of a saved db to another file format, see set_storage().
+    pub fn convert_storage(name: &str, db_dir: &str, storage: Storage) -> Result<(), Error> {
+        let mut db = Db::load(name, db_dir)?;
+        db.set_storage(storage);
+        db.save()
+    }
+
+    fn build_ids_index(&mut self) {
+        self.ids_index.clear();
+        for (idx, row) in self.tables[IDS_TABLE_ID].select().iter().enumerate() {
+            if let Ok(datum) = row.select_at(IDS_COLUMN_ID) {
+                self.ids_index
+                    .entry(datum.encode_typed())
+                    .or_insert(idx as i64);
+            }
+        }
+    }
+
+    fn get_id_references(&self, id: i64) -> Result<i64, Error> {
+        match self.tables[IDS_TABLE_ID].select_at(IDS_REFERENCES_ID, id as usize)? {
+            Data::Int(n) => Ok(n),
+            _ => Ok(0),
+        }
     }
 
-    pub fn store_ids(&mut self, values: Vec<&str>) -> Result<Data, Box<dyn Error>> {
-        let data = Data::parse_multi(&values);
-        let table_ids = self.get_table_id(".ids")?;
+    fn set_id_references(&mut self, id: i64, references: i64) -> Result<(), Error> {
+        self.tables[IDS_TABLE_ID].set_at(id as usize, IDS_REFERENCES_ID, Data::Int(references))
+    }
+
+    /// stores a single value in `.ids` and returns its id, reusing an id with the same
+    /// content if interning is enabled
+    fn store_id(&mut self, datum: Data) -> Result<i64, Error> {
+        let key = datum.encode_typed();
+        if self.intern_ids {
+            if let Some(&id) = self.ids_index.get(&key) {
+                let references = self.get_id_references(id)?;
+                self.set_id_references(id, references + 1)?;
+                return Ok(id);
+            }
+        }
+        // ids are never reused: a join copied elsewhere may still hold a released id
+        let id = self.tables[IDS_TABLE_ID].len() as i64;
+        let line = vec![Data::Int(id), Data::Int(1), datum];
+        self.tables[IDS_TABLE_ID].insert_data(line)?;
+        self.ids_index.entry(key).or_insert(id);
+        Ok(id)
+    }
+
+    /// drops one reference to an id in `.ids`; the value stays, ids are never reused
+    fn release_id(&mut self, id: i64) -> Result<(), Error> {
+        let references = self.get_id_references(id)?;
+        self.set_id_references(id, (references - 1).max(0))
+    }
+
+    pub fn store_ids(&mut self, values: Vec<&str>) -> Result<Data, Error> {
         let mut ids = vec![];
-        for datum in &data {
-            let new_id = self.tables[table_ids].len();
-            ids.push(new_id as i64);
-            let line = vec![Data::Int(new_id as i64), Data::Int(1), datum.clone()];
-            self.tables[table_ids].insert_data(line)?;
+        for value in values {
+            let datum = Data::parse_with(value, &self.date_format);
+            ids.push(self.store_id(datum)?);
         }
         Ok(Data::Join(Join::new(ids)))
     }
 
-    pub fn from_ids(&self, datum: Data) -> Result<Vec<Data>, Box<dyn Error>> {
+    fn get_join_at(
+        &self,
+        table_id: usize,
+        row_idx: usize,
+        column_idx: usize,
+    ) -> Result<Join, Error> {
+        match self.tables[table_id].select_at(column_idx, row_idx)? {
+            Data::Join(join) => Ok(join),
+            Data::Empty => Ok(Join::new(vec![])),
+            other => Err(Error::invalid(format!(
+                "Db::get_join_at({}, {}, {}): {} is not a join",
+                self.tables[table_id].get_name(),
+                row_idx,
+                column_idx,
+                other,
+            ))),
+        }
+    }
+
+    fn set_join_at(
+        &mut self,
+        table_id: usize,
+        row_idx: usize,
+        column_idx: usize,
+        join: Join,
+    ) -> Result<(), Error> {
+        let value = if join.ids.is_empty() {
+            Data::Empty
+        } else {
+            Data::Join(join)
+        };
+        self.tables[table_id].set_at(row_idx, column_idx, value)
+    }
+
+    fn join_position_error(join: &Join, position: usize) -> Error {
+        Error::IndexOutOfBounds {
+            context: "join position".to_string(),
+            index: position,
+            len: join.ids.len(),
+        }
+    }
+
+    /// appends a value to the join stored at the given cell; an empty cell becomes a new join
+    /// ```
+    /// use rzdb::Db;
+    /// let mut db = Db::create("test", "~/.local/rzdb").unwrap();
+    /// let table_name = "join_push";
+    /// db.create_or_replace_table(table_name).unwrap();
+    /// db.create_column(table_name, "tags").unwrap();
+    /// let tags = db.store_ids(vec!["urgent"]).unwrap();
+    /// db.insert_data(table_name, vec![tags]).unwrap();
+    /// db.join_push(table_name, 0, 0, "later").unwrap();
+    /// let rows = db.select_array(table_name).unwrap();
+    /// assert_eq!(rows[0][0].len(), 2);
+    /// assert_eq!(rows[0][0][1].to_string(), "later");
+    /// ```
+    pub fn join_push(
+        &mut self,
+        table_name: &str,
+        row_idx: usize,
+        column_idx: usize,
+        value: &str,
+    ) -> Result<(), Error> {
+        let id = self.get_table_id(table_name)?;
+        let mut join = self.get_join_at(id, row_idx, column_idx)?;
+        join.ids
+            .push(self.store_id(Data::parse_with(value, &self.date_format))?);
+        self.set_join_at(id, row_idx, column_idx, join)
+    }
+
+    /// removes the member at `position` from the join stored at the given cell
+    pub fn join_remove_at(
+        &mut self,
+        table_name: &str,
+        row_idx: usize,
+        column_idx: usize,
+        position: usize,
+    ) -> Result<(), Error> {
+        let id = self.get_table_id(table_name)?;
+        let mut join = self.get_join_at(id, row_idx, column_idx)?;
+        if position >= join.ids.len() {
+            return Err(Db::join_position_error(&join, position));
+        }
+        let removed = join.ids.remove(position);
+        self.release_id(removed)?;
+        self.set_join_at(id, row_idx, column_idx, join)
+    }
+
+    /// removes the first member whose content equals `value` from the join stored at the given cell
+    /// ```
+    /// use rzdb::Db;
+    /// let mut db = Db::create("test", "~/.local/rzdb").unwrap();
+    /// let table_name = "join_remove_value";
+    /// db.create_or_replace_table(table_name).unwrap();
+    /// db.create_column(table_name, "tags").unwrap();
+    /// let tags = db.store_ids(vec!["urgent", "later", "home"]).unwrap();
+    /// db.insert_data(table_name, vec![tags]).unwrap();
+    /// db.join_remove_value(table_name, 0, 0, "later").unwrap();
+    /// let rows = db.select_array(table_name).unwrap();
+    /// assert_eq!(rows[0][0].len(), 2);
+    /// assert_eq!(rows[0][0][1].to_string(), "home");
+    /// assert!(db.join_remove_value(table_name, 0, 0, "later").is_err());
+    /// ```
+    pub fn join_remove_value(
+        &mut self,
+        table_name: &str,
+        row_idx: usize,
+        column_idx: usize,
+        value: &str,
+    ) -> Result<(), Error> {
+        let id = self.get_table_id(table_name)?;
+        let join = self.get_join_at(id, row_idx, column_idx)?;
+        let value = Data::parse_with(value, &self.date_format);
+        let contents = self.from_ids(Data::Join(join))?;
+        match contents.iter().position(|datum| *datum == value) {
+            Some(position) => self.join_remove_at(table_name, row_idx, column_idx, position),
+            None => Err(Error::invalid(format!(
+                "Db::join_remove_value({}, {}, {}): {} is not a member",
+                table_name, row_idx, column_idx, value,
+            ))),
+        }
+    }
+
+    /// Replaces the member at `position` of the join stored at the given cell with `value`.
+    /// The old member is released only after the new one is stored.
+    /// ```
+    /// use rzdb::{Data, Db};
+    /// let mut db = Db::create("test", "~/.local/rzdb").unwrap();
+    /// let table_name = "join_replace";
+    /// db.set_intern_ids(true);
+    /// db.create_or_replace_table(table_name).unwrap();
+    /// db.create_column(table_name, "tags").unwrap();
+    /// let tags = db.store_ids(vec!["urgent", "home"]).unwrap();
+    /// db.insert_data(table_name, vec![tags]).unwrap();
+    /// db.join_replace_at(table_name, 0, 0, 1, "work").unwrap();
+    /// db.join_replace_at(table_name, 0, 0, 0, "urgent").unwrap();
+    /// let rows = db.select_array(table_name).unwrap();
+    /// let tags: Vec<String> = rows[0][0].iter().map(|d| d.to_string()).collect();
+    /// assert_eq!(tags, vec!["urgent", "work"]);
+    /// assert!(db.join_replace_at(table_name, 0, 0, 2, "late").is_err());
+    ///
+    /// // released ids are not reused, a copy of the join keeps its values
+    /// let copy = db.select_at(table_name, 0, 0).unwrap();
+    /// db.insert_data(table_name, vec![copy.clone()]).unwrap();
+    /// db.join_replace_at(table_name, 0, 0, 1, "home").unwrap();
+    /// db.store_ids(vec!["other"]).unwrap();
+    /// assert_eq!(db.from_ids(copy).unwrap(), vec![Data::parse("urgent"), Data::parse("work")]);
+    /// ```
+    pub fn join_replace_at(
+        &mut self,
+        table_name: &str,
+        row_idx: usize,
+        column_idx: usize,
+        position: usize,
+        value: &str,
+    ) -> Result<(), Error> {
+        let id = self.get_table_id(table_name)?;
+        let mut join = self.get_join_at(id, row_idx, column_idx)?;
+        if position >= join.ids.len() {
+            return Err(Db::join_position_error(&join, position));
+        }
+        let new_id = self.store_id(Data::parse_with(value, &self.date_format))?;
+        let old_id = std::mem::replace(&mut join.ids[position], new_id);
+        self.set_join_at(id, row_idx, column_idx, join)?;
+        self.release_id(old_id)
+    }
+
+    /// moves the member at `from` of the join stored at the given cell to position `to`
+    /// ```
+    /// use rzdb::Db;
+    /// let mut db = Db::create("test", "~/.local/rzdb").unwrap();
+    /// let table_name = "join_move";
+    /// db.create_or_replace_table(table_name).unwrap();
+    /// db.create_column(table_name, "steps").unwrap();
+    /// let steps = db.store_ids(vec!["one", "two", "three"]).unwrap();
+    /// db.insert_data(table_name, vec![steps]).unwrap();
+    /// db.join_move(table_name, 0, 0, 2, 0).unwrap();
+    /// let rows = db.select_array(table_name).unwrap();
+    /// let steps: Vec<String> = rows[0][0].iter().map(|d| d.to_string()).collect();
+    /// assert_eq!(steps, vec!["three", "one", "two"]);
+    /// ```
+    pub fn join_move(
+        &mut self,
+        table_name: &str,
+        row_idx: usize,
+        column_idx: usize,
+        from: usize,
+        to: usize,
+    ) -> Result<(), Error> {
+        let id = self.get_table_id(table_name)?;
+        let mut join = self.get_join_at(id, row_idx, column_idx)?;
+        if from >= join.ids.len() {
+            return Err(Db::join_position_error(&join, from));
+        }
+        if to >= join.ids.len() {
+            return Err(Db::join_position_error(&join, to));
+        }
+        let member = join.ids.remove(from);
+        join.ids.insert(to, member);
+        self.set_join_at(id, row_idx, column_idx, join)
+    }
+
+    pub fn from_ids(&self, datum: Data) -> Result<Vec<Data>, Error> {
         if let Data::Join(join) = datum {
             let table_ids = self.get_table_id(".ids")?;
             let mut result = vec![];
@@ -432,32 +1097,55 @@ impl Db {
 
             Ok(result)
         } else {
-            Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "Data must be a join",
-            )))
+            Err(Error::invalid("Data must be a join"))
         }
     }
 
-    fn expand(&self, datum: Data) -> Result<Vec<Data>, Box<dyn Error>> {
-        if let Data::Join(join) = datum {
-            let mut result = vec![];
-            for id in join.ids {
-                let datum = self.tables[IDS_TABLE_ID].select_at(IDS_COLUMN_ID, id as usize);
-                result.push(datum?);
+    fn expand(&self, datum: Data) -> Result<Vec<Data>, Error> {
+        match datum {
+            Data::Join(join) => {
+                let mut result = vec![];
+                for id in join.ids {
+                    let datum = self.tables[IDS_TABLE_ID].select_at(IDS_COLUMN_ID, id as usize);
+                    result.push(datum?);
+                }
+                Ok(result)
             }
-            Ok(result)
-        } else {
-            Ok(vec![datum])
+            Data::List(list) => Ok(list),
+            _ => Ok(vec![datum]),
+        }
+    }
+
+    /// the items of a multi-value datum (join or list), or None for single values
+    fn multi_values(&self, datum: &Data) -> Option<Vec<Data>> {
+        match datum {
+            Data::Join(_) => self.from_ids(datum.clone()).ok(),
+            Data::List(list) => Some(list.clone()),
+            _ => None,
         }
     }
 
-    pub fn select_from(&self, table_name: &str) -> Result<Vec<Row>, Box<dyn Error>> {
+    pub fn select_from(&self, table_name: &str) -> Result<Vec<Row>, Error> {
         let id = self.get_table_id(table_name)?;
         Ok(self.tables[id].select())
     }
 
-    pub fn select_array(&self, table_name: &str) -> Result<Vec<Vec<Vec<Data>>>, Box<dyn Error>> {
+    /// Selects all rows with every column as a vector of values: joins are expanded through
+    /// `.ids`, lists into their items, and all other values become a single item.
+    /// ```
+    /// use rzdb::{Data, Db};
+    /// let mut db = Db::create("test", "~/.local/rzdb").unwrap();
+    /// let table_name = "select_array";
+    /// db.create_or_replace_table(table_name).unwrap();
+    /// db.create_column(table_name, "name").unwrap();
+    /// db.create_column(table_name, "scores").unwrap();
+    /// let scores = Data::List(vec![Data::Int(3), Data::Int(5)]);
+    /// db.insert_data(table_name, vec![Data::parse("ann"), scores]).unwrap();
+    /// let rows = db.select_array(table_name).unwrap();
+    /// assert_eq!(rows[0][0], vec![Data::parse("ann")]);
+    /// assert_eq!(rows[0][1], vec![Data::Int(3), Data::Int(5)]);
+    /// ```
+    pub fn select_array(&self, table_name: &str) -> Result<Vec<Vec<Vec<Data>>>, Error> {
         let id = self.get_table_id(table_name)?;
         let data = self.tables[id].select();
         let mut result = vec![];
@@ -476,7 +1164,7 @@ impl Db {
         table_name: &str,
         col_idx: usize,
         row_idx: usize,
-    ) -> Result<Data, Box<dyn Error>> {
+    ) -> Result<Data, Error> {
         let id = self.get_table_id(table_name)?;
         self.tables[id].select_at(col_idx, row_idx)
     }
@@ -489,7 +1177,7 @@ impl Db {
         columns: &[&str],
         start_idx: usize,
         end_idx: usize,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), Error> {
         self.create_or_replace_table(dest_table)?;
         let dest_id = self.get_table_id(dest_table)?;
 
@@ -503,12 +1191,15 @@ impl Db {
         //if  rows = table.select_columns(columns)
         let mut rows = vec![];
         if let Ok(r) = table.select_columns(columns) {
-            for row in r.iter().skip(start_idx).take(end_idx - start_idx) {
+            for row in r
+                .iter()
+                .skip(start_idx)
+                .take(end_idx.saturating_sub(start_idx))
+            {
                 rows.push(row.clone());
             }
         }
-        self.tables[dest_id].append_rows(&mut rows).unwrap();
-        Ok(())
+        self.tables[dest_id].append_rows(&mut rows)
     }
 
     /// Selects columns in the given order from the table and returns a vector of rows.
@@ -516,9 +1207,9 @@ impl Db {
     /// * `table_name` - The name of the table to select from
     /// * `columns` - The names of the columns to select
     /// # Returns
-    /// * `Result<Vec<Row>, Box<dyn Error>>` - A vector of rows containing the selected columns
+    /// * `Result<Vec<Row>, Error>` - A vector of rows containing the selected columns
     /// # Errors
-    /// * `Box<dyn Error>` - If the table does not exist or if the columns do not exist
+    /// * `Error` - If the table does not exist or if the columns do not exist
     /// # Examples
     /// ```
     /// use rzdb::Db;
@@ -545,11 +1236,7 @@ impl Db {
     /// assert_eq!(rows[2].select_at(1).unwrap().to_string(), "9");
     /// assert_eq!(rows[2].select_at(2).unwrap().to_string(), "7");
     /// ```
-    pub fn select_columns(
-        &self,
-        table_name: &str,
-        columns: &[&str],
-    ) -> Result<Vec<Row>, Box<dyn Error>> {
+    pub fn select_columns(&self, table_name: &str, columns: &[&str]) -> Result<Vec<Row>, Error> {
         let id = self.get_table_id(table_name)?;
         self.tables[id].select_columns(columns)
     }
@@ -559,9 +1246,9 @@ impl Db {
     /// * `table_name` - The name of the table to select from
     /// * `conditions` - The conditions to match
     /// # Returns
-    /// * `Result<Vec<Row>, Box<dyn Error>>` - A vector of rows containing the selected columns
+    /// * `Result<Vec<Row>, Error>` - A vector of rows containing the selected columns
     /// # Errors
-    /// * `Box<dyn Error>` - If the table does not exist or if the columns do not exist
+    /// * `Error` - If the table does not exist or if the columns do not exist
     /// # Examples
     /// ```
     /// use rzdb::Db;
@@ -587,31 +1274,55 @@ impl Db {
         &self,
         table_name: &str,
         conditions: &[Condition],
-    ) -> Result<Vec<Row>, Box<dyn Error>> {
+    ) -> Result<Vec<Row>, Error> {
         let id = self.get_table_id(table_name)?;
         self.tables[id].select_where(conditions)
     }
 
-    pub fn get_column_name_at(
+    /// Applies an aggregate function to a column of all rows matching the conditions.
+    /// ```
+    /// use rzdb::{Aggregate, ColumnType, Condition, ConditionType, Data, Db};
+    /// let mut db = Db::create("test", "~/.local/rzdb").unwrap();
+    /// let table_name = "aggregate";
+    /// db.create_or_replace_table(table_name).unwrap();
+    /// db.create_column(table_name, "amount").unwrap();
+    /// db.create_typed_column(table_name, "paid", ColumnType::Bool).unwrap();
+    /// db.insert(table_name, vec!["10", "true"]).unwrap();
+    /// db.insert(table_name, vec!["20", "false"]).unwrap();
+    /// db.insert(table_name, vec!["30", "true"]).unwrap();
+    /// assert_eq!(db.aggregate(table_name, "amount", Aggregate::Sum, &[]).unwrap(), Data::Int(60));
+    /// assert_eq!(db.aggregate(table_name, "paid", Aggregate::CountTrue, &[]).unwrap(), Data::Int(2));
+    /// assert_eq!(db.aggregate(table_name, "paid", Aggregate::All, &[]).unwrap(), Data::Bool(false));
+    /// let cond = Condition::new("amount", Data::Int(15), ConditionType::GreaterThan);
+    /// assert_eq!(db.aggregate(table_name, "amount", Aggregate::Min, &[cond]).unwrap(), Data::Int(20));
+    /// ```
+    pub fn aggregate(
         &self,
         table_name: &str,
-        idx: usize,
-    ) -> Result<String, Box<dyn Error>> {
+        column_name: &str,
+        aggregate: Aggregate,
+        conditions: &[Condition],
+    ) -> Result<Data, Error> {
+        let id = self.get_table_id(table_name)?;
+        self.tables[id].aggregate(column_name, aggregate, conditions)
+    }
+
+    pub fn get_column_name_at(&self, table_name: &str, idx: usize) -> Result<String, Error> {
         let id = self.get_table_id(table_name)?;
-        Ok(self.tables[id].get_column_name_at(idx))
+        self.tables[id].get_column_name_at(idx)
     }
 
-    pub fn get_column_names(&self, table_name: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    pub fn get_column_names(&self, table_name: &str) -> Result<Vec<String>, Error> {
         let id = self.get_table_id(table_name)?;
         Ok(self.tables[id].get_column_names())
     }
 
-    pub fn get_row_count(&self, table_name: &str) -> Result<usize, Box<dyn Error>> {
+    pub fn get_row_count(&self, table_name: &str) -> Result<usize, Error> {
         let id = self.get_table_id(table_name)?;
         Ok(self.tables[id].row_count())
     }
 
-    pub fn get_column_count(&self, table_name: &str) -> Result<usize, Box<dyn Error>> {
+    pub fn get_column_count(&self, table_name: &str) -> Result<usize, Error> {
         let id = self.get_table_id(table_name)?;
         Ok(self.tables[id].column_count())
     }
@@ -622,12 +1333,506 @@ impl Db {
         row_idx: usize,
         column_idx: usize,
         value: Data,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), Error> {
         let id = self.get_table_id(table_name)?;
         self.tables[id].set_at(row_idx, column_idx, value)
     }
 
-    pub fn display(&self, table_name: &str) -> Result<String, Box<dyn Error>> {
+    /// sets the zone used by display() to show date and time values
+    /// ```
+    /// use rzdb::{Data, Db};
+    /// use rzdb::time::TimeZone;
+    /// let mut db = Db::create("test", "~/.local/rzdb").unwrap();
+    /// let table_name = "set_timezone";
+    /// db.create_or_replace_table(table_name).unwrap();
+    /// db.create_column(table_name, "modified").unwrap();
+    /// db.insert(table_name, vec!["2024-03-01T10:30:00Z"]).unwrap();
+    /// db.set_timezone(TimeZone::Fixed(-5 * 3600));
+    /// assert!(db.display(table_name).unwrap().contains("2024-03-01 05:30:00 -05:00"));
+    /// ```
+    pub fn set_timezone(&mut self, timezone: TimeZone) {
+        self.timezone = timezone;
+    }
+
+    pub fn get_timezone(&self) -> TimeZone {
+        self.timezone.clone()
+    }
+
+    /// Sets the zone of the timestamps in backup file names, the system's zone by default.
+    /// Use `TimeZone::Utc` for backup names that do not depend on the machine.
+    pub fn set_backup_timezone(&mut self, timezone: TimeZone) {
+        self.backup_timezone = timezone;
+    }
+
+    /// Sets the formats used to read dates inserted as text, and to show dates in display()
+    /// and export_csv(). Saved tables always store ISO dates.
+    /// ```
+    /// use rzdb::{Data, Db};
+    /// use rzdb::time::{Date, DateFormat};
+    /// let mut db = Db::create("test", "~/.local/rzdb").unwrap();
+    /// let table_name = "set_date_format";
+    /// db.create_or_replace_table(table_name).unwrap();
+    /// db.create_column(table_name, "born").unwrap();
+    /// db.set_date_format(DateFormat::new(&["%d/%m/%y"], "%d/%m/%Y").unwrap());
+    /// db.insert(table_name, vec!["13/01/69"]).unwrap();
+    /// db.insert(table_name, vec!["1/13/1969"]).unwrap();
+    /// let rows = db.select_from(table_name).unwrap();
+    /// assert_eq!(rows[0].select_at(0).unwrap(), Data::Date(Date::new(1969, 1, 13).unwrap()));
+    /// assert_eq!(rows[1].select_at(0).unwrap(), Data::String("1/13/1969".to_string()));
+    /// assert!(db.display(table_name).unwrap().contains("13/01/1969"));
+    /// assert_eq!(db.export_csv(table_name).unwrap(), "born\n13/01/1969\n1/13/1969\n");
+    /// ```
+    pub fn set_date_format(&mut self, date_format: DateFormat) {
+        self.date_format = date_format;
+    }
+
+    pub fn get_date_format(&self) -> DateFormat {
+        self.date_format.clone()
+    }
+
+    /// formats a value for display, date and time values are shown in the db's zone
+    fn format_value(&self, datum: &Data) -> String {
+        match datum {
+            Data::Date(date) => self.date_format.format(date),
+            Data::Blob(blob) => format!("<blob {} bytes>", blob.len()),
+            Data::DateTime(dt) => dt.format_in(&self.timezone),
+            _ => datum.to_string(),
+        }
+    }
+
+    /// Sorts the rows of a table by a column. The sort is stable; values that can not be
+    /// compared to each other keep their order, empty values are sorted last.
+    /// ```
+    /// use rzdb::{Data, Db};
+    /// let mut db = Db::create("test", "~/.local/rzdb").unwrap();
+    /// let table_name = "sort_by";
+    /// db.create_or_replace_table(table_name).unwrap();
+    /// db.create_column(table_name, "modified").unwrap();
+    /// db.insert(table_name, vec!["2024-03-01T10:30:00Z"]).unwrap();
+    /// db.insert(table_name, vec![""]).unwrap();
+    /// db.insert(table_name, vec!["2024-03-01T09:30:00-02:00"]).unwrap();
+    /// db.insert(table_name, vec!["2024-02-01T10:30:00Z"]).unwrap();
+    /// db.sort_by(table_name, "modified", true).unwrap();
+    /// let rows = db.select_from(table_name).unwrap();
+    /// assert_eq!(rows[0].select_at(0).unwrap().to_string(), "2024-02-01T10:30:00Z");
+    /// assert_eq!(rows[2].select_at(0).unwrap().to_string(), "2024-03-01T11:30:00Z");
+    /// assert_eq!(rows[3].select_at(0).unwrap(), Data::Empty);
+    /// ```
+    pub fn sort_by(
+        &mut self,
+        table_name: &str,
+        column_name: &str,
+        ascending: bool,
+    ) -> Result<(), Error> {
+        let id = self.get_table_id(table_name)?;
+        self.tables[id].sort_by(column_name, ascending)
+    }
+
+    /// Imports a csv file written by another tool into a new table and returns the number
+    /// of imported rows. The encoding, the dialect and whether there is a header are
+    /// detected unless given in the options. Each column gets the most specific type that
+    /// all values in the first `sample_rows` rows fit. A later value that does not fit makes
+    /// it an `Any` column, or is an error with the `strict` option.
+    /// ```
+    /// use rzdb::{ColumnType, Data, Db, ImportOptions};
+    /// let mut db = Db::create("test", "~/.local/rzdb").unwrap();
+    /// let table_name = "import_csv";
+    /// let path = std::env::temp_dir().join("rzdb_import_csv.csv");
+    /// std::fs::write(&path, "name;born;score\r\nAnn;1969-01-13;\"1,5\"\r\n\"B\"\"ob\";;2\r\n").unwrap();
+    /// let count = db.import_csv(path.to_str().unwrap(), table_name, &ImportOptions::default()).unwrap();
+    /// assert_eq!(count, 2);
+    /// assert_eq!(db.get_column_names(table_name).unwrap(), vec!["name", "born", "score"]);
+    /// assert_eq!(
+    ///     db.get_column_types(table_name).unwrap(),
+    ///     vec![ColumnType::String, ColumnType::Date, ColumnType::String]
+    /// );
+    /// assert_eq!(db.select_at(table_name, 0, 1).unwrap(), Data::String("B\"ob".to_string()));
+    /// assert!(db.import_csv(path.to_str().unwrap(), table_name, &ImportOptions::default()).is_err());
+    /// ```
+    pub fn import_csv(
+        &mut self,
+        path: &str,
+        table_name: &str,
+        options: &ImportOptions,
+    ) -> Result<usize, Error> {
+        if self.exists(table_name) {
+            return Err(Error::TableExists(table_name.to_string()));
+        }
+        let path = Db::expand_home_dir(path)?;
+        let table = import::import_csv(&path, table_name, options, &self.date_format)?;
+        let count = table.len();
+        self.tables.push(table);
+        Ok(count)
+    }
+
+    /// Imports a JSON file with an array of objects into a new table and returns the number
+    /// of imported rows. Every key becomes a column, in the order the keys first appear.
+    /// Numbers, bools and strings are stored as they are, with strings as dates or times if
+    /// all of a column fit. Arrays of scalars are stored with `store_ids` as joins, nested
+    /// objects and arrays of arrays or objects are rejected.
+    /// ```
+    /// use rzdb::{ColumnType, Data, Db};
+    /// let mut db = Db::create("test", "~/.local/rzdb").unwrap();
+    /// let table_name = "import_json";
+    /// let path = std::env::temp_dir().join("rzdb_import_json.json");
+    /// std::fs::write(&path, r#"[
+    ///     {"task": "write docs", "due": "2024-03-01", "tags": ["docs", "urgent"]},
+    ///     {"task": "fix bug", "done": true}
+    /// ]"#).unwrap();
+    /// let count = db.import_json(path.to_str().unwrap(), table_name).unwrap();
+    /// assert_eq!(count, 2);
+    /// assert_eq!(db.get_column_names(table_name).unwrap(), vec!["task", "due", "tags", "done"]);
+    /// assert_eq!(db.get_column_types(table_name).unwrap()[1], ColumnType::Date);
+    /// let tags = db.select_at(table_name, 2, 0).unwrap();
+    /// assert_eq!(db.from_ids(tags).unwrap(), vec![Data::parse("docs"), Data::parse("urgent")]);
+    /// assert_eq!(db.select_at(table_name, 3, 0).unwrap(), Data::Empty);
+    ///
+    /// std::fs::write(&path, r#"[{"task": "nested", "owner": {"name": "Ann"}}]"#).unwrap();
+    /// assert!(db.import_json(path.to_str().unwrap(), "import_json_nested").is_err());
+    /// ```
+    pub fn import_json(&mut self, path: &str, table_name: &str) -> Result<usize, Error> {
+        if self.exists(table_name) {
+            return Err(Error::TableExists(table_name.to_string()));
+        }
+        let path = Db::expand_home_dir(path)?;
+        let content = std::fs::read_to_string(&path)?;
+        let json =
+            Json::parse(&content).map_err(|e| e.in_context(&format!("import_json({})", path)))?;
+        let date_format = self.date_format.clone();
+        let table = import::import_json(table_name, &json, &date_format, &mut |datum| {
+            self.store_id(datum)
+        })?;
+        let count = table.len();
+        self.tables.push(table);
+        Ok(count)
+    }
+
+    /// Inserts a struct (or map) as a row, with `serde::Serialize`. Fields are matched to
+    /// columns by name, columns without a field are left empty. Strings are parsed for typed
+    /// columns and ints converted for float and decimal columns; `Vec` and tuple fields are
+    /// stored as joins, in untyped columns only. Fails without changes if a field has no
+    /// column or its value does not fit the column.
+    /// ```
+    /// use rzdb::{ColumnType, Db};
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    /// struct Task {
+    ///     title: String,
+    ///     due: Option<String>,
+    ///     hours: f64,
+    ///     tags: Vec<String>,
+    /// }
+    ///
+    /// let mut db = Db::create("test", "~/.local/rzdb").unwrap();
+    /// let table_name = "insert_record";
+    /// db.create_or_replace_table(table_name).unwrap();
+    /// db.create_typed_column(table_name, "title", ColumnType::String).unwrap();
+    /// db.create_typed_column(table_name, "due", ColumnType::Date).unwrap();
+    /// db.create_typed_column(table_name, "hours", ColumnType::Float).unwrap();
+    /// db.create_column(table_name, "tags").unwrap();
+    /// let task = Task {
+    ///     title: "write docs".to_string(),
+    ///     due: Some("2024-03-01".to_string()),
+    ///     hours: 2.5,
+    ///     tags: vec!["docs".to_string(), "urgent".to_string()],
+    /// };
+    /// db.insert_record(table_name, &task).unwrap();
+    /// assert_eq!(db.get_column_types(table_name).unwrap()[1], ColumnType::Date);
+    /// let tasks: Vec<Task> = db.select_records(table_name, &[]).unwrap();
+    /// assert_eq!(tasks, vec![task]);
+    ///
+    /// #[derive(Serialize)]
+    /// struct Note {
+    ///     text: String,
+    /// }
+    /// let note = Note { text: "no such column".to_string() };
+    /// assert!(db.insert_record(table_name, &note).is_err());
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn insert_record<T: serde::Serialize>(
+        &mut self,
+        table_name: &str,
+        record: &T,
+    ) -> Result<(), Error> {
+        let id = self.get_table_id(table_name)?;
+        let table = &self.tables[id];
+        let mut values: Vec<Field> = (0..table.column_count())
+            .map(|_| Field::Value(Data::Empty))
+            .collect();
+        // check all fields before values are stored in `.ids`
+        for (name, field) in record::to_fields(record)? {
+            let idx = table.get_column_idx_result(&name)?;
+            values[idx] = match field {
+                Field::Value(datum) => Field::Value(table.coerce(idx, datum, &self.date_format)?),
+                Field::Items(items) => {
+                    // only untyped columns can hold a join
+                    table.check_type(idx, &Data::List(items.clone()))?;
+                    Field::Items(items)
+                }
+            };
+        }
+        let mut data = vec![];
+        for value in values {
+            data.push(match value {
+                Field::Value(datum) => datum,
+                Field::Items(items) => {
+                    let mut ids = vec![];
+                    for item in items {
+                        ids.push(self.store_id(item)?);
+                    }
+                    Data::Join(Join::new(ids))
+                }
+            });
+        }
+        self.tables[id].insert_data(data)
+    }
+
+    /// Returns the rows matching the conditions as structs (or maps), with
+    /// `serde::Deserialize`. Columns are matched to fields by name, joins are read as
+    /// sequences of their values and empty values as `None`. Columns without a field are
+    /// ignored unless the type denies unknown fields. See `insert_record()`.
+    #[cfg(feature = "serde")]
+    pub fn select_records<T: serde::de::DeserializeOwned>(
+        &self,
+        table_name: &str,
+        conditions: &[Condition],
+    ) -> Result<Vec<T>, Error> {
+        let id = self.get_table_id(table_name)?;
+        let names = self.tables[id].get_column_names();
+        let mut records = vec![];
+        for row in self.tables[id].select_where(conditions)? {
+            let mut columns = vec![];
+            for (name, datum) in names.iter().zip(row.select()) {
+                let datum = match datum {
+                    Data::Join(_) => Data::List(self.from_ids(datum)?),
+                    datum => datum,
+                };
+                columns.push((name.clone(), datum));
+            }
+            records.push(record::from_row(table_name, columns)?);
+        }
+        Ok(records)
+    }
+
+    /// Creates the table of an `RzdbTable`, with its typed columns.
+    #[cfg(feature = "derive")]
+    pub fn create_table_for<T: RzdbTable>(&mut self) -> Result<(), Error> {
+        self.create_table(T::TABLE_NAME)?;
+        for (name, column_type) in T::COLUMNS {
+            self.create_typed_column(T::TABLE_NAME, name, *column_type)?;
+        }
+        Ok(())
+    }
+
+    /// Inserts a struct into its table, sequences are stored as joins. Other columns of the
+    /// table are left empty.
+    #[cfg(feature = "derive")]
+    pub fn insert_typed<T: RzdbTable>(&mut self, record: &T) -> Result<(), Error> {
+        let id = self.get_table_id(T::TABLE_NAME)?;
+        let table = &self.tables[id];
+        let mut row = vec![Data::Empty; table.column_count()];
+        // check all values before lists are stored in `.ids`
+        for ((name, _), datum) in T::COLUMNS.iter().zip(record.to_row()) {
+            let idx = table.get_column_idx_result(name)?;
+            table.check_type(idx, &datum)?;
+            row[idx] = datum;
+        }
+        for datum in row.iter_mut() {
+            if let Data::List(items) = datum {
+                let mut ids = vec![];
+                for item in std::mem::take(items) {
+                    ids.push(self.store_id(item)?);
+                }
+                *datum = Data::Join(Join::new(ids));
+            }
+        }
+        self.tables[id].insert_data(row)
+    }
+
+    /// Returns the rows of a struct's table matching the conditions.
+    #[cfg(feature = "derive")]
+    pub fn select_typed<T: RzdbTable>(&self, conditions: &[Condition]) -> Result<Vec<T>, Error> {
+        let id = self.get_table_id(T::TABLE_NAME)?;
+        let mut indices = vec![];
+        for (name, _) in T::COLUMNS {
+            indices.push(self.tables[id].get_column_idx_result(name)?);
+        }
+        let mut records = vec![];
+        for row in self.tables[id].select_where(conditions)? {
+            let mut values = vec![];
+            for idx in &indices {
+                values.push(match row.select_at(*idx)? {
+                    datum @ Data::Join(_) => Data::List(self.from_ids(datum)?),
+                    datum => datum,
+                });
+            }
+            records.push(T::from_row(values)?);
+        }
+        Ok(records)
+    }
+
+    /// Exports a table as csv text with a header line. Values are written as in display(),
+    /// the values of joins and lists are written comma separated into a single field.
+    pub fn export_csv(&self, table_name: &str) -> Result<String, Error> {
+        let mut out = vec![];
+        self.export(table_name, ExportFormat::Csv, &mut out)?;
+        Ok(String::from_utf8(out)?)
+    }
+
+    /// Writes a table in one of the formats of `ExportFormat`. Values are shown as in
+    /// display(), dates and times in the db's formats and zone. The text formats join the
+    /// values of joins and lists with commas. JSON keeps ints, floats, bools and empty values
+    /// (`null`), writes decimals as strings, so that they stay exact, and joins, expanded
+    /// through `.ids`, and lists as arrays.
+    /// ```
+    /// use rzdb::{Data, Db, ExportFormat};
+    /// let export = |db: &Db, format| {
+    ///     let mut out = vec![];
+    ///     db.export("export", format, &mut out).unwrap();
+    ///     String::from_utf8(out).unwrap()
+    /// };
+    /// let mut db = Db::create("test", "~/.local/rzdb").unwrap();
+    /// let table_name = "export";
+    /// db.create_or_replace_table(table_name).unwrap();
+    /// db.create_column(table_name, "name").unwrap();
+    /// db.create_column(table_name, "tags").unwrap();
+    /// db.create_column(table_name, "score").unwrap();
+    /// let tags = db.store_ids(vec!["a|b", "c"]).unwrap();
+    /// db.insert_data(table_name, vec![Data::parse("say \"hi\""), tags, Data::Int(3)]).unwrap();
+    /// db.insert_data(table_name, vec![Data::parse("<b>"), Data::Empty, Data::Float(0.5)]).unwrap();
+    ///
+    /// assert_eq!(
+    ///     export(&db, ExportFormat::Csv),
+    ///     "name,tags,score\n\"say \"\"hi\"\"\",\"a|b,c\",3\n<b>,,0.5\n"
+    /// );
+    /// assert_eq!(export(&db, ExportFormat::Tsv), "name\ttags\tscore\nsay \"hi\"\ta|b,c\t3\n<b>\t\t0.5\n");
+    /// assert_eq!(
+    ///     export(&db, ExportFormat::JsonLines),
+    ///     "{\"name\":\"say \\\"hi\\\"\",\"tags\":[\"a|b\",\"c\"],\"score\":3}\n\
+    ///      {\"name\":\"<b>\",\"tags\":null,\"score\":0.5}\n"
+    /// );
+    /// assert!(export(&db, ExportFormat::Json).starts_with("[\n  {\"name\":"));
+    /// assert!(export(&db, ExportFormat::Markdown).contains("| say \"hi\" | a\\|b,c | 3 |"));
+    /// assert!(export(&db, ExportFormat::Html).contains("<td>&lt;b&gt;</td><td></td><td>0.5</td>"));
+    ///
+    /// db.set_timezone(rzdb::time::TimeZone::Fixed(3600));
+    /// db.create_or_replace_table(table_name).unwrap();
+    /// db.create_column(table_name, "price").unwrap();
+    /// db.create_column(table_name, "modified").unwrap();
+    /// db.create_column(table_name, "groups").unwrap();
+    /// let price = Data::Decimal(rzdb::Decimal::parse("0.10000000000000001").unwrap());
+    /// let nested = Data::List(vec![db.store_ids(vec!["a", "b"]).unwrap(), Data::Int(1)]);
+    /// db.insert_data(table_name, vec![price, Data::parse("2024-03-01T10:30:00Z"), nested]).unwrap();
+    /// assert_eq!(
+    ///     export(&db, ExportFormat::JsonLines),
+    ///     "{\"price\":\"0.10000000000000001\",\"modified\":\"2024-03-01 11:30:00 +01:00\",\
+    ///      \"groups\":[[\"a\",\"b\"],1]}\n"
+    /// );
+    /// assert!(export(&db, ExportFormat::Csv).ends_with("0.10000000000000001,2024-03-01 11:30:00 +01:00,\"a,b,1\"\n"));
+    /// ```
+    pub fn export<W: Write>(
+        &self,
+        table_name: &str,
+        format: ExportFormat,
+        mut writer: W,
+    ) -> Result<(), Error> {
+        let table_id = self.get_table_id(table_name)?;
+        let table = &self.tables[table_id];
+        let names = table.get_column_names();
+        let text = |datum: &Data| self.export_text(datum);
+        let json_object = |row: &Row| {
+            let members = names
+                .iter()
+                .zip(row.iter())
+                .map(|(name, datum)| (name.clone(), self.export_json(datum)))
+                .collect();
+            Json::Object(members)
+        };
+        let rows = table.select();
+
+        match format {
+            ExportFormat::Csv | ExportFormat::Tsv => {
+                let (separator, field): (&str, fn(&str) -> String) = match format {
+                    ExportFormat::Csv => (",", quote_field),
+                    _ => ("\t", export::tsv_field),
+                };
+                let names: Vec<String> = names.iter().map(|name| field(name)).collect();
+                writeln!(writer, "{}", names.join(separator))?;
+                for row in &rows {
+                    let fields: Vec<String> = row.iter().map(|datum| field(&text(datum))).collect();
+                    writeln!(writer, "{}", fields.join(separator))?;
+                }
+            }
+            ExportFormat::Json => {
+                write!(writer, "[")?;
+                for (idx, row) in rows.iter().enumerate() {
+                    let separator = if idx > 0 { "," } else { "" };
+                    write!(writer, "{}\n  {}", separator, json_object(row))?;
+                }
+                writeln!(writer, "\n]")?;
+            }
+            ExportFormat::JsonLines => {
+                for row in &rows {
+                    writeln!(writer, "{}", json_object(row))?;
+                }
+            }
+            ExportFormat::Markdown => {
+                let line = |cells: Vec<String>| format!("| {} |", cells.join(" | "));
+                let names: Vec<String> = names.iter().map(|n| export::markdown_cell(n)).collect();
+                writeln!(writer, "{}", line(names.clone()))?;
+                writeln!(
+                    writer,
+                    "{}",
+                    line(names.iter().map(|_| "---".into()).collect())
+                )?;
+                for row in &rows {
+                    let cells = row.iter().map(|datum| export::markdown_cell(&text(datum)));
+                    writeln!(writer, "{}", line(cells.collect()))?;
+                }
+            }
+            ExportFormat::Html => {
+                let cells = |tag: &str, values: Vec<String>| -> String {
+                    values
+                        .iter()
+                        .map(|value| format!("<{}>{}</{}>", tag, export::html_escape(value), tag))
+                        .collect()
+                };
+                writeln!(writer, "<table>\n  <thead>")?;
+                writeln!(writer, "    <tr>{}</tr>", cells("th", names.clone()))?;
+                writeln!(writer, "  </thead>\n  <tbody>")?;
+                for row in &rows {
+                    let values = row.iter().map(&text).collect();
+                    writeln!(writer, "    <tr>{}</tr>", cells("td", values))?;
+                }
+                writeln!(writer, "  </tbody>\n</table>")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// a value for the text formats of export(), the items of joins and lists, also nested
+    /// ones, comma separated
+    fn export_text(&self, datum: &Data) -> String {
+        match self.multi_values(datum) {
+            Some(data) => data
+                .iter()
+                .map(|datum| self.export_text(datum))
+                .collect::<Vec<String>>()
+                .join(","),
+            None => self.format_value(datum),
+        }
+    }
+
+    /// a value for the JSON formats of export(), joins and lists, also nested ones, as arrays
+    fn export_json(&self, datum: &Data) -> Json {
+        match self.multi_values(datum) {
+            Some(data) => Json::Array(data.iter().map(|datum| self.export_json(datum)).collect()),
+            None => export::json_value(datum, &self.format_value(datum)),
+        }
+    }
+
+    pub fn display(&self, table_name: &str) -> Result<String, Error> {
         let table_id = self.get_table_id(table_name)?;
         let table = &self.tables[table_id];
 
@@ -658,12 +1863,12 @@ impl Db {
         for row in &rows {
             for (i, value) in row.select().iter().enumerate() {
                 let mut width = column_widths[i];
-                if let Ok(data) = self.from_ids(value.clone()) {
+                if let Some(data) = self.multi_values(value) {
                     for datum in &data {
-                        width = width.max(datum.to_string().chars().count());
+                        width = width.max(self.format_value(datum).chars().count());
                     }
                 } else {
-                    let value_str = value.to_string();
+                    let value_str = self.format_value(value);
                     width = width.max(value_str.chars().count());
                 }
                 column_widths[i] = width;
@@ -691,13 +1896,13 @@ impl Db {
             loop {
                 let mut has_more_multi = false;
                 for (i, datum) in row.select().iter().enumerate() {
-                    if let Ok(multi_data) = self.from_ids(datum.clone()) {
+                    if let Some(multi_data) = self.multi_values(datum) {
                         if multi_index < multi_data.len() {
                             if multi_index + 1 < multi_data.len() {
                                 has_more_multi = true;
                             }
                             result.push_str(&pad(
-                                &multi_data[multi_index].to_string(),
+                                &self.format_value(&multi_data[multi_index]),
                                 column_widths[i] + 1,
                             ));
                         } else {
@@ -706,7 +1911,7 @@ impl Db {
                     } else {
                         let width = column_widths[i];
                         if multi_index == 0 {
-                            result.push_str(&pad(&datum.to_string(), width + 1));
+                            result.push_str(&pad(&self.format_value(datum), width + 1));
                         } else {
                             result.push_str(&pad("", width + 1));
                         }
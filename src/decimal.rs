@@ -0,0 +1,221 @@
+use crate::error::Error;
+use std::cmp::Ordering;
+
+/// A fixed-point decimal number for exact amounts such as money: `mantissa / 10^scale`.
+/// The scale of a parsed value is kept, so `0.10` is displayed as `0.10`. Values of
+/// different scales compare by their numeric value.
+#[derive(Clone, Copy, Debug)]
+pub struct Decimal {
+    mantissa: i128,
+    scale: u32,
+}
+
+/// more digits after the point are rejected, so that rescaling can not overflow easily
+const MAX_SCALE: u32 = 18;
+
+fn gen_error(msg: &str, s: &str) -> Error {
+    Error::parse(format!("{}: {}", msg, s))
+}
+
+impl Decimal {
+    pub fn new(mantissa: i128, scale: u32) -> Decimal {
+        Decimal { mantissa, scale }
+    }
+
+    pub fn from_int(n: i64) -> Decimal {
+        Decimal::new(n as i128, 0)
+    }
+
+    pub fn mantissa(&self) -> i128 {
+        self.mantissa
+    }
+
+    /// number of digits after the decimal point
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    /// Parses a decimal number such as `12`, `-0.10` or `+3.5`.
+    /// ```
+    /// use rzdb::Decimal;
+    /// assert_eq!(Decimal::parse("-0.10").unwrap().to_string(), "-0.10");
+    /// assert_eq!(Decimal::parse("+3.").unwrap().to_string(), "3");
+    /// assert_eq!(Decimal::parse(".5").unwrap().to_string(), "0.5");
+    /// assert!(Decimal::parse("1e3").is_err());
+    /// assert!(Decimal::parse("1.2.3").is_err());
+    /// assert_eq!(Decimal::parse(&i128::MIN.to_string()).unwrap().mantissa(), i128::MIN);
+    /// ```
+    pub fn parse(s: &str) -> Result<Decimal, Error> {
+        let (negative, rest) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (int_part, fraction) = rest.split_once('.').unwrap_or((rest, ""));
+        let is_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if (int_part.is_empty() && fraction.is_empty())
+            || !is_digits(int_part)
+            || !is_digits(fraction)
+        {
+            return Err(gen_error("Not a decimal", s));
+        }
+        if fraction.len() as u32 > MAX_SCALE {
+            return Err(gen_error("Too many decimal places", s));
+        }
+        // negative numbers are accumulated negatively, so that i128::MIN can be read back
+        let sign = if negative { -1 } else { 1 };
+        let mut mantissa: i128 = 0;
+        for ch in int_part.chars().chain(fraction.chars()) {
+            mantissa = mantissa
+                .checked_mul(10)
+                .and_then(|n| n.checked_add(sign * (ch as i128 - '0' as i128)))
+                .ok_or_else(|| gen_error("Decimal out of range", s))?;
+        }
+        Ok(Decimal::new(mantissa, fraction.len() as u32))
+    }
+
+    /// the same value with more digits after the point, None on overflow
+    fn rescale(&self, scale: u32) -> Option<Decimal> {
+        let factor = 10i128.checked_pow(scale.checked_sub(self.scale)?)?;
+        Some(Decimal::new(self.mantissa.checked_mul(factor)?, scale))
+    }
+
+    /// adds exactly, the result has the larger scale; None on overflow
+    /// ```
+    /// use rzdb::Decimal;
+    /// let a = Decimal::parse("0.10").unwrap();
+    /// let b = Decimal::parse("0.2").unwrap();
+    /// assert_eq!(a.checked_add(&b).unwrap().to_string(), "0.30");
+    /// ```
+    pub fn checked_add(&self, other: &Decimal) -> Option<Decimal> {
+        let scale = self.scale.max(other.scale);
+        let a = self.rescale(scale)?;
+        let b = other.rescale(scale)?;
+        Some(Decimal::new(a.mantissa.checked_add(b.mantissa)?, scale))
+    }
+
+    /// the remainder of a division with the sign of self, None for a division by zero
+    /// ```
+    /// use rzdb::Decimal;
+    /// let a = Decimal::parse("-7.5").unwrap();
+    /// assert_eq!(a.checked_rem(&Decimal::parse("2").unwrap()).unwrap().to_string(), "-1.5");
+    /// ```
+    pub fn checked_rem(&self, other: &Decimal) -> Option<Decimal> {
+        let scale = self.scale.max(other.scale);
+        let a = self.rescale(scale)?;
+        let b = other.rescale(scale)?;
+        Some(Decimal::new(a.mantissa.checked_rem(b.mantissa)?, scale))
+    }
+
+    /// multiplies exactly, the scale is the sum of both scales, but at most 18 digits;
+    /// None on overflow
+    /// ```
+    /// use rzdb::Decimal;
+    /// let a = Decimal::parse("1.25").unwrap();
+    /// let b = Decimal::parse("-0.5").unwrap();
+    /// assert_eq!(a.checked_mul(&b).unwrap().to_string(), "-0.625");
+    /// assert_eq!(Decimal::new(1, u32::MAX).checked_mul(&Decimal::new(1, 1)), None);
+    /// ```
+    pub fn checked_mul(&self, other: &Decimal) -> Option<Decimal> {
+        let product = Decimal::new(
+            self.mantissa.checked_mul(other.mantissa)?,
+            self.scale.checked_add(other.scale)?,
+        );
+        Some(product.round_to_scale(MAX_SCALE))
+    }
+
+    /// rounds half away from zero to at most the given number of digits after the point
+    /// ```
+    /// use rzdb::Decimal;
+    /// assert_eq!(Decimal::parse("2.345").unwrap().round_to_scale(2).to_string(), "2.35");
+    /// assert_eq!(Decimal::parse("-2.5").unwrap().round_to_scale(0).to_string(), "-3");
+    /// assert_eq!(Decimal::parse("2.5").unwrap().round_to_scale(3).to_string(), "2.5");
+    /// ```
+    pub fn round_to_scale(&self, scale: u32) -> Decimal {
+        if scale >= self.scale {
+            return *self;
+        }
+        // a factor that does not fit means that the value rounds to 0
+        let Some(factor) = 10i128.checked_pow(self.scale - scale) else {
+            return Decimal::new(0, scale);
+        };
+        let rounded = Decimal::new(self.mantissa, 0)
+            .div_rounded_i128(factor)
+            .mantissa;
+        Decimal::new(rounded, scale)
+    }
+
+    /// divides by a count keeping the scale, rounding half away from zero
+    /// ```
+    /// use rzdb::Decimal;
+    /// assert_eq!(Decimal::parse("1.00").unwrap().div_rounded(3).unwrap().to_string(), "0.33");
+    /// assert_eq!(Decimal::parse("-0.05").unwrap().div_rounded(2).unwrap().to_string(), "-0.03");
+    /// ```
+    pub fn div_rounded(&self, divisor: i64) -> Option<Decimal> {
+        if divisor == 0 {
+            return None;
+        }
+        Some(self.div_rounded_i128(divisor as i128))
+    }
+
+    fn div_rounded_i128(&self, divisor: i128) -> Decimal {
+        let quotient = self.mantissa / divisor;
+        let remainder = self.mantissa % divisor;
+        let rounded = if remainder.unsigned_abs() * 2 >= divisor.unsigned_abs() {
+            if (self.mantissa < 0) != (divisor < 0) {
+                quotient - 1
+            } else {
+                quotient + 1
+            }
+        } else {
+            quotient
+        };
+        Decimal::new(rounded, self.scale)
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.mantissa as f64 / 10f64.powi(self.scale as i32)
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Decimal) -> Ordering {
+        let scale = self.scale.max(other.scale);
+        match (self.rescale(scale), other.rescale(scale)) {
+            (Some(a), Some(b)) => a.mantissa.cmp(&b.mantissa),
+            // an overflowing rescale means a huge value, compare the approximations
+            _ => self
+                .to_f64()
+                .partial_cmp(&other.to_f64())
+                .unwrap_or(Ordering::Equal),
+        }
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Decimal) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// `0.10` and `0.1` are equal
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Decimal) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl std::fmt::Display for Decimal {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{}{}", sign, digits);
+        }
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (int_part, fraction) = digits.split_at(digits.len() - scale);
+        write!(f, "{}{}.{}", sign, int_part, fraction)
+    }
+}
@@ -0,0 +1,219 @@
+use crate::time::DateError;
+
+/// The error type of all fallible operations of rzdb.
+/// ```
+/// use rzdb::{Db, Error};
+/// let mut db = Db::create("test", "~/.local/rzdb").unwrap();
+/// db.create_or_replace_table("errors").unwrap();
+/// db.create_column("errors", "name").unwrap();
+/// assert!(matches!(db.insert("nothing", vec!["x"]), Err(Error::TableNotFound(_))));
+/// assert!(matches!(
+///     db.insert("errors", vec!["x", "y"]),
+///     Err(Error::ArityMismatch { expected: 1, found: 2, .. })
+/// ));
+/// assert!(matches!(
+///     db.delete_row_at("errors", 3),
+///     Err(Error::IndexOutOfBounds { index: 3, len: 0, .. })
+/// ));
+/// match db.create_column("errors", "name") {
+///     Err(Error::DuplicateColumn { table, column }) => assert_eq!((table, column), ("errors".to_string(), "name".to_string())),
+///     other => panic!("{:?}", other),
+/// }
+/// ```
+#[derive(Debug)]
+pub enum Error {
+    TableNotFound(String),
+    TableExists(String),
+    ColumnNotFound {
+        table: String,
+        column: String,
+    },
+    DuplicateColumn {
+        table: String,
+        column: String,
+    },
+    /// a row has a different number of values than the table has columns
+    ArityMismatch {
+        table: String,
+        expected: usize,
+        found: usize,
+    },
+    /// a row index, or a column or join position within a row, does not exist
+    IndexOutOfBounds {
+        context: String,
+        index: usize,
+        len: usize,
+    },
+    /// a value does not fit the type of its column
+    TypeMismatch {
+        table: String,
+        column: String,
+        expected: String,
+        value: String,
+    },
+    /// an operation is not allowed, e.g. setting a computed column
+    ConstraintViolation {
+        table: String,
+        column: String,
+        message: String,
+    },
+    /// text that can not be parsed, e.g. a table file, a value or an expression;
+    /// line and column count from 1 where known
+    ParseError {
+        line: Option<usize>,
+        column: Option<usize>,
+        message: String,
+    },
+    InvalidDate(DateError),
+    /// a value that can not be used for an operation, e.g. a division by zero
+    InvalidValue(String),
+    Io(std::io::Error),
+}
+
+impl Error {
+    pub(crate) fn parse(message: impl Into<String>) -> Error {
+        Error::ParseError {
+            line: None,
+            column: None,
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn invalid(message: impl Into<String>) -> Error {
+        Error::InvalidValue(message.into())
+    }
+
+    /// prefixes the message of a parse error, e.g. with the file it is in
+    pub(crate) fn in_context(self, context: &str) -> Error {
+        match self {
+            Error::ParseError {
+                line,
+                column,
+                message,
+            } => Error::ParseError {
+                line,
+                column,
+                message: format!("{}: {}", context, message),
+            },
+            e => e,
+        }
+    }
+}
+
+/// an IndexOutOfBounds error unless index < len
+pub(crate) fn check_index(context: &str, index: usize, len: usize) -> Result<(), Error> {
+    if index < len {
+        Ok(())
+    } else {
+        Err(Error::IndexOutOfBounds {
+            context: context.to_string(),
+            index,
+            len,
+        })
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::TableNotFound(table) => write!(f, "Table {} not found", table),
+            Error::TableExists(table) => write!(f, "Table {} already exists", table),
+            Error::ColumnNotFound { table, column } => {
+                write!(f, "Column {} not found in table {}", column, table)
+            }
+            Error::DuplicateColumn { table, column } => {
+                write!(f, "Column {} already exists in table {}", column, table)
+            }
+            Error::ArityMismatch {
+                table,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Table {} has {} columns, but got {} values",
+                table, expected, found
+            ),
+            Error::IndexOutOfBounds {
+                context,
+                index,
+                len,
+            } => write!(f, "{}: index {} out of bounds ({})", context, index, len),
+            Error::TypeMismatch {
+                table,
+                column,
+                expected,
+                value,
+            } => write!(
+                f,
+                "Table {}: column {} of type {} does not accept {}",
+                table, column, expected, value
+            ),
+            Error::ConstraintViolation {
+                table,
+                column,
+                message,
+            } => write!(f, "Table {}: column {} {}", table, column, message),
+            Error::ParseError {
+                line,
+                column,
+                message,
+            } => match (line, column) {
+                (Some(line), Some(column)) => {
+                    write!(f, "line {}, column {}: {}", line, column, message)
+                }
+                (Some(line), None) => write!(f, "line {}: {}", line, message),
+                (None, Some(column)) => write!(f, "column {}: {}", column, message),
+                (None, None) => write!(f, "{}", message),
+            },
+            Error::InvalidDate(e) => write!(f, "{}", e),
+            Error::InvalidValue(message) => write!(f, "{}", message),
+            Error::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::InvalidDate(e) => Some(e),
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<DateError> for Error {
+    fn from(e: DateError) -> Error {
+        Error::InvalidDate(e)
+    }
+}
+
+impl From<std::num::ParseIntError> for Error {
+    fn from(e: std::num::ParseIntError) -> Error {
+        Error::parse(e.to_string())
+    }
+}
+
+impl From<std::num::ParseFloatError> for Error {
+    fn from(e: std::num::ParseFloatError) -> Error {
+        Error::parse(e.to_string())
+    }
+}
+
+impl From<std::str::ParseBoolError> for Error {
+    fn from(e: std::str::ParseBoolError) -> Error {
+        Error::parse(e.to_string())
+    }
+}
+
+impl From<std::string::FromUtf8Error> for Error {
+    fn from(e: std::string::FromUtf8Error) -> Error {
+        Error::invalid(e.to_string())
+    }
+}
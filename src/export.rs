@@ -0,0 +1,68 @@
+use crate::data::Data;
+use crate::json::Json;
+
+/// The formats of `Db::export()`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ExportFormat {
+    /// RFC 4180, fields with commas, quotes or line breaks are quoted
+    Csv,
+    /// tab separated, tabs, line breaks and backslashes in values are written as `\t`,
+    /// `\n`, `\r` and `\\`
+    Tsv,
+    /// an array with an object for every row
+    Json,
+    /// an object for every row on its own line
+    JsonLines,
+    Markdown,
+    Html,
+}
+
+/// A single value as JSON: ints, floats and bools as they are, empty values as null, and
+/// all other values as a string of their text. Decimals are strings too, so that no digits
+/// are lost in a float.
+pub(crate) fn json_value(datum: &Data, text: &str) -> Json {
+    match datum {
+        Data::Empty => Json::Null,
+        Data::Int(n) => Json::Int(*n),
+        Data::Float(n) => Json::Float(*n),
+        Data::Bool(b) => Json::Bool(*b),
+        _ => Json::String(text.to_string()),
+    }
+}
+
+pub(crate) fn tsv_field(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        match c {
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\\' => out.push_str("\\\\"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// a table cell in Markdown, `|` is escaped and line breaks become `<br>`
+pub(crate) fn markdown_cell(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace(['\n', '\r'], "<br>")
+}
+
+pub(crate) fn html_escape(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,1038 @@
+use crate::data::Data;
+use crate::decimal::Decimal;
+use crate::error::Error;
+use crate::time::{local_zone, Date, DateTime, Duration};
+use std::cmp::Ordering;
+
+/// An expression over the columns of a row, e.g. `qty * price` or
+/// `if(age >= 18, 'adult', 'minor')`. Expressions are written as text:
+/// - numbers (`12`, `1.5` is a decimal), strings in single quotes (`'it''s'`), `true`, `false`
+/// - column names, or any column name in double quotes (`"unit price"`)
+/// - `+ - * / %` for numbers, decimals, durations and dates, `||` to concatenate text
+/// - comparisons `= != < <= > >=` returning a bool, combined with `and`, `or` and `not`
+/// - date functions: `today()`, `now()`, `date(text)`, `year(date)`, `month(date)`,
+///   `day(date)`, `weekday(date)` (1 for Monday), `years(from, to)` (full years, e.g. an age)
+/// - string functions: `upper(text)`, `lower(text)`, `trim(text)`, `length(text)`,
+///   `substr(text, start[, length])` (counting characters from 1) and `concat(value, ...)`
+/// - `abs(number)`, `round(number[, digits])`, `if(condition, then, else)` and
+///   `coalesce(value, ...)`, the first value that is not empty
+///
+/// If any value of an operation is empty, the result is empty, except for `concat()` and
+/// `coalesce()`, which skip empty values, and for `and`/`or` if the other value decides
+/// the result, e.g. `false and <empty>` is false.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Expr {
+    Literal(Data),
+    Column(String),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(Box<Expr>, BinaryOp, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Concat,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+/// (name, minimum number of arguments, maximum number of arguments)
+const FUNCTIONS: [(&str, usize, usize); 19] = [
+    ("today", 0, 0),
+    ("now", 0, 0),
+    ("date", 1, 1),
+    ("year", 1, 1),
+    ("month", 1, 1),
+    ("day", 1, 1),
+    ("weekday", 1, 1),
+    ("years", 2, 2),
+    ("abs", 1, 1),
+    ("round", 1, 2),
+    ("if", 3, 3),
+    ("coalesce", 1, usize::MAX),
+    ("upper", 1, 1),
+    ("lower", 1, 1),
+    ("trim", 1, 1),
+    ("length", 1, 1),
+    ("substr", 2, 3),
+    ("concat", 1, usize::MAX),
+    ("replace", 3, 3),
+];
+
+/// names that are not column names, unless quoted
+const KEYWORDS: [&str; 5] = ["true", "false", "and", "or", "not"];
+
+/// keywords are case-insensitive, like function names
+fn is_keyword(name: &str) -> bool {
+    KEYWORDS
+        .iter()
+        .any(|keyword| keyword.eq_ignore_ascii_case(name))
+}
+
+/// the precedence of `not`: it binds weaker than comparisons, but stronger than `and`
+const NOT_PRECEDENCE: u8 = 3;
+
+fn gen_error(msg: String) -> Error {
+    Error::invalid(msg)
+}
+
+impl BinaryOp {
+    fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Rem => "%",
+            BinaryOp::Concat => "||",
+            BinaryOp::Eq => "=",
+            BinaryOp::Ne => "!=",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => ">=",
+            BinaryOp::And => "and",
+            BinaryOp::Or => "or",
+        }
+    }
+
+    /// operators with a higher precedence bind stronger
+    fn precedence(&self) -> u8 {
+        match self {
+            BinaryOp::Or => 1,
+            BinaryOp::And => 2,
+            BinaryOp::Eq
+            | BinaryOp::Ne
+            | BinaryOp::Lt
+            | BinaryOp::Le
+            | BinaryOp::Gt
+            | BinaryOp::Ge => 4,
+            BinaryOp::Concat => 5,
+            BinaryOp::Add | BinaryOp::Sub => 6,
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => 7,
+        }
+    }
+
+    fn from_symbol(symbol: &str) -> Option<BinaryOp> {
+        [
+            BinaryOp::Add,
+            BinaryOp::Sub,
+            BinaryOp::Mul,
+            BinaryOp::Div,
+            BinaryOp::Rem,
+            BinaryOp::Concat,
+            BinaryOp::Eq,
+            BinaryOp::Ne,
+            BinaryOp::Lt,
+            BinaryOp::Le,
+            BinaryOp::Gt,
+            BinaryOp::Ge,
+            BinaryOp::And,
+            BinaryOp::Or,
+        ]
+        .into_iter()
+        .find(|op| op.symbol() == symbol)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+    Number(String),
+    Text(String),
+    Name(String),
+    QuotedName(String),
+    Op(String),
+    Open,
+    Close,
+    Comma,
+}
+
+/// a parse error at a character position counting from 0
+fn parse_error(s: &str, pos: usize, message: &str) -> Error {
+    Error::ParseError {
+        line: None,
+        column: Some(pos + 1),
+        message: format!("{} in: {}", message, s),
+    }
+}
+
+/// splits an expression into tokens and their character positions
+fn tokenize(s: &str) -> Result<(Vec<Token>, Vec<usize>), Error> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = vec![];
+    let mut offsets = vec![];
+    let mut pos = 0;
+    while pos < chars.len() {
+        let ch = chars[pos];
+        if !ch.is_whitespace() {
+            offsets.push(pos);
+        }
+        if ch.is_whitespace() {
+            pos += 1;
+        } else if ch.is_ascii_digit() || (ch == '.' && pos + 1 < chars.len()) {
+            let start = pos;
+            while pos < chars.len() && (chars[pos].is_ascii_digit() || chars[pos] == '.') {
+                pos += 1;
+            }
+            tokens.push(Token::Number(chars[start..pos].iter().collect()));
+        } else if ch.is_alphabetic() || ch == '_' {
+            let start = pos;
+            while pos < chars.len() && (chars[pos].is_alphanumeric() || chars[pos] == '_') {
+                pos += 1;
+            }
+            tokens.push(Token::Name(chars[start..pos].iter().collect()));
+        } else if ch == '\'' || ch == '"' {
+            // quotes inside are doubled
+            let mut text = String::new();
+            pos += 1;
+            loop {
+                match chars.get(pos) {
+                    None => {
+                        return Err(parse_error(
+                            s,
+                            offsets[offsets.len() - 1],
+                            "Unterminated quote",
+                        ))
+                    }
+                    Some(c) if *c == ch => {
+                        if chars.get(pos + 1) == Some(&ch) {
+                            text.push(ch);
+                            pos += 2;
+                        } else {
+                            pos += 1;
+                            break;
+                        }
+                    }
+                    Some(c) => {
+                        text.push(*c);
+                        pos += 1;
+                    }
+                }
+            }
+            tokens.push(if ch == '\'' {
+                Token::Text(text)
+            } else {
+                Token::QuotedName(text)
+            });
+        } else if ch == '(' {
+            tokens.push(Token::Open);
+            pos += 1;
+        } else if ch == ')' {
+            tokens.push(Token::Close);
+            pos += 1;
+        } else if ch == ',' {
+            tokens.push(Token::Comma);
+            pos += 1;
+        } else {
+            let two: String = chars[pos..(pos + 2).min(chars.len())].iter().collect();
+            let op = if ["||", "!=", "<=", ">="].contains(&two.as_str()) {
+                two
+            } else if "+-*/%=<>".contains(ch) {
+                ch.to_string()
+            } else {
+                return Err(parse_error(s, pos, &format!("Unexpected {}", ch)));
+            };
+            pos += op.len();
+            tokens.push(Token::Op(op));
+        }
+    }
+    Ok((tokens, offsets))
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<Token>,
+    offsets: Vec<usize>,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    /// an error at the next token
+    fn error(&self) -> Error {
+        self.error_at(self.pos)
+    }
+
+    /// an error at the given token, or at the end if there are no more tokens
+    fn error_at(&self, token_idx: usize) -> Error {
+        let pos = match self.offsets.get(token_idx) {
+            Some(pos) => *pos,
+            None => self.source.chars().count(),
+        };
+        parse_error(self.source, pos, "Invalid expression")
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), Error> {
+        if self.next() == Some(token) {
+            Ok(())
+        } else {
+            Err(self.error_at(self.pos - 1))
+        }
+    }
+
+    /// parses binary operators of at least the given precedence, all left associative
+    fn binary(&mut self, min_precedence: u8) -> Result<Expr, Error> {
+        let mut left = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Op(symbol)) => {
+                    BinaryOp::from_symbol(symbol).ok_or_else(|| self.error())?
+                }
+                Some(Token::Name(name)) if name.eq_ignore_ascii_case("and") => BinaryOp::And,
+                Some(Token::Name(name)) if name.eq_ignore_ascii_case("or") => BinaryOp::Or,
+                _ => break,
+            };
+            if op.precedence() < min_precedence {
+                break;
+            }
+            self.pos += 1;
+            let right = self.binary(op.precedence() + 1)?;
+            left = Expr::Binary(Box::new(left), op, Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, Error> {
+        if self.peek() == Some(&Token::Op("-".to_string())) {
+            self.pos += 1;
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        if matches!(self.peek(), Some(Token::Name(name)) if name.eq_ignore_ascii_case("not")) {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.binary(NOT_PRECEDENCE + 1)?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, Error> {
+        match self.next() {
+            Some(Token::Number(n)) => {
+                if n.contains('.') {
+                    Ok(Expr::Literal(Data::Decimal(
+                        Decimal::parse(&n).map_err(|_| self.error_at(self.pos - 1))?,
+                    )))
+                } else {
+                    Ok(Expr::Literal(Data::Int(
+                        n.parse::<i64>().map_err(|_| self.error_at(self.pos - 1))?,
+                    )))
+                }
+            }
+            Some(Token::Text(s)) => Ok(Expr::Literal(Data::String(s))),
+            Some(Token::QuotedName(name)) => Ok(Expr::Column(name)),
+            Some(Token::Name(name)) => {
+                let name_idx = self.pos - 1;
+                if self.peek() == Some(&Token::Open) {
+                    self.pos += 1;
+                    let mut args = vec![];
+                    if self.peek() == Some(&Token::Close) {
+                        self.pos += 1;
+                    } else {
+                        loop {
+                            args.push(self.binary(0)?);
+                            match self.next() {
+                                Some(Token::Comma) => {}
+                                Some(Token::Close) => break,
+                                _ => return Err(self.error_at(self.pos - 1)),
+                            }
+                        }
+                    }
+                    let name = name.to_lowercase();
+                    match FUNCTIONS.iter().find(|(n, _, _)| *n == name) {
+                        Some((_, min, max)) if (*min..=*max).contains(&args.len()) => {
+                            Ok(Expr::Call(name, args))
+                        }
+                        Some(_) => Err(parse_error(
+                            self.source,
+                            self.offsets[name_idx],
+                            &format!("Wrong number of arguments for {}()", name),
+                        )),
+                        None => Err(parse_error(
+                            self.source,
+                            self.offsets[name_idx],
+                            &format!("Unknown function {}()", name),
+                        )),
+                    }
+                } else if name.eq_ignore_ascii_case("true") || name.eq_ignore_ascii_case("false") {
+                    Ok(Expr::Literal(Data::Bool(name.eq_ignore_ascii_case("true"))))
+                } else if is_keyword(&name) {
+                    Err(self.error_at(name_idx))
+                } else {
+                    Ok(Expr::Column(name))
+                }
+            }
+            Some(Token::Open) => {
+                let expr = self.binary(0)?;
+                self.expect(Token::Close)?;
+                Ok(expr)
+            }
+            _ => Err(self.error_at(self.pos - 1)),
+        }
+    }
+}
+
+impl Expr {
+    /// Parses an expression.
+    /// ```
+    /// use rzdb::Expr;
+    /// let expr = Expr::parse("qty*(price + 0.50)").unwrap();
+    /// assert_eq!(expr.to_string(), "qty * (price + 0.50)");
+    /// assert_eq!(Expr::parse(&expr.to_string()).unwrap(), expr);
+    /// assert!(Expr::parse("qty *").is_err());
+    /// assert!(Expr::parse("nofunction(qty)").is_err());
+    /// ```
+    pub fn parse(s: &str) -> Result<Expr, Error> {
+        let (tokens, offsets) = tokenize(s)?;
+        let mut parser = Parser {
+            source: s,
+            tokens,
+            offsets,
+            pos: 0,
+        };
+        let expr = parser.binary(0)?;
+        if parser.pos < parser.tokens.len() {
+            return Err(parser.error());
+        }
+        Ok(expr)
+    }
+
+    /// names of all columns used by the expression
+    pub fn columns(&self) -> Vec<String> {
+        let mut columns = vec![];
+        self.collect_columns(&mut columns);
+        columns
+    }
+
+    fn collect_columns(&self, columns: &mut Vec<String>) {
+        match self {
+            Expr::Literal(_) => {}
+            Expr::Column(name) => {
+                if !columns.contains(name) {
+                    columns.push(name.clone());
+                }
+            }
+            Expr::Neg(expr) | Expr::Not(expr) => expr.collect_columns(columns),
+            Expr::Binary(left, _, right) => {
+                left.collect_columns(columns);
+                right.collect_columns(columns);
+            }
+            Expr::Call(_, args) => args.iter().for_each(|arg| arg.collect_columns(columns)),
+        }
+    }
+
+    /// replaces references to a renamed column
+    pub fn rename_column(&mut self, old_name: &str, new_name: &str) {
+        match self {
+            Expr::Literal(_) => {}
+            Expr::Column(name) => {
+                if name == old_name {
+                    *name = new_name.to_string();
+                }
+            }
+            Expr::Neg(expr) | Expr::Not(expr) => expr.rename_column(old_name, new_name),
+            Expr::Binary(left, _, right) => {
+                left.rename_column(old_name, new_name);
+                right.rename_column(old_name, new_name);
+            }
+            Expr::Call(_, args) => args
+                .iter_mut()
+                .for_each(|arg| arg.rename_column(old_name, new_name)),
+        }
+    }
+
+    /// Evaluates the expression, `column` returns the value of a column by name.
+    /// ```
+    /// use rzdb::{Data, Expr};
+    /// let column = |name: &str| match name {
+    ///     "qty" => Some(Data::Int(3)),
+    ///     "price" => Some(Data::parse("2.5")),
+    ///     "note" => Some(Data::Empty),
+    ///     _ => None,
+    /// };
+    /// let eval = |s: &str| Expr::parse(s).unwrap().eval(&column).unwrap();
+    /// assert_eq!(eval("qty * price"), Data::Float(7.5));
+    /// assert_eq!(eval("qty || ' pieces'"), Data::String("3 pieces".to_string()));
+    /// assert_eq!(eval("if(qty > 2, 'many', 'few')"), Data::String("many".to_string()));
+    /// assert_eq!(eval("qty + note"), Data::Empty);
+    /// assert!(Expr::parse("missing + 1").unwrap().eval(&column).is_err());
+    /// ```
+    pub fn eval(&self, column: &dyn Fn(&str) -> Option<Data>) -> Result<Data, Error> {
+        match self {
+            Expr::Literal(datum) => Ok(datum.clone()),
+            Expr::Column(name) => {
+                column(name).ok_or_else(|| gen_error(format!("Column {} not found", name)))
+            }
+            Expr::Neg(expr) => {
+                let value = expr.eval(column)?;
+                match value {
+                    Data::Empty => Ok(Data::Empty),
+                    Data::Int(n) => Ok(Data::Int(
+                        n.checked_neg()
+                            .ok_or_else(|| gen_error(format!("Overflow: -{}", n)))?,
+                    )),
+                    Data::Float(n) => Ok(Data::Float(-n)),
+                    Data::Decimal(n) => Ok(Data::Decimal(Decimal::new(-n.mantissa(), n.scale()))),
+                    Data::Duration(n) => Ok(Data::Duration(Duration::from_seconds(-n.seconds()))),
+                    _ => Err(gen_error(format!("Can not negate {}", value))),
+                }
+            }
+            Expr::Not(expr) => match expr.eval(column)? {
+                Data::Bool(b) => Ok(Data::Bool(!b)),
+                Data::Empty => Ok(Data::Empty),
+                other => Err(gen_error(format!("not: {} is not a bool", other))),
+            },
+            Expr::Binary(left, op @ (BinaryOp::And | BinaryOp::Or), right) => {
+                // the right side is only evaluated if the left side does not decide
+                let deciding = *op == BinaryOp::Or;
+                let as_bool = |value: Data| match value {
+                    Data::Bool(b) => Ok(Some(b)),
+                    Data::Empty => Ok(None),
+                    other => Err(gen_error(format!(
+                        "{}: {} is not a bool",
+                        op.symbol(),
+                        other
+                    ))),
+                };
+                let left = as_bool(left.eval(column)?)?;
+                if left == Some(deciding) {
+                    return Ok(Data::Bool(deciding));
+                }
+                let right = as_bool(right.eval(column)?)?;
+                Ok(match (left, right) {
+                    (_, Some(b)) if b == deciding => Data::Bool(deciding),
+                    (Some(_), Some(_)) => Data::Bool(!deciding),
+                    _ => Data::Empty,
+                })
+            }
+            Expr::Binary(left, op, right) => {
+                let left = left.eval(column)?;
+                let right = right.eval(column)?;
+                binary(*op, &left, &right)
+            }
+            Expr::Call(name, args) => {
+                if name == "if" {
+                    // only the chosen branch is evaluated
+                    return match args[0].eval(column)? {
+                        Data::Bool(true) => args[1].eval(column),
+                        Data::Bool(false) | Data::Empty => args[2].eval(column),
+                        other => Err(gen_error(format!("if(): {} is not a bool", other))),
+                    };
+                }
+                let mut values = vec![];
+                for arg in args {
+                    values.push(arg.eval(column)?);
+                }
+                call(name, &values)
+            }
+        }
+    }
+}
+
+fn numeric_error(op: BinaryOp, left: &Data, right: &Data) -> Error {
+    gen_error(format!(
+        "Unsupported operation: {} {} {}",
+        left,
+        op.symbol(),
+        right
+    ))
+}
+
+fn as_f64(datum: &Data) -> Option<f64> {
+    match datum {
+        Data::Int(n) => Some(*n as f64),
+        Data::Float(n) => Some(*n),
+        Data::Decimal(n) => Some(n.to_f64()),
+        _ => None,
+    }
+}
+
+fn as_decimal(datum: &Data) -> Option<Decimal> {
+    match datum {
+        Data::Int(n) => Some(Decimal::from_int(*n)),
+        Data::Decimal(n) => Some(*n),
+        _ => None,
+    }
+}
+
+fn binary(op: BinaryOp, left: &Data, right: &Data) -> Result<Data, Error> {
+    if *left == Data::Empty || *right == Data::Empty {
+        return Ok(Data::Empty);
+    }
+    let error = || numeric_error(op, left, right);
+    let overflow = || gen_error(format!("Overflow: {} {} {}", left, op.symbol(), right));
+    match op {
+        BinaryOp::Concat => Ok(Data::String(format!("{}{}", left, right))),
+        BinaryOp::Eq | BinaryOp::Ne => {
+            let equal = left.compare(right) == Some(Ordering::Equal);
+            Ok(Data::Bool(equal == (op == BinaryOp::Eq)))
+        }
+        BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+            let ordering = left.compare(right).ok_or_else(error)?;
+            Ok(Data::Bool(match op {
+                BinaryOp::Lt => ordering == Ordering::Less,
+                BinaryOp::Le => ordering != Ordering::Greater,
+                BinaryOp::Gt => ordering == Ordering::Greater,
+                _ => ordering != Ordering::Less,
+            }))
+        }
+        BinaryOp::Add | BinaryOp::Sub => {
+            let sign = if op == BinaryOp::Add { 1 } else { -1 };
+            let signed = |d: &Duration| if sign == 1 { Some(*d) } else { d.checked_neg() };
+            match (left, right) {
+                (Data::Int(a), Data::Int(b)) => Ok(Data::Int(
+                    a.checked_add(b.checked_mul(sign).ok_or_else(overflow)?)
+                        .ok_or_else(overflow)?,
+                )),
+                (Data::Date(a), Data::Date(b)) if op == BinaryOp::Sub => {
+                    Ok(Data::Int(a.days_since(b)))
+                }
+                (Data::Date(a), Data::Int(days)) => {
+                    let days = days.checked_mul(sign).ok_or_else(overflow)?;
+                    Ok(Data::Date(a.checked_add_days(days).ok_or_else(overflow)?))
+                }
+                (Data::Int(days), Data::Date(a)) if op == BinaryOp::Add => {
+                    Ok(Data::Date(a.checked_add_days(*days).ok_or_else(overflow)?))
+                }
+                (Data::Date(a), Data::Duration(d)) => {
+                    let d = signed(d).ok_or_else(overflow)?;
+                    Ok(Data::Date(a.checked_add_duration(&d).ok_or_else(overflow)?))
+                }
+                (Data::DateTime(a), Data::DateTime(b)) if op == BinaryOp::Sub => Ok(
+                    Data::Duration(a.checked_duration_since(b).ok_or_else(overflow)?),
+                ),
+                (Data::DateTime(a), Data::Duration(d)) => {
+                    let d = signed(d).ok_or_else(overflow)?;
+                    Ok(Data::DateTime(
+                        a.checked_add_duration(&d).ok_or_else(overflow)?,
+                    ))
+                }
+                (Data::Time(a), Data::Time(b)) if op == BinaryOp::Sub => {
+                    Ok(Data::Duration(a.duration_since(b)))
+                }
+                (Data::Time(a), Data::Duration(d)) => {
+                    Ok(Data::Time(a.add_duration(&signed(d).ok_or_else(overflow)?)))
+                }
+                (Data::Duration(a), Data::Duration(b)) => {
+                    let b = signed(b).ok_or_else(overflow)?;
+                    Ok(Data::Duration(
+                        a.checked_add_duration(&b).ok_or_else(overflow)?,
+                    ))
+                }
+                (Data::Duration(d), Data::Date(_) | Data::DateTime(_) | Data::Time(_))
+                    if op == BinaryOp::Add =>
+                {
+                    binary(op, right, &Data::Duration(*d))
+                }
+                _ => {
+                    if let (Some(a), Some(b)) = (as_decimal(left), as_decimal(right)) {
+                        let b = Decimal::new(sign as i128 * b.mantissa(), b.scale());
+                        Ok(Data::Decimal(a.checked_add(&b).ok_or_else(overflow)?))
+                    } else if let (Some(a), Some(b)) = (as_f64(left), as_f64(right)) {
+                        Ok(Data::Float(a + sign as f64 * b))
+                    } else {
+                        Err(error())
+                    }
+                }
+            }
+        }
+        BinaryOp::Mul => match (left, right) {
+            (Data::Int(a), Data::Int(b)) => Ok(Data::Int(a.checked_mul(*b).ok_or_else(overflow)?)),
+            (Data::Duration(d), Data::Int(n)) | (Data::Int(n), Data::Duration(d)) => {
+                Ok(Data::Duration(Duration::from_seconds(
+                    d.seconds().checked_mul(*n).ok_or_else(overflow)?,
+                )))
+            }
+            _ => {
+                if let (Some(a), Some(b)) = (as_decimal(left), as_decimal(right)) {
+                    Ok(Data::Decimal(a.checked_mul(&b).ok_or_else(overflow)?))
+                } else if let (Some(a), Some(b)) = (as_f64(left), as_f64(right)) {
+                    Ok(Data::Float(a * b))
+                } else {
+                    Err(error())
+                }
+            }
+        },
+        BinaryOp::Rem => {
+            let division_by_zero = || gen_error(format!("Division by zero: {} % {}", left, right));
+            match (left, right) {
+                (Data::Int(a), Data::Int(b)) => {
+                    Ok(Data::Int(a.checked_rem(*b).ok_or_else(division_by_zero)?))
+                }
+                _ => {
+                    if let (Some(a), Some(b)) = (as_decimal(left), as_decimal(right)) {
+                        Ok(Data::Decimal(
+                            a.checked_rem(&b).ok_or_else(division_by_zero)?,
+                        ))
+                    } else if let (Some(a), Some(b)) = (as_f64(left), as_f64(right)) {
+                        if b == 0.0 {
+                            Err(division_by_zero())
+                        } else {
+                            Ok(Data::Float(a % b))
+                        }
+                    } else {
+                        Err(error())
+                    }
+                }
+            }
+        }
+        BinaryOp::And | BinaryOp::Or => unreachable!("evaluated in Expr::eval()"),
+        BinaryOp::Div => {
+            let division_by_zero = || gen_error(format!("Division by zero: {} / {}", left, right));
+            match (left, right) {
+                (Data::Decimal(a), Data::Int(n)) => Ok(Data::Decimal(
+                    a.div_rounded(*n).ok_or_else(division_by_zero)?,
+                )),
+                (Data::Duration(d), Data::Int(n)) => {
+                    if *n == 0 {
+                        return Err(division_by_zero());
+                    }
+                    let seconds = (d.seconds() as f64 / *n as f64).round() as i64;
+                    Ok(Data::Duration(Duration::from_seconds(seconds)))
+                }
+                _ => match (as_f64(left), as_f64(right)) {
+                    (Some(a), Some(b)) => {
+                        if b == 0.0 {
+                            Err(division_by_zero())
+                        } else {
+                            Ok(Data::Float(a / b))
+                        }
+                    }
+                    _ => Err(error()),
+                },
+            }
+        }
+    }
+}
+
+/// date of a date or point in time, points in time are taken in the system's zone
+fn as_date(name: &str, datum: &Data) -> Result<Date, Error> {
+    match datum {
+        Data::Date(date) => Ok(date.clone()),
+        Data::DateTime(dt) => Ok(dt.to_date_time(local_zone()).0),
+        _ => Err(gen_error(format!("{}(): {} is not a date", name, datum))),
+    }
+}
+
+fn call(name: &str, values: &[Data]) -> Result<Data, Error> {
+    match name {
+        "coalesce" => {
+            return Ok(values
+                .iter()
+                .find(|value| **value != Data::Empty)
+                .cloned()
+                .unwrap_or(Data::Empty))
+        }
+        "concat" => return Ok(Data::String(values.iter().map(|v| v.to_string()).collect())),
+        _ => {}
+    }
+    if values.contains(&Data::Empty) {
+        return Ok(Data::Empty);
+    }
+    let text = |idx: usize| -> Result<&str, Error> {
+        match &values[idx] {
+            Data::String(s) => Ok(s),
+            other => Err(gen_error(format!("{}(): {} is not a text", name, other))),
+        }
+    };
+    let int = |idx: usize| -> Result<i64, Error> {
+        match &values[idx] {
+            Data::Int(n) => Ok(*n),
+            other => Err(gen_error(format!("{}(): {} is not an int", name, other))),
+        }
+    };
+    match name {
+        "upper" => Ok(Data::String(text(0)?.to_uppercase())),
+        "lower" => Ok(Data::String(text(0)?.to_lowercase())),
+        "trim" => Ok(Data::String(text(0)?.trim().to_string())),
+        "length" => Ok(Data::Int(text(0)?.chars().count() as i64)),
+        "substr" => {
+            let start = (int(1)?.max(1) - 1) as usize;
+            let length = match values.get(2) {
+                Some(_) => int(2)?.max(0) as usize,
+                None => usize::MAX,
+            };
+            Ok(Data::String(
+                text(0)?.chars().skip(start).take(length).collect(),
+            ))
+        }
+        "replace" => Ok(Data::String(text(0)?.replace(text(1)?, text(2)?))),
+        "today" => Ok(Data::Date(Date::today())),
+        "now" => Ok(Data::DateTime(DateTime::now())),
+        "date" => match &values[0] {
+            Data::Date(date) => Ok(Data::Date(date.clone())),
+            other => Ok(Data::Date(as_date(name, other).or_else(|_| {
+                Date::parse(&other.to_string()).map_err(Error::from)
+            })?)),
+        },
+        "year" => Ok(Data::Int(as_date(name, &values[0])?.year() as i64)),
+        "month" => Ok(Data::Int(as_date(name, &values[0])?.month() as i64)),
+        "day" => Ok(Data::Int(as_date(name, &values[0])?.day() as i64)),
+        "weekday" => Ok(Data::Int(
+            as_date(name, &values[0])?.weekday().number_from_monday() as i64,
+        )),
+        "years" => {
+            let from = as_date(name, &values[0])?;
+            let to = as_date(name, &values[1])?;
+            let mut years = to.year() as i64 - from.year() as i64;
+            if (to.month(), to.day()) < (from.month(), from.day()) {
+                years -= 1;
+            }
+            Ok(Data::Int(years))
+        }
+        "abs" => {
+            let abs_overflow = || gen_error(format!("Overflow: abs({})", values[0]));
+            match &values[0] {
+                Data::Int(n) => Ok(Data::Int(n.checked_abs().ok_or_else(abs_overflow)?)),
+                Data::Float(n) => Ok(Data::Float(n.abs())),
+                Data::Decimal(n) => Ok(Data::Decimal(Decimal::new(
+                    n.mantissa().checked_abs().ok_or_else(abs_overflow)?,
+                    n.scale(),
+                ))),
+                Data::Duration(n) => Ok(Data::Duration(Duration::from_seconds(
+                    n.seconds().checked_abs().ok_or_else(abs_overflow)?,
+                ))),
+                other => Err(gen_error(format!("abs(): {} is not a number", other))),
+            }
+        }
+        "round" => {
+            let digits = match values.get(1) {
+                None => 0,
+                Some(Data::Int(n)) if (0..=18).contains(n) => *n as u32,
+                Some(other) => return Err(gen_error(format!("round(): invalid digits {}", other))),
+            };
+            match &values[0] {
+                Data::Int(n) => Ok(Data::Int(*n)),
+                Data::Float(n) => {
+                    let factor = 10f64.powi(digits as i32);
+                    Ok(Data::Float((n * factor).round() / factor))
+                }
+                Data::Decimal(n) => Ok(Data::Decimal(n.round_to_scale(digits))),
+                other => Err(gen_error(format!("round(): {} is not a number", other))),
+            }
+        }
+        _ => Err(gen_error(format!("Unknown function {}()", name))),
+    }
+}
+
+fn fmt_name(name: &str) -> String {
+    let is_plain = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+        && !is_keyword(name);
+    if is_plain {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+}
+
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Expr::Literal(Data::String(s)) => write!(f, "'{}'", s.replace('\'', "''")),
+            Expr::Literal(datum) => write!(f, "{}", datum),
+            Expr::Column(name) => write!(f, "{}", fmt_name(name)),
+            Expr::Neg(expr) => match **expr {
+                Expr::Binary(..) | Expr::Not(_) => write!(f, "-({})", expr),
+                _ => write!(f, "-{}", expr),
+            },
+            Expr::Not(expr) => match **expr {
+                Expr::Binary(_, op, _) if op.precedence() <= NOT_PRECEDENCE => {
+                    write!(f, "not ({})", expr)
+                }
+                _ => write!(f, "not {}", expr),
+            },
+            Expr::Binary(left, op, right) => {
+                // parentheses are needed for weaker operators, and on the right side for
+                // operators of the same strength, as all operators are left associative
+                let needs_parens = |expr: &Expr, right_side: bool| match expr {
+                    Expr::Binary(_, inner, _) => {
+                        inner.precedence() < op.precedence()
+                            || (right_side && inner.precedence() == op.precedence())
+                    }
+                    Expr::Not(_) => NOT_PRECEDENCE < op.precedence(),
+                    _ => false,
+                };
+                if needs_parens(left, false) {
+                    write!(f, "({})", left)?;
+                } else {
+                    write!(f, "{}", left)?;
+                }
+                write!(f, " {} ", op.symbol())?;
+                if needs_parens(right, true) {
+                    write!(f, "({})", right)
+                } else {
+                    write!(f, "{}", right)
+                }
+            }
+            Expr::Call(name, args) => {
+                let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+                write!(f, "{}({})", name, args.join(", "))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_display() {
+        for (input, output) in [
+            ("a+b*c", "a + b * c"),
+            ("(a+b)*c", "(a + b) * c"),
+            ("a-(b-c)", "a - (b - c)"),
+            ("(a-b)-c", "a - b - c"),
+            ("-(a+1)", "-(a + 1)"),
+            ("\"unit price\"*2", "\"unit price\" * 2"),
+            ("'it''s' || \"true\"", "'it''s' || \"true\""),
+            ("IF(a>=1,true,'no')", "if(a >= 1, true, 'no')"),
+            ("years(born, today())", "years(born, today())"),
+            ("a % 2=0 AND NOT b or c", "a % 2 = 0 and not b or c"),
+            ("not (a or b) and c", "not (a or b) and c"),
+            ("a and (b or c)", "a and (b or c)"),
+            ("-(not a)", "-(not a)"),
+            ("\"and\" || 'x'", "\"and\" || 'x'"),
+            ("substr(trim(name),2)", "substr(trim(name), 2)"),
+        ] {
+            let expr = Expr::parse(input).unwrap();
+            assert_eq!(expr.to_string(), output);
+            assert_eq!(Expr::parse(output).unwrap(), expr);
+        }
+        for (input, column) in [("a + * b", 5), ("f(a) + 1", 1), ("(a", 3), ("a ? b", 3)] {
+            match Expr::parse(input) {
+                Err(Error::ParseError { column: c, .. }) => {
+                    assert_eq!(c, Some(column), "{}", input)
+                }
+                other => panic!("{}: {:?}", input, other),
+            }
+        }
+        for input in [
+            "", "a +", "(a", "a b", "'open", "round()", "a ? b", "and", "a or",
+        ] {
+            assert!(Expr::parse(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_eval() {
+        let column = |name: &str| match name {
+            "born" => Some(Data::Date(Date::new(1990, 6, 15).unwrap())),
+            "start" => Some(Data::parse("2024-03-01T08:00:00Z")),
+            "end" => Some(Data::parse("2024-03-01T16:30:00Z")),
+            "amount" => Some(Data::Decimal(Decimal::parse("10.05").unwrap())),
+            "zero" => Some(Data::Int(0)),
+            _ => None,
+        };
+        let eval = |s: &str| Expr::parse(s).unwrap().eval(&column).unwrap().to_string();
+        assert_eq!(eval("born + 17"), "1990-07-02");
+        assert_eq!(eval("date('1990-07-02') - born"), "17");
+        assert_eq!(eval("years(born, date('2024-06-14'))"), "33");
+        assert_eq!(eval("years(born, date('2024-06-15'))"), "34");
+        assert_eq!(eval("end - start"), "08:30:00");
+        assert_eq!(eval("year(start) * 100 + month(start)"), "202403");
+        assert_eq!(eval("amount * 3"), "30.15");
+        assert_eq!(eval("amount / 2"), "5.03");
+        assert_eq!(eval("round(amount * 1.19, 2)"), "11.96");
+        assert_eq!(eval("-amount + 0.05"), "-10.00");
+        assert_eq!(eval("amount > 10"), "true");
+        assert!(Expr::parse("1 / zero").unwrap().eval(&column).is_err());
+        assert!(Expr::parse("born * 2").unwrap().eval(&column).is_err());
+    }
+
+    #[test]
+    fn test_eval_strings_and_logic() {
+        let column = |name: &str| match name {
+            "name" => Some(Data::String("  Jürgen Müller ".into())),
+            "amount" => Some(Data::Decimal(Decimal::parse("10.05").unwrap())),
+            "missing" => Some(Data::Empty),
+            "zero" => Some(Data::Int(0)),
+            _ => None,
+        };
+        let eval = |s: &str| Expr::parse(s).unwrap().eval(&column).unwrap().to_string();
+        assert_eq!(eval("upper(trim(name))"), "JÜRGEN MÜLLER");
+        assert_eq!(eval("lower(substr(trim(name), 8))"), "müller");
+        assert_eq!(eval("substr(trim(name), 2, 3)"), "ürg");
+        assert_eq!(eval("length(trim(name))"), "13");
+        assert_eq!(eval("replace(trim(name), 'ü', 'ue')"), "Juergen Mueller");
+        assert_eq!(eval("concat('a', missing, 1, true)"), "a1true");
+        assert_eq!(eval("coalesce(missing, amount, 1)"), "10.05");
+        assert_eq!(eval("upper(missing)"), "");
+        assert_eq!(eval("17 % 5"), "2");
+        assert_eq!(eval("-17 % 5"), "-2");
+        assert_eq!(eval("amount % 1"), "0.05");
+        assert_eq!(eval("7.5 % 2"), "1.5");
+        assert_eq!(eval("1 < 2 and 2 < 3"), "true");
+        assert_eq!(eval("not 1 < 2 or false"), "false");
+        // three-valued logic: empty is unknown
+        assert_eq!(eval("missing > 1 and false"), "false");
+        assert_eq!(eval("missing > 1 or true"), "true");
+        assert_eq!(eval("missing > 1 or false"), "");
+        assert_eq!(eval("not missing"), "");
+        // the right side is not evaluated if the left side decides
+        assert_eq!(eval("false and 1 / zero = 1"), "false");
+        assert!(Expr::parse("1 % zero").unwrap().eval(&column).is_err());
+        assert!(Expr::parse("1 and true").unwrap().eval(&column).is_err());
+        assert!(Expr::parse("upper(1)").unwrap().eval(&column).is_err());
+    }
+
+    #[test]
+    fn test_eval_overflow() {
+        let column = |name: &str| match name {
+            "born" => Some(Data::Date(Date::new(1990, 6, 15).unwrap())),
+            "start" => Some(Data::parse("2024-03-01T08:00:00Z")),
+            "noon" => Some(Data::parse("12:00")),
+            "min" => Some(Data::Int(i64::MIN)),
+            "max" => Some(Data::Int(i64::MAX)),
+            "shortest" => Some(Data::Duration(Duration::from_seconds(i64::MIN))),
+            "longest" => Some(Data::Duration(Duration::from_seconds(i64::MAX))),
+            _ => None,
+        };
+        for input in [
+            "abs(min)",
+            "abs(shortest)",
+            "born + max",
+            "born - min",
+            "max + born",
+            "born + 30000000",
+            "born - shortest",
+            "start + longest",
+            "start - shortest",
+            "longest + longest",
+            "shortest - longest",
+            "longest - shortest",
+            "noon - shortest",
+        ] {
+            match Expr::parse(input).unwrap().eval(&column) {
+                Err(Error::InvalidValue(_)) => {}
+                other => panic!("{}: {:?}", input, other),
+            }
+        }
+        let eval = |s: &str| Expr::parse(s).unwrap().eval(&column).unwrap().to_string();
+        assert_eq!(eval("abs(min + 1)"), i64::MAX.to_string());
+        assert_eq!(eval("noon + longest"), "03:30:07");
+    }
+}
@@ -0,0 +1,767 @@
+use crate::column_type::ColumnType;
+use crate::csv::{CsvReader, Dialect};
+use crate::data::Data;
+use crate::error::Error;
+use crate::join::Join;
+use crate::json::Json;
+use crate::table::Table;
+use crate::time::DateFormat;
+use std::fs::File;
+use std::io::{BufReader, Read};
+
+/// bytes at the start of a file used to detect the encoding and the dialect
+const SAMPLE_BYTES: u64 = 64 * 1024;
+
+/// The character encoding of an imported file.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Encoding {
+    /// UTF-16 if the file starts with its byte order mark, UTF-8 if the start of the
+    /// file is valid UTF-8, Latin-1 otherwise
+    Auto,
+    Utf8,
+    /// ISO 8859-1, every byte is a character
+    Latin1,
+    /// little or big endian as given by the byte order mark, little endian without one
+    Utf16,
+}
+
+/// Options of `Db::import_csv()`. The defaults detect everything:
+/// ```
+/// use rzdb::{Dialect, ImportOptions};
+/// let options = ImportOptions {
+///     dialect: Some(Dialect { delimiter: ';', ..Dialect::default() }),
+///     header: Some(false),
+///     ..ImportOptions::default()
+/// };
+/// assert_eq!(options.sample_rows, 100);
+/// ```
+#[derive(Clone, Debug)]
+pub struct ImportOptions {
+    /// None to guess the dialect from the start of the file, see `Dialect::sniff()`
+    pub dialect: Option<Dialect>,
+    /// whether the first record holds the column names, None to guess
+    pub header: Option<bool>,
+    pub encoding: Encoding,
+    /// the number of rows the type of each column is inferred from
+    pub sample_rows: usize,
+    /// whether a later value that does not fit the inferred type of its column is an error;
+    /// otherwise the column becomes an `Any` column
+    pub strict: bool,
+}
+
+impl Default for ImportOptions {
+    fn default() -> ImportOptions {
+        ImportOptions {
+            dialect: None,
+            header: None,
+            encoding: Encoding::Auto,
+            sample_rows: 100,
+            strict: false,
+        }
+    }
+}
+
+/// an encoding with the byte order resolved
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Charset {
+    Utf8,
+    Latin1,
+    Utf16 { big_endian: bool },
+}
+
+impl Charset {
+    fn detect(encoding: Encoding, sample: &[u8]) -> Charset {
+        let utf16 = Charset::Utf16 {
+            big_endian: sample.starts_with(&[0xfe, 0xff]),
+        };
+        match encoding {
+            Encoding::Utf8 => Charset::Utf8,
+            Encoding::Latin1 => Charset::Latin1,
+            Encoding::Utf16 => utf16,
+            Encoding::Auto if sample.starts_with(&[0xff, 0xfe]) => utf16,
+            Encoding::Auto if sample.starts_with(&[0xfe, 0xff]) => utf16,
+            // the sample may end within a character
+            Encoding::Auto => match std::str::from_utf8(sample) {
+                Err(e) if e.error_len().is_some() => Charset::Latin1,
+                _ => Charset::Utf8,
+            },
+        }
+    }
+}
+
+/// converts the text read from `inner` to UTF-8
+struct Decoder<R> {
+    inner: R,
+    charset: Charset,
+    /// bytes read but not yet decoded, the rest of a UTF-16 character
+    pending: Vec<u8>,
+    decoded: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: Read> Decoder<R> {
+    fn new(inner: R, charset: Charset) -> Decoder<R> {
+        Decoder {
+            inner,
+            charset,
+            pending: vec![],
+            decoded: vec![],
+            pos: 0,
+        }
+    }
+
+    fn decode(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.decoded.clear();
+        self.pos = 0;
+        match self.charset {
+            Charset::Utf8 => self.decoded.extend_from_slice(bytes),
+            Charset::Latin1 => {
+                let text: String = bytes.iter().map(|b| *b as char).collect();
+                self.decoded.extend_from_slice(text.as_bytes());
+            }
+            Charset::Utf16 { big_endian } => {
+                self.pending.extend_from_slice(bytes);
+                let mut units: Vec<u16> = self
+                    .pending
+                    .chunks_exact(2)
+                    .map(|pair| match big_endian {
+                        true => u16::from_be_bytes([pair[0], pair[1]]),
+                        false => u16::from_le_bytes([pair[0], pair[1]]),
+                    })
+                    .collect();
+                let mut rest = self.pending.len() % 2;
+                // keep a high surrogate until its low surrogate is read
+                if !bytes.is_empty() && matches!(units.last(), Some(0xd800..=0xdbff)) {
+                    units.pop();
+                    rest += 2;
+                }
+                self.pending.drain(..self.pending.len() - rest);
+                if bytes.is_empty() && !self.pending.is_empty() {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "incomplete UTF-16 character at the end",
+                    ));
+                }
+                let text = char::decode_utf16(units)
+                    .collect::<Result<String, _>>()
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+                self.decoded.extend_from_slice(text.as_bytes());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.pos == self.decoded.len() {
+            let mut chunk = [0; 8192];
+            let len = self.inner.read(&mut chunk)?;
+            self.decode(&chunk[..len])?;
+            if len == 0 {
+                break;
+            }
+        }
+        let len = buf.len().min(self.decoded.len() - self.pos);
+        buf[..len].copy_from_slice(&self.decoded[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+/// Reads a csv file into a new table, see `Db::import_csv()`.
+pub(crate) fn import_csv(
+    path: &str,
+    table_name: &str,
+    options: &ImportOptions,
+    date_format: &DateFormat,
+) -> Result<Table, Error> {
+    let mut sample = vec![];
+    File::open(path)?
+        .take(SAMPLE_BYTES)
+        .read_to_end(&mut sample)?;
+    let charset = Charset::detect(options.encoding, &sample);
+    let dialect = match options.dialect {
+        Some(dialect) => dialect,
+        None => {
+            let truncated = sample.len() as u64 == SAMPLE_BYTES;
+            let mut text = vec![];
+            // the sample may end within a UTF-16 character, errors are found when reading
+            let _ = Decoder::new(&sample[..], charset).read_to_end(&mut text);
+            let mut text = String::from_utf8_lossy(&text).into_owned();
+            // without the last line, which may be incomplete
+            if truncated {
+                text.truncate(text.rfind('\n').unwrap_or(0));
+            }
+            Dialect::sniff(&text)
+        }
+    };
+
+    let decoder = Decoder::new(File::open(path)?, charset);
+    let mut records = CsvReader::with_dialect(BufReader::new(decoder), dialect);
+    let context = format!("import_csv({})", path);
+    let in_file = |e: Error| e.in_context(&context);
+
+    // the first records with their lines, blank lines are skipped
+    let mut first_records = vec![];
+    while first_records.len() <= options.sample_rows {
+        let Some(record) = records.next() else {
+            break;
+        };
+        let fields = record.map_err(in_file)?;
+        if !fields.is_empty() {
+            first_records.push((records.record_line(), fields));
+        }
+    }
+    let header = match options.header {
+        Some(header) => header,
+        None => has_header(&first_records, date_format),
+    };
+    let names = match header && !first_records.is_empty() {
+        true => first_records.remove(0).1,
+        false => vec![],
+    };
+    first_records.truncate(options.sample_rows);
+    let num_columns = first_records
+        .iter()
+        .map(|(_, fields)| fields.len())
+        .max()
+        .unwrap_or(0)
+        .max(names.len());
+    let mut types = infer_types(&first_records, num_columns, date_format);
+
+    // the table is created at the end, when the types of all columns are known
+    let mut rows = vec![];
+    let mut insert = |line: usize, fields: Vec<String>| -> Result<(), Error> {
+        if fields.len() > num_columns {
+            return Err(Error::ParseError {
+                line: Some(line),
+                column: None,
+                message: format!(
+                    "{}: the row has {} fields, but the table has {} columns",
+                    context,
+                    fields.len(),
+                    num_columns
+                ),
+            });
+        }
+        let mut data = vec![];
+        for (field, column_type) in fields.iter().zip(types.iter_mut()) {
+            let value = match column_type {
+                ColumnType::String => field.as_str(),
+                _ => field.trim(),
+            };
+            let datum = match column_type.parse_value_with(value, date_format) {
+                Ok(datum) => datum,
+                Err(_) if options.strict => {
+                    return Err(Error::ParseError {
+                        line: Some(line),
+                        column: None,
+                        message: format!(
+                            "{}: {} is not of type {}, which was inferred from the first {} rows",
+                            context, field, column_type, options.sample_rows
+                        ),
+                    })
+                }
+                // the values read so far stay as they are, an Any column takes them all
+                Err(_) => {
+                    *column_type = ColumnType::Any;
+                    ColumnType::Any.parse_value_with(field.trim(), date_format)?
+                }
+            };
+            data.push(datum);
+        }
+        data.resize(num_columns, Data::Empty);
+        rows.push(data);
+        Ok(())
+    };
+    for (line, fields) in first_records {
+        insert(line, fields)?;
+    }
+    while let Some(record) = records.next() {
+        let fields = record.map_err(in_file)?;
+        if !fields.is_empty() {
+            insert(records.record_line(), fields)?;
+        }
+    }
+
+    let mut table = Table::create(table_name);
+    for (name, column_type) in column_names(names, num_columns).iter().zip(&types) {
+        table.create_typed_column(name, *column_type)?;
+    }
+    for data in rows {
+        table.insert_data(data)?;
+    }
+    Ok(table)
+}
+
+/// Builds a table from an array of JSON objects, see `Db::import_json()`. Arrays are
+/// stored with `store_id` and become joins.
+pub(crate) fn import_json(
+    table_name: &str,
+    json: &Json,
+    date_format: &DateFormat,
+    store_id: &mut dyn FnMut(Data) -> Result<i64, Error>,
+) -> Result<Table, Error> {
+    let unsupported = |row: usize, key: &str, message: String| {
+        Error::invalid(format!(
+            "import_json(): row {}, {}: {} are not supported",
+            row + 1,
+            key,
+            message
+        ))
+    };
+    let objects: Vec<&Vec<(String, Json)>> = match json {
+        Json::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(row, item)| match item {
+                Json::Object(members) => Ok(members),
+                other => Err(Error::invalid(format!(
+                    "import_json(): row {} is {} instead of an object",
+                    row + 1,
+                    other.kind()
+                ))),
+            })
+            .collect::<Result<_, _>>()?,
+        Json::Object(members) => vec![members],
+        other => {
+            return Err(Error::invalid(format!(
+                "import_json(): expected an array of objects, not {}",
+                other.kind()
+            )))
+        }
+    };
+
+    // the columns in the order their keys first appear
+    let mut names: Vec<&str> = vec![];
+    for (row, members) in objects.iter().enumerate() {
+        for (key, value) in members.iter() {
+            match value {
+                Json::Object(_) => {
+                    return Err(unsupported(row, key, "nested objects".to_string()));
+                }
+                Json::Array(items) => {
+                    if let Some(item) = items
+                        .iter()
+                        .find(|item| matches!(item, Json::Array(_) | Json::Object(_)))
+                    {
+                        let message = format!("arrays of {}s", item.kind());
+                        return Err(unsupported(row, key, message));
+                    }
+                }
+                _ => {}
+            }
+            if !names.contains(&key.as_str()) {
+                names.push(key);
+            }
+        }
+    }
+    let types: Vec<ColumnType> = names
+        .iter()
+        .map(|name| {
+            let values = objects.iter().filter_map(|members| member(members, name));
+            json_column_type(values, date_format)
+        })
+        .collect();
+
+    let mut table = Table::create(table_name);
+    for (name, column_type) in names.iter().zip(&types) {
+        table.create_typed_column(name, *column_type)?;
+    }
+    for members in objects {
+        let mut data = vec![];
+        for (name, column_type) in names.iter().zip(&types) {
+            data.push(match member(members, name) {
+                Some(Json::Array(items)) => {
+                    let mut ids = vec![];
+                    for item in items {
+                        ids.push(store_id(json_data(item, ColumnType::Any, date_format))?);
+                    }
+                    Data::Join(Join::new(ids))
+                }
+                Some(value) => json_data(value, *column_type, date_format),
+                None => Data::Empty,
+            });
+        }
+        table.insert_data(data)?;
+    }
+    Ok(table)
+}
+
+/// the value of the first member with the key
+fn member<'a>(members: &'a [(String, Json)], key: &str) -> Option<&'a Json> {
+    members
+        .iter()
+        .find(|(name, _)| name == key)
+        .map(|(_, value)| value)
+}
+
+/// The type of a column with the given JSON values: numbers are ints or floats, strings
+/// are dates or times if all of them fit. Mixed columns and columns with arrays are `Any`.
+fn json_column_type<'a>(
+    values: impl Iterator<Item = &'a Json>,
+    date_format: &DateFormat,
+) -> ColumnType {
+    let values: Vec<&Json> = values.filter(|value| **value != Json::Null).collect();
+    let all = |f: fn(&Json) -> bool| values.iter().all(|value| f(value));
+    if values.is_empty() {
+        ColumnType::String
+    } else if all(|value| matches!(value, Json::Bool(_))) {
+        ColumnType::Bool
+    } else if all(|value| matches!(value, Json::Int(_))) {
+        ColumnType::Int
+    } else if all(|value| matches!(value, Json::Int(_) | Json::Float(_))) {
+        ColumnType::Float
+    } else if all(|value| matches!(value, Json::String(_))) {
+        [ColumnType::Date, ColumnType::Time, ColumnType::DateTime]
+            .into_iter()
+            .find(|column_type| {
+                values.iter().all(|value| match value {
+                    Json::String(s) => fits(*column_type, s, date_format),
+                    _ => false,
+                })
+            })
+            .unwrap_or(ColumnType::String)
+    } else {
+        ColumnType::Any
+    }
+}
+
+/// a scalar JSON value for a column of the given type
+fn json_data(value: &Json, column_type: ColumnType, date_format: &DateFormat) -> Data {
+    match (value, column_type) {
+        (Json::Int(n), ColumnType::Float) => Data::Float(*n as f64),
+        (Json::Int(n), _) => Data::Int(*n),
+        (Json::Float(n), _) => Data::Float(*n),
+        (Json::Bool(b), _) => Data::Bool(*b),
+        (Json::String(s), ColumnType::Date | ColumnType::Time | ColumnType::DateTime) => {
+            column_type
+                .parse_value_with(s, date_format)
+                .unwrap_or_else(|_| Data::String(s.to_string()))
+        }
+        (Json::String(s), _) => Data::String(s.to_string()),
+        _ => Data::Empty,
+    }
+}
+
+/// whether a value fits a column type; integers with leading zeros, e.g. zip codes, are
+/// kept as text
+fn fits(column_type: ColumnType, value: &str, date_format: &DateFormat) -> bool {
+    let digits = value.trim_start_matches(['-', '+']);
+    let leading_zero =
+        digits.starts_with('0') && digits[1..].starts_with(|c: char| c.is_ascii_digit());
+    match column_type {
+        ColumnType::Int | ColumnType::Float if leading_zero => false,
+        ColumnType::Float if !value.contains(|c: char| c.is_ascii_digit()) => false,
+        _ => column_type.parse_value_with(value, date_format).is_ok(),
+    }
+}
+
+/// the most specific type that all non-empty values of a column in the sample fit
+fn infer_type<'a>(values: impl Iterator<Item = &'a str>, date_format: &DateFormat) -> ColumnType {
+    let values: Vec<&str> = values.map(str::trim).filter(|v| !v.is_empty()).collect();
+    if values.is_empty() {
+        return ColumnType::String;
+    }
+    [
+        ColumnType::Int,
+        ColumnType::Float,
+        ColumnType::Bool,
+        ColumnType::Date,
+        ColumnType::Time,
+        ColumnType::DateTime,
+    ]
+    .into_iter()
+    .find(|column_type| {
+        values
+            .iter()
+            .all(|value| fits(*column_type, value, date_format))
+    })
+    .unwrap_or(ColumnType::String)
+}
+
+fn infer_types(
+    records: &[(usize, Vec<String>)],
+    num_columns: usize,
+    date_format: &DateFormat,
+) -> Vec<ColumnType> {
+    (0..num_columns)
+        .map(|idx| {
+            let values = records
+                .iter()
+                .filter_map(|(_, fields)| fields.get(idx).map(String::as_str));
+            infer_type(values, date_format)
+        })
+        .collect()
+}
+
+/// The first record is taken as a header if its fields are distinct and not empty, unless
+/// they all fit the types inferred from the records below them.
+fn has_header(records: &[(usize, Vec<String>)], date_format: &DateFormat) -> bool {
+    let Some(((_, first), rest)) = records.split_first() else {
+        return false;
+    };
+    let distinct = first
+        .iter()
+        .enumerate()
+        .all(|(idx, name)| !name.trim().is_empty() && !first[..idx].contains(name));
+    if !distinct {
+        return false;
+    }
+    let types = infer_types(rest, first.len(), date_format);
+    let typed: Vec<(&String, &ColumnType)> = first
+        .iter()
+        .zip(&types)
+        .filter(|(_, column_type)| **column_type != ColumnType::String)
+        .collect();
+    typed.is_empty()
+        || typed
+            .iter()
+            .any(|(name, column_type)| !fits(**column_type, name.trim(), date_format))
+}
+
+/// the header names with empty names replaced by `column<n>` and duplicates numbered
+fn column_names(names: Vec<String>, num_columns: usize) -> Vec<String> {
+    let mut out: Vec<String> = vec![];
+    for idx in 0..num_columns {
+        let name = match names.get(idx).map(|name| name.trim()) {
+            Some(name) if !name.is_empty() => name.to_string(),
+            _ => format!("column{}", idx + 1),
+        };
+        let mut unique = name.clone();
+        let mut n = 2;
+        while out.contains(&unique) {
+            unique = format!("{}_{}", name, n);
+            n += 1;
+        }
+        out.push(unique);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn import(content: &[u8], options: &ImportOptions) -> Result<Table, Error> {
+        static FILES: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!("rzdb-import-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(format!("{}.csv", FILES.fetch_add(1, Ordering::Relaxed)));
+        std::fs::write(&path, content).unwrap();
+        let table = import_csv(
+            path.to_str().unwrap(),
+            "imported",
+            options,
+            &DateFormat::default(),
+        );
+        std::fs::remove_file(&path).unwrap();
+        table
+    }
+
+    #[test]
+    fn test_import_types() {
+        let table = import(
+            b"id;zip;price;ok;day;note\n\
+              1;01234;2.5;yes;2024-01-31;\"a;b\"\n\
+              \n\
+              2;99999; 3;no;;12\n",
+            &ImportOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            table.get_column_names(),
+            vec!["id", "zip", "price", "ok", "day", "note"]
+        );
+        assert_eq!(
+            table.get_column_types(),
+            vec![
+                ColumnType::Int,
+                ColumnType::String,
+                ColumnType::Float,
+                ColumnType::Bool,
+                ColumnType::Date,
+                ColumnType::String,
+            ]
+        );
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.select_at(2, 1).unwrap(), Data::Float(3.0));
+        assert_eq!(table.select_at(4, 1).unwrap(), Data::Empty);
+        assert_eq!(table.select_at(5, 1).unwrap(), Data::String("12".into()));
+    }
+
+    #[test]
+    fn test_import_header() {
+        let table = import(b"1,2\n3,4,5\n", &ImportOptions::default()).unwrap();
+        assert_eq!(
+            table.get_column_names(),
+            vec!["column1", "column2", "column3"]
+        );
+        assert_eq!(table.select_at(2, 0).unwrap(), Data::Empty);
+
+        let table = import(b"a,,a\n", &ImportOptions::default()).unwrap();
+        assert_eq!(
+            table.get_column_names(),
+            vec!["column1", "column2", "column3"]
+        );
+        assert_eq!(table.len(), 1);
+
+        let options = ImportOptions {
+            header: Some(true),
+            ..ImportOptions::default()
+        };
+        let table = import(b"a,,a\n", &options).unwrap();
+        assert_eq!(table.get_column_names(), vec!["a", "column2", "a_2"]);
+        assert_eq!(table.len(), 0);
+
+        let table = import(b"name,value\nx,1\n", &ImportOptions::default()).unwrap();
+        assert_eq!(table.get_column_names(), vec!["name", "value"]);
+    }
+
+    #[test]
+    fn test_import_encodings() {
+        let mut utf16 = vec![0xff, 0xfe];
+        for unit in "name\tnote\r\nZoë\t\u{1f600}\r\n".encode_utf16() {
+            utf16.extend_from_slice(&unit.to_le_bytes());
+        }
+        let table = import(&utf16, &ImportOptions::default()).unwrap();
+        assert_eq!(table.get_column_names(), vec!["name", "note"]);
+        assert_eq!(table.select_at(0, 0).unwrap(), Data::String("Zoë".into()));
+        assert_eq!(
+            table.select_at(1, 0).unwrap(),
+            Data::String("\u{1f600}".into())
+        );
+
+        let table = import(
+            b"name|city\nJos\xe9|M\xfcnchen\n",
+            &ImportOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(table.select_at(0, 0).unwrap(), Data::String("José".into()));
+        assert_eq!(
+            table.select_at(1, 0).unwrap(),
+            Data::String("München".into())
+        );
+
+        let options = ImportOptions {
+            encoding: Encoding::Utf8,
+            ..ImportOptions::default()
+        };
+        assert!(matches!(
+            import(b"a\n\xe9\n", &options),
+            Err(Error::ParseError { line: Some(2), .. })
+        ));
+        let mut decoded = String::new();
+        Decoder::new(&[0x3d, 0xd8][..], Charset::Utf16 { big_endian: false })
+            .read_to_string(&mut decoded)
+            .unwrap_err();
+    }
+
+    #[test]
+    fn test_import_json() {
+        let json = Json::parse(
+            r#"[
+                {"name": "Ann", "age": 30, "tags": ["a", 1], "born": "1994-02-01"},
+                {"age": 30.5, "name": "Bob", "extra": true, "mixed": 1},
+                {"name": null, "tags": [], "mixed": "x"}
+            ]"#,
+        )
+        .unwrap();
+        let mut stored = vec![];
+        let mut store_id = |datum: Data| {
+            stored.push(datum);
+            Ok(stored.len() as i64 - 1)
+        };
+        let table = import_json("people", &json, &DateFormat::default(), &mut store_id).unwrap();
+        assert_eq!(
+            table.get_column_names(),
+            vec!["name", "age", "tags", "born", "extra", "mixed"]
+        );
+        assert_eq!(
+            table.get_column_types(),
+            vec![
+                ColumnType::String,
+                ColumnType::Float,
+                ColumnType::Any,
+                ColumnType::Date,
+                ColumnType::Bool,
+                ColumnType::Any,
+            ]
+        );
+        assert_eq!(table.select_at(1, 0).unwrap(), Data::Float(30.0));
+        assert_eq!(
+            table.select_at(2, 0).unwrap(),
+            Data::Join(Join::new(vec![0, 1]))
+        );
+        assert_eq!(table.select_at(2, 1).unwrap(), Data::Empty);
+        assert_eq!(
+            table.select_at(2, 2).unwrap(),
+            Data::Join(Join::new(vec![]))
+        );
+        assert_eq!(table.select_at(0, 2).unwrap(), Data::Empty);
+        assert_eq!(table.select_at(5, 2).unwrap(), Data::String("x".into()));
+        assert_eq!(stored, vec![Data::String("a".into()), Data::Int(1)]);
+    }
+
+    #[test]
+    fn test_import_json_errors() {
+        let mut store_id = |_: Data| -> Result<i64, Error> { panic!("nothing is stored") };
+        for (text, message) in [
+            (
+                r#"[{"a": [1]}, {"a": {"b": 1}}]"#,
+                "row 2, a: nested objects",
+            ),
+            (r#"[{"a": [1, [2]]}]"#, "row 1, a: arrays of arrays"),
+            (r#"[{"a": 1}, 2]"#, "row 2 is number"),
+            (r#""text""#, "not string"),
+        ] {
+            let json = Json::parse(text).unwrap();
+            match import_json("bad", &json, &DateFormat::default(), &mut store_id) {
+                Err(Error::InvalidValue(e)) => assert!(e.contains(message), "{}", e),
+                other => panic!("{:?}", other.map(|table| table.to_string())),
+            }
+        }
+    }
+
+    #[test]
+    fn test_import_widens_types() {
+        let options = ImportOptions {
+            sample_rows: 2,
+            ..ImportOptions::default()
+        };
+        let table = import(b"n,name\n1,a\n2,b\n3,c\nN/A,d\n5,e\n", &options).unwrap();
+        assert_eq!(
+            table.get_column_types(),
+            vec![ColumnType::Any, ColumnType::String]
+        );
+        assert_eq!(table.len(), 5);
+        assert_eq!(table.select_at(0, 2).unwrap(), Data::Int(3));
+        assert_eq!(table.select_at(0, 3).unwrap(), Data::String("N/A".into()));
+        assert_eq!(table.select_at(0, 4).unwrap(), Data::Int(5));
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let options = ImportOptions {
+            sample_rows: 2,
+            strict: true,
+            ..ImportOptions::default()
+        };
+        match import(b"n\n1\n2\n\n3\nfour\n", &options) {
+            Err(Error::ParseError { line, .. }) => assert_eq!(line, Some(6)),
+            other => panic!("{:?}", other.map(|table| table.to_string())),
+        }
+        let options = ImportOptions {
+            dialect: Some(Dialect::default()),
+            ..options
+        };
+        match import(b"a,b\n1,2\n3,4\n\"5,6\n", &options) {
+            Err(Error::ParseError { line, column, .. }) => {
+                assert_eq!((line, column), (Some(4), Some(1)))
+            }
+            other => panic!("{:?}", other.map(|table| table.to_string())),
+        }
+    }
+}
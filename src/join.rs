@@ -1,19 +1,19 @@
-use std::error::Error;
+use crate::error::Error;
 
 #[derive(Clone, PartialEq)]
 pub struct Join {
     pub ids: Vec<i64>,
 }
 
-fn gen_error(msg: &str) -> Box<dyn Error> {
-    Box::new(std::io::Error::new(std::io::ErrorKind::InvalidData, msg))
+fn gen_error(msg: &str) -> Error {
+    Error::parse(msg)
 }
 
 impl Join {
     pub fn new(ids: Vec<i64>) -> Join {
         Join { ids }
     }
-    pub fn parse(s: &str) -> Result<Join, Box<dyn Error>> {
+    pub fn parse(s: &str) -> Result<Join, Error> {
         let opening_bracket = s.chars().next().unwrap_or('x');
         if opening_bracket != '[' {
             return Err(gen_error("Missing '[' in Join::parse()"));
@@ -24,6 +24,9 @@ impl Join {
         }
         let mut data = vec![];
         let l = s.len();
+        if l == 2 {
+            return Ok(Join { ids: data });
+        }
         for s in s[1..(l - 1)].split(',') {
             let n = s.parse::<i64>()?;
             data.push(n);
@@ -0,0 +1,369 @@
+use crate::error::Error;
+
+/// nesting deeper than this is rejected instead of overflowing the stack
+const MAX_DEPTH: usize = 128;
+
+/// A JSON value. Numbers without fraction and exponent that fit an i64 are `Int`, all
+/// other numbers `Float`. Objects keep the order of their members.
+/// ```
+/// use rzdb::Json;
+/// let json = Json::parse(r#"{"name": "Zo\u00eb", "scores": [1, 2.5e1], "ok": true, "x": null}"#).unwrap();
+/// assert_eq!(
+///     json,
+///     Json::Object(vec![
+///         ("name".to_string(), Json::String("Zoë".to_string())),
+///         ("scores".to_string(), Json::Array(vec![Json::Int(1), Json::Float(25.0)])),
+///         ("ok".to_string(), Json::Bool(true)),
+///         ("x".to_string(), Json::Null),
+///     ])
+/// );
+/// assert_eq!(json.to_string(), r#"{"name":"Zoë","scores":[1,25],"ok":true,"x":null}"#);
+/// ```
+#[derive(Clone, PartialEq, Debug)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// parses JSON text, errors have the line and column of the offending character
+    pub fn parse(s: &str) -> Result<Json, Error> {
+        let mut parser = Parser {
+            chars: s.chars().collect(),
+            pos: 0,
+        };
+        let value = parser.value(0)?;
+        parser.skip_whitespace();
+        if parser.pos < parser.chars.len() {
+            return Err(parser.error("unexpected text after the value"));
+        }
+        Ok(value)
+    }
+
+    /// the name of the kind of value, for messages
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Json::Null => "null",
+            Json::Bool(_) => "bool",
+            Json::Int(_) | Json::Float(_) => "number",
+            Json::String(_) => "string",
+            Json::Array(_) => "array",
+            Json::Object(_) => "object",
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> Error {
+        let before = &self.chars[..self.pos.min(self.chars.len())];
+        let line = before.iter().filter(|c| **c == '\n').count() + 1;
+        let column = before.iter().rev().take_while(|c| **c != '\n').count() + 1;
+        Error::ParseError {
+            line: Some(line),
+            column: Some(column),
+            message: format!("Json::parse(): {}", message),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), Error> {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", c)))
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Json, Error> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("too deeply nested"));
+        }
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => {
+                self.pos += 1;
+                let mut members = vec![];
+                self.skip_whitespace();
+                if self.peek() == Some('}') {
+                    self.pos += 1;
+                    return Ok(Json::Object(members));
+                }
+                loop {
+                    self.skip_whitespace();
+                    if self.peek() != Some('"') {
+                        return Err(self.error("expected a string as key"));
+                    }
+                    let key = self.string()?;
+                    self.expect(':')?;
+                    members.push((key, self.value(depth + 1)?));
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        Some('}') => {
+                            self.pos += 1;
+                            return Ok(Json::Object(members));
+                        }
+                        _ => return Err(self.error("expected ',' or '}'")),
+                    }
+                }
+            }
+            Some('[') => {
+                self.pos += 1;
+                let mut items = vec![];
+                self.skip_whitespace();
+                if self.peek() == Some(']') {
+                    self.pos += 1;
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value(depth + 1)?);
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        Some(']') => {
+                            self.pos += 1;
+                            return Ok(Json::Array(items));
+                        }
+                        _ => return Err(self.error("expected ',' or ']'")),
+                    }
+                }
+            }
+            Some('"') => Ok(Json::String(self.string()?)),
+            Some('-' | '0'..='9') => self.number(),
+            Some(_) => {
+                for (word, value) in [
+                    ("true", Json::Bool(true)),
+                    ("false", Json::Bool(false)),
+                    ("null", Json::Null),
+                ] {
+                    let end = self.pos + word.len();
+                    if self.chars.len() >= end
+                        && self.chars[self.pos..end].iter().copied().eq(word.chars())
+                    {
+                        self.pos = end;
+                        return Ok(value);
+                    }
+                }
+                Err(self.error("expected a value"))
+            }
+            None => Err(self.error("unexpected end, expected a value")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        // skip the opening quote
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            let Some(c) = self.peek() else {
+                return Err(self.error("unterminated string"));
+            };
+            self.pos += 1;
+            match c {
+                '"' => return Ok(out),
+                '\\' => {
+                    let escaped = self.peek();
+                    self.pos += 1;
+                    match escaped {
+                        Some('"') => out.push('"'),
+                        Some('\\') => out.push('\\'),
+                        Some('/') => out.push('/'),
+                        Some('b') => out.push('\u{8}'),
+                        Some('f') => out.push('\u{c}'),
+                        Some('n') => out.push('\n'),
+                        Some('r') => out.push('\r'),
+                        Some('t') => out.push('\t'),
+                        Some('u') => {
+                            let mut unit = self.hex4()?;
+                            // a surrogate pair written as two escapes
+                            if (0xd800..0xdc00).contains(&unit)
+                                && self.chars.get(self.pos..self.pos + 2) == Some(&['\\', 'u'])
+                            {
+                                self.pos += 2;
+                                let low = self.hex4()?;
+                                if !(0xdc00..0xe000).contains(&low) {
+                                    self.pos -= 6;
+                                    return Err(self.error("invalid surrogate pair"));
+                                }
+                                unit = 0x10000 + ((unit - 0xd800) << 10) + (low - 0xdc00);
+                            }
+                            let c = char::from_u32(unit).ok_or_else(|| {
+                                self.pos -= 6;
+                                self.error("invalid unicode escape")
+                            })?;
+                            out.push(c);
+                        }
+                        _ => {
+                            self.pos -= 1;
+                            return Err(self.error("invalid escape"));
+                        }
+                    }
+                }
+                c if (c as u32) < 0x20 => {
+                    self.pos -= 1;
+                    return Err(self.error("control character in string"));
+                }
+                c => out.push(c),
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, Error> {
+        let digits: String = self.chars.iter().skip(self.pos).take(4).collect();
+        if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(self.error("expected 4 hex digits"));
+        }
+        self.pos += 4;
+        Ok(u32::from_str_radix(&digits, 16).unwrap_or_default())
+    }
+
+    fn number(&mut self) -> Result<Json, Error> {
+        let start = self.pos;
+        let digits = |parser: &mut Parser| {
+            let from = parser.pos;
+            while matches!(parser.peek(), Some('0'..='9')) {
+                parser.pos += 1;
+            }
+            parser.pos - from
+        };
+        if self.peek() == Some('-') {
+            self.pos += 1;
+        }
+        let int_start = self.pos;
+        let int_digits = digits(self);
+        if int_digits == 0 || (int_digits > 1 && self.chars[int_start] == '0') {
+            return Err(self.error("invalid number"));
+        }
+        let mut is_float = false;
+        if self.peek() == Some('.') {
+            self.pos += 1;
+            is_float = true;
+            if digits(self) == 0 {
+                return Err(self.error("expected digits after '.'"));
+            }
+        }
+        if matches!(self.peek(), Some('e' | 'E')) {
+            self.pos += 1;
+            is_float = true;
+            if matches!(self.peek(), Some('+' | '-')) {
+                self.pos += 1;
+            }
+            if digits(self) == 0 {
+                return Err(self.error("expected digits in the exponent"));
+            }
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        match text.parse::<i64>() {
+            Ok(n) if !is_float => Ok(Json::Int(n)),
+            _ => text.parse::<f64>().map(Json::Float).map_err(|_| {
+                self.pos = start;
+                self.error("invalid number")
+            }),
+        }
+    }
+}
+
+/// a string as JSON, with quotes
+fn json_string(s: &str) -> String {
+    let mut out = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+impl std::fmt::Display for Json {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Int(n) => write!(f, "{}", n),
+            Json::Float(n) if n.is_finite() => write!(f, "{}", n),
+            Json::Float(_) => write!(f, "null"),
+            Json::String(s) => write!(f, "{}", json_string(s)),
+            Json::Array(items) => {
+                let items: Vec<String> = items.iter().map(|item| item.to_string()).collect();
+                write!(f, "[{}]", items.join(","))
+            }
+            Json::Object(members) => {
+                let members: Vec<String> = members
+                    .iter()
+                    .map(|(key, value)| format!("{}:{}", json_string(key), value))
+                    .collect();
+                write!(f, "{{{}}}", members.join(","))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Json::parse(" [ ] ").unwrap(), Json::Array(vec![]));
+        assert_eq!(Json::parse("{}").unwrap(), Json::Object(vec![]));
+        assert_eq!(Json::parse("-0").unwrap(), Json::Int(0));
+        assert_eq!(Json::parse("-1.5E+2").unwrap(), Json::Float(-150.0));
+        assert_eq!(
+            Json::parse("9223372036854775808").unwrap(),
+            Json::Float(9223372036854775808.0)
+        );
+        assert_eq!(
+            Json::parse(r#""\"\\\/\b\f\n\r\t\ud83d\ude00""#).unwrap(),
+            Json::String("\"\\/\u{8}\u{c}\n\r\t\u{1f600}".to_string())
+        );
+        let text = r#"[{"a":[1,{"b":null}],"c":"x\ny"},false,-2.5]"#;
+        assert_eq!(Json::parse(text).unwrap().to_string(), text);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let position = |s: &str| match Json::parse(s) {
+            Err(Error::ParseError { line, column, .. }) => (line.unwrap(), column.unwrap()),
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(position(""), (1, 1));
+        assert_eq!(position("[1,\n 2,]"), (2, 4));
+        assert_eq!(position("{\"a\" 1}"), (1, 6));
+        assert_eq!(position("[01]"), (1, 4));
+        assert_eq!(position("[1.]"), (1, 4));
+        assert_eq!(position("\"open"), (1, 6));
+        assert_eq!(position("\"\\x\""), (1, 3));
+        assert_eq!(position("\"\\ud800\\u0041\""), (1, 8));
+        assert_eq!(position("[true] x"), (1, 8));
+        assert_eq!(position("nul"), (1, 1));
+        assert_eq!(position(&"[".repeat(200)), (1, 130));
+    }
+}
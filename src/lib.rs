@@ -1,12 +1,43 @@
+mod aggregate;
+mod blob;
+mod column_type;
 mod condition;
+mod csv;
 mod data;
 mod db;
+mod decimal;
+mod error;
+mod export;
+mod expr;
+mod import;
 mod join;
+mod json;
+#[cfg(feature = "serde")]
+mod record;
 mod row;
+mod storage;
 mod table;
 pub mod time;
+#[cfg(feature = "derive")]
+mod typed;
 
+pub use crate::aggregate::Aggregate;
+pub use crate::blob::Blob;
+pub use crate::column_type::ColumnType;
 pub use crate::condition::{Condition, ConditionType};
+pub use crate::csv::{CsvReader, Dialect, EscapeStyle};
 pub use crate::data::Data;
 pub use crate::db::Db;
+pub use crate::decimal::Decimal;
+pub use crate::error::Error;
+pub use crate::export::ExportFormat;
+pub use crate::expr::{BinaryOp, Expr};
+pub use crate::import::{Encoding, ImportOptions};
+pub use crate::json::Json;
 pub use crate::row::Row;
+pub use crate::storage::{Storage, StorageBackend};
+pub use crate::table::Table;
+#[cfg(feature = "derive")]
+pub use crate::typed::{FieldType, RzdbTable};
+#[cfg(feature = "derive")]
+pub use rzdb_derive::RzdbTable;
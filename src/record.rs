@@ -0,0 +1,751 @@
+//! Maps structs to rows and back with serde, see `Db::insert_record()` and
+//! `Db::select_records()`.
+
+use crate::data::Data;
+use crate::error::Error;
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use serde::ser::{self, Impossible, Serialize};
+
+impl ser::Error for Error {
+    fn custom<T: std::fmt::Display>(msg: T) -> Error {
+        Error::invalid(msg.to_string())
+    }
+}
+
+/// The column and table of missing fields and type errors are filled in by the callers.
+impl de::Error for Error {
+    fn custom<T: std::fmt::Display>(msg: T) -> Error {
+        Error::invalid(msg.to_string())
+    }
+
+    fn invalid_type(unexpected: de::Unexpected, expected: &dyn de::Expected) -> Error {
+        Error::TypeMismatch {
+            table: String::new(),
+            column: String::new(),
+            expected: expected.to_string(),
+            value: unexpected.to_string(),
+        }
+    }
+
+    fn missing_field(field: &'static str) -> Error {
+        Error::ColumnNotFound {
+            table: String::new(),
+            column: field.to_string(),
+        }
+    }
+}
+
+/// the value of a field: a single value, or the items of a sequence stored as a join
+pub(crate) enum Field {
+    Value(Data),
+    Items(Vec<Data>),
+}
+
+/// the fields of a struct or map by name
+pub(crate) fn to_fields<T: Serialize>(record: &T) -> Result<Vec<(String, Field)>, Error> {
+    record.serialize(RecordSerializer)
+}
+
+/// a row given as column names and values; joins are expected as lists of their values
+pub(crate) fn from_row<T: DeserializeOwned>(
+    table_name: &str,
+    columns: Vec<(String, Data)>,
+) -> Result<T, Error> {
+    T::deserialize(RowDeserializer { columns }).map_err(|e| match e {
+        Error::ColumnNotFound { column, .. } => Error::ColumnNotFound {
+            table: table_name.to_string(),
+            column,
+        },
+        Error::TypeMismatch {
+            column,
+            expected,
+            value,
+            ..
+        } => Error::TypeMismatch {
+            table: table_name.to_string(),
+            column,
+            expected,
+            value,
+        },
+        e => e,
+    })
+}
+
+fn unsupported(what: &str) -> Error {
+    Error::invalid(format!("records: {} are not supported", what))
+}
+
+struct RecordSerializer;
+
+impl ser::Serializer for RecordSerializer {
+    type Ok = Vec<(String, Field)>;
+    type Error = Error;
+    type SerializeSeq = Impossible<Self::Ok, Error>;
+    type SerializeTuple = Impossible<Self::Ok, Error>;
+    type SerializeTupleStruct = Impossible<Self::Ok, Error>;
+    type SerializeTupleVariant = Impossible<Self::Ok, Error>;
+    type SerializeMap = FieldsSerializer;
+    type SerializeStruct = FieldsSerializer;
+    type SerializeStructVariant = Impossible<Self::Ok, Error>;
+
+    fn serialize_struct(self, _: &'static str, len: usize) -> Result<FieldsSerializer, Error> {
+        Ok(FieldsSerializer {
+            fields: Vec::with_capacity(len),
+            key: None,
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<FieldsSerializer, Error> {
+        Ok(FieldsSerializer {
+            fields: Vec::with_capacity(len.unwrap_or_default()),
+            key: None,
+        })
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_bool(self, _: bool) -> Result<Self::Ok, Error> {
+        Err(not_a_record())
+    }
+    fn serialize_i8(self, _: i8) -> Result<Self::Ok, Error> {
+        Err(not_a_record())
+    }
+    fn serialize_i16(self, _: i16) -> Result<Self::Ok, Error> {
+        Err(not_a_record())
+    }
+    fn serialize_i32(self, _: i32) -> Result<Self::Ok, Error> {
+        Err(not_a_record())
+    }
+    fn serialize_i64(self, _: i64) -> Result<Self::Ok, Error> {
+        Err(not_a_record())
+    }
+    fn serialize_u8(self, _: u8) -> Result<Self::Ok, Error> {
+        Err(not_a_record())
+    }
+    fn serialize_u16(self, _: u16) -> Result<Self::Ok, Error> {
+        Err(not_a_record())
+    }
+    fn serialize_u32(self, _: u32) -> Result<Self::Ok, Error> {
+        Err(not_a_record())
+    }
+    fn serialize_u64(self, _: u64) -> Result<Self::Ok, Error> {
+        Err(not_a_record())
+    }
+    fn serialize_f32(self, _: f32) -> Result<Self::Ok, Error> {
+        Err(not_a_record())
+    }
+    fn serialize_f64(self, _: f64) -> Result<Self::Ok, Error> {
+        Err(not_a_record())
+    }
+    fn serialize_char(self, _: char) -> Result<Self::Ok, Error> {
+        Err(not_a_record())
+    }
+    fn serialize_str(self, _: &str) -> Result<Self::Ok, Error> {
+        Err(not_a_record())
+    }
+    fn serialize_bytes(self, _: &[u8]) -> Result<Self::Ok, Error> {
+        Err(not_a_record())
+    }
+    fn serialize_none(self) -> Result<Self::Ok, Error> {
+        Err(not_a_record())
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, _: &T) -> Result<Self::Ok, Error> {
+        Err(not_a_record())
+    }
+    fn serialize_unit(self) -> Result<Self::Ok, Error> {
+        Err(not_a_record())
+    }
+    fn serialize_unit_struct(self, _: &'static str) -> Result<Self::Ok, Error> {
+        Err(not_a_record())
+    }
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+    ) -> Result<Self::Ok, Error> {
+        Err(not_a_record())
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<Self::Ok, Error> {
+        Err(not_a_record())
+    }
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(not_a_record())
+    }
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(not_a_record())
+    }
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(not_a_record())
+    }
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(not_a_record())
+    }
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(not_a_record())
+    }
+}
+
+fn not_a_record() -> Error {
+    Error::invalid("records: only structs and maps can be stored as rows")
+}
+
+struct FieldsSerializer {
+    fields: Vec<(String, Field)>,
+    /// the key of a map entry whose value comes next
+    key: Option<String>,
+}
+
+impl ser::SerializeStruct for FieldsSerializer {
+    type Ok = Vec<(String, Field)>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        let field = value
+            .serialize(ValueSerializer { in_seq: false })
+            .map_err(|e| in_field(key, e))?;
+        self.fields.push((key.to_string(), field));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(self.fields)
+    }
+}
+
+impl ser::SerializeMap for FieldsSerializer {
+    type Ok = Vec<(String, Field)>;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        match key.serialize(ValueSerializer { in_seq: true })? {
+            Field::Value(Data::String(key)) => self.key = Some(key),
+            _ => return Err(unsupported("map keys other than strings")),
+        }
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self.key.take().unwrap_or_default();
+        let field = value
+            .serialize(ValueSerializer { in_seq: false })
+            .map_err(|e| in_field(&key, e))?;
+        self.fields.push((key, field));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(self.fields)
+    }
+}
+
+fn in_field(key: &str, e: Error) -> Error {
+    match e {
+        Error::InvalidValue(message) => Error::InvalidValue(format!("field {}: {}", key, message)),
+        e => e,
+    }
+}
+
+/// serializes a field, or an item of a sequence field
+struct ValueSerializer {
+    in_seq: bool,
+}
+
+impl ValueSerializer {
+    fn value(datum: Data) -> Result<Field, Error> {
+        Ok(Field::Value(datum))
+    }
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Field;
+    type Error = Error;
+    type SerializeSeq = ItemsSerializer;
+    type SerializeTuple = ItemsSerializer;
+    type SerializeTupleStruct = ItemsSerializer;
+    type SerializeTupleVariant = Impossible<Field, Error>;
+    type SerializeMap = Impossible<Field, Error>;
+    type SerializeStruct = Impossible<Field, Error>;
+    type SerializeStructVariant = Impossible<Field, Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<Field, Error> {
+        Self::value(Data::Bool(v))
+    }
+    fn serialize_i8(self, v: i8) -> Result<Field, Error> {
+        Self::value(Data::Int(v.into()))
+    }
+    fn serialize_i16(self, v: i16) -> Result<Field, Error> {
+        Self::value(Data::Int(v.into()))
+    }
+    fn serialize_i32(self, v: i32) -> Result<Field, Error> {
+        Self::value(Data::Int(v.into()))
+    }
+    fn serialize_i64(self, v: i64) -> Result<Field, Error> {
+        Self::value(Data::Int(v))
+    }
+    fn serialize_u8(self, v: u8) -> Result<Field, Error> {
+        Self::value(Data::Int(v.into()))
+    }
+    fn serialize_u16(self, v: u16) -> Result<Field, Error> {
+        Self::value(Data::Int(v.into()))
+    }
+    fn serialize_u32(self, v: u32) -> Result<Field, Error> {
+        Self::value(Data::Int(v.into()))
+    }
+    fn serialize_u64(self, v: u64) -> Result<Field, Error> {
+        let n = i64::try_from(v).map_err(|_| Error::invalid(format!("{} is too large", v)))?;
+        Self::value(Data::Int(n))
+    }
+    fn serialize_f32(self, v: f32) -> Result<Field, Error> {
+        Self::value(Data::Float(v.into()))
+    }
+    fn serialize_f64(self, v: f64) -> Result<Field, Error> {
+        Self::value(Data::Float(v))
+    }
+    fn serialize_char(self, v: char) -> Result<Field, Error> {
+        Self::value(Data::String(v.to_string()))
+    }
+    fn serialize_str(self, v: &str) -> Result<Field, Error> {
+        Self::value(Data::String(v.to_string()))
+    }
+    fn serialize_bytes(self, _: &[u8]) -> Result<Field, Error> {
+        Err(unsupported("bytes, use Db::store_blob(),"))
+    }
+    fn serialize_none(self) -> Result<Field, Error> {
+        Self::value(Data::Empty)
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Field, Error> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<Field, Error> {
+        Self::value(Data::Empty)
+    }
+    fn serialize_unit_struct(self, _: &'static str) -> Result<Field, Error> {
+        Self::value(Data::Empty)
+    }
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+    ) -> Result<Field, Error> {
+        Self::value(Data::String(variant.to_string()))
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<Field, Error> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<Field, Error> {
+        Err(unsupported("enum variants with values"))
+    }
+    fn serialize_seq(self, len: Option<usize>) -> Result<ItemsSerializer, Error> {
+        if self.in_seq {
+            return Err(unsupported("nested sequences"));
+        }
+        Ok(ItemsSerializer {
+            items: Vec::with_capacity(len.unwrap_or_default()),
+        })
+    }
+    fn serialize_tuple(self, len: usize) -> Result<ItemsSerializer, Error> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_struct(self, _: &'static str, len: usize) -> Result<ItemsSerializer, Error> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(unsupported("enum variants with values"))
+    }
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(unsupported("nested maps"))
+    }
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeStruct, Error> {
+        Err(unsupported("nested structs"))
+    }
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(unsupported("enum variants with values"))
+    }
+}
+
+struct ItemsSerializer {
+    items: Vec<Data>,
+}
+
+impl ItemsSerializer {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        match value.serialize(ValueSerializer { in_seq: true })? {
+            Field::Value(datum) => self.items.push(datum),
+            Field::Items(_) => return Err(unsupported("nested sequences")),
+        }
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for ItemsSerializer {
+    type Ok = Field;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Field, Error> {
+        Ok(Field::Items(self.items))
+    }
+}
+
+impl ser::SerializeTuple for ItemsSerializer {
+    type Ok = Field;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Field, Error> {
+        Ok(Field::Items(self.items))
+    }
+}
+
+impl ser::SerializeTupleStruct for ItemsSerializer {
+    type Ok = Field;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Field, Error> {
+        Ok(Field::Items(self.items))
+    }
+}
+
+struct RowDeserializer {
+    columns: Vec<(String, Data)>,
+}
+
+impl<'de> de::Deserializer<'de> for RowDeserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_map(ColumnsAccess {
+            columns: self.columns.into_iter(),
+            value: None,
+        })
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct ColumnsAccess {
+    columns: std::vec::IntoIter<(String, Data)>,
+    /// the column whose value comes next
+    value: Option<(String, Data)>,
+}
+
+impl<'de> de::MapAccess<'de> for ColumnsAccess {
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        match self.columns.next() {
+            Some((name, datum)) => {
+                let key = seed.deserialize(de::value::StrDeserializer::<Error>::new(&name))?;
+                self.value = Some((name, datum));
+                Ok(Some(key))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let (name, datum) = self.value.take().unwrap_or((String::new(), Data::Empty));
+        seed.deserialize(DataDeserializer(datum))
+            .map_err(|e| match e {
+                Error::TypeMismatch {
+                    table,
+                    expected,
+                    value,
+                    ..
+                } => Error::TypeMismatch {
+                    table,
+                    column: name,
+                    expected,
+                    value,
+                },
+                Error::InvalidValue(message) => {
+                    Error::InvalidValue(format!("column {}: {}", name, message))
+                }
+                e => e,
+            })
+    }
+}
+
+struct DataDeserializer(Data);
+
+impl<'de> de::Deserializer<'de> for DataDeserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Data::Empty => visitor.visit_unit(),
+            Data::String(s) => visitor.visit_string(s),
+            Data::Int(n) => visitor.visit_i64(n),
+            Data::Float(n) => visitor.visit_f64(n),
+            Data::Bool(b) => visitor.visit_bool(b),
+            Data::List(items) => visitor.visit_seq(de::value::SeqDeserializer::new(
+                items.into_iter().map(DataDeserializer),
+            )),
+            datum => visitor.visit_string(datum.to_string()),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Data::Empty => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match &self.0 {
+            Data::Decimal(n) => match n.to_string().parse::<f64>() {
+                Ok(n) => visitor.visit_f64(n),
+                Err(_) => self.deserialize_any(visitor),
+            },
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_f64(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.0 {
+            Data::String(s) => visitor.visit_enum(s.into_deserializer()),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 char str string
+        bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for DataDeserializer {
+    type Deserializer = DataDeserializer;
+
+    fn into_deserializer(self) -> DataDeserializer {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    enum Status {
+        Open,
+        Done,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Task {
+        id: u32,
+        title: String,
+        score: Option<f64>,
+        status: Status,
+        tags: Vec<String>,
+    }
+
+    fn task() -> Task {
+        Task {
+            id: 7,
+            title: "write docs".to_string(),
+            score: None,
+            status: Status::Done,
+            tags: vec!["a".to_string(), "b".to_string()],
+        }
+    }
+
+    fn s(s: &str) -> Data {
+        Data::String(s.to_string())
+    }
+
+    #[test]
+    fn test_to_fields() {
+        let fields: Vec<(String, String)> = to_fields(&task())
+            .unwrap()
+            .into_iter()
+            .map(|(name, field)| match field {
+                Field::Value(datum) => (name, format!("{:?}", datum)),
+                Field::Items(items) => (name, format!("items {:?}", items)),
+            })
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("id".to_string(), format!("{:?}", Data::Int(7))),
+                ("title".to_string(), format!("{:?}", s("write docs"))),
+                ("score".to_string(), format!("{:?}", Data::Empty)),
+                ("status".to_string(), format!("{:?}", s("Done"))),
+                (
+                    "tags".to_string(),
+                    format!("items {:?}", vec![s("a"), s("b")])
+                ),
+            ]
+        );
+
+        let map = BTreeMap::from([("x", 1.5)]);
+        assert_eq!(to_fields(&map).unwrap().len(), 1);
+
+        #[derive(Serialize)]
+        struct Nested {
+            inner: BTreeMap<String, i64>,
+        }
+        assert!(matches!(
+            to_fields(&Nested { inner: BTreeMap::new() }),
+            Err(Error::InvalidValue(message)) if message.starts_with("field inner:")
+        ));
+        #[derive(Serialize)]
+        struct Matrix {
+            cells: Vec<Vec<i64>>,
+        }
+        assert!(to_fields(&Matrix {
+            cells: vec![vec![1]]
+        })
+        .is_err());
+        #[derive(Serialize)]
+        struct Large {
+            n: u64,
+        }
+        assert!(to_fields(&Large { n: u64::MAX }).is_err());
+        assert!(to_fields(&5).is_err());
+        assert!(to_fields(&vec![1, 2]).is_err());
+    }
+
+    #[test]
+    fn test_from_row() {
+        let row = vec![
+            ("id".to_string(), Data::Int(7)),
+            ("title".to_string(), s("write docs")),
+            ("score".to_string(), Data::Empty),
+            ("status".to_string(), s("Done")),
+            ("tags".to_string(), Data::List(vec![s("a"), s("b")])),
+            ("unused".to_string(), Data::Bool(true)),
+        ];
+        assert_eq!(from_row::<Task>("tasks", row.clone()).unwrap(), task());
+
+        let mut missing = row.clone();
+        missing.remove(1);
+        match from_row::<Task>("tasks", missing) {
+            Err(Error::ColumnNotFound { table, column }) => {
+                assert_eq!((table.as_str(), column.as_str()), ("tasks", "title"))
+            }
+            other => panic!("{:?}", other),
+        }
+
+        let mut mistyped = row.clone();
+        mistyped[0].1 = s("seven");
+        match from_row::<Task>("tasks", mistyped) {
+            Err(Error::TypeMismatch { table, column, .. }) => {
+                assert_eq!((table.as_str(), column.as_str()), ("tasks", "id"))
+            }
+            other => panic!("{:?}", other),
+        }
+
+        let mut unknown_variant = row;
+        unknown_variant[3].1 = s("Lost");
+        assert!(matches!(
+            from_row::<Task>("tasks", unknown_variant),
+            Err(Error::InvalidValue(message)) if message.starts_with("column status:")
+        ));
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Amount {
+            value: f64,
+        }
+        let amount = vec![("value".to_string(), Data::parse("1.25"))];
+        assert_eq!(
+            from_row::<Amount>("amounts", amount).unwrap(),
+            Amount { value: 1.25 }
+        );
+    }
+}
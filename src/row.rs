@@ -1,6 +1,7 @@
 use crate::data::Data;
-use std::error::Error;
+use crate::error::{check_index, Error};
 
+#[derive(PartialEq, Debug)]
 pub struct Row {
     data: Vec<Data>,
 }
@@ -46,44 +47,43 @@ impl Row {
         result
     }
 
-    pub fn select_at_multiple(&self, indices: &[usize]) -> Row {
+    pub fn select_at_multiple(&self, indices: &[usize]) -> Result<Row, Error> {
         let mut result = vec![];
         for index in indices {
-            result.push(self.data[*index].clone());
+            result.push(self.select_at(*index)?);
         }
-        Row::from(result)
+        Ok(Row::from(result))
     }
 
-    pub fn select_at(&self, idx: usize) -> Result<Data, Box<dyn Error>> {
-        if idx >= self.data.len() {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!(
-                    "Row::select_at({}): index out of bounds ({} columns)",
-                    idx,
-                    self.data.len(),
-                ),
-            )));
-        }
+    pub fn select_at(&self, idx: usize) -> Result<Data, Error> {
+        check_index("Row::select_at", idx, self.data.len())?;
         Ok(self.data[idx].clone())
     }
 
-    pub fn set_at(&mut self, idx: usize, value: Data) -> Result<(), Box<dyn Error>> {
+    pub fn set_at(&mut self, idx: usize, value: Data) -> Result<(), Error> {
+        check_index("Row::set_at", idx, self.data.len())?;
         self.data[idx] = value;
         Ok(())
     }
 
-    pub fn insert_at(&mut self, idx: usize, value: Data) {
+    /// inserts before idx, which may be the length of the row to append
+    pub fn insert_at(&mut self, idx: usize, value: Data) -> Result<(), Error> {
+        check_index("Row::insert_at", idx, self.data.len() + 1)?;
         self.data.insert(idx, value);
+        Ok(())
     }
-    pub fn insert_columns_at(&mut self, index: usize, other: &Row) {
+    pub fn insert_columns_at(&mut self, index: usize, other: &Row) -> Result<(), Error> {
+        check_index("Row::insert_columns_at", index, self.data.len() + 1)?;
         self.data.splice(index..index, other.data.iter().cloned());
+        Ok(())
     }
-    pub fn delete(&mut self, idx: usize) {
+    pub fn delete(&mut self, idx: usize) -> Result<(), Error> {
+        check_index("Row::delete", idx, self.data.len())?;
         self.data.remove(idx);
+        Ok(())
     }
 
-    pub fn iter(&self) -> std::slice::Iter<Data> {
+    pub fn iter(&self) -> std::slice::Iter<'_, Data> {
         self.data.iter()
     }
 }
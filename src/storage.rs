@@ -0,0 +1,609 @@
+use crate::blob::Blob;
+use crate::data::Data;
+use crate::decimal::Decimal;
+use crate::error::Error;
+use crate::join::Join;
+use crate::row::Row;
+use crate::table::Table;
+use crate::time::{Date, DateTime, Duration, Time};
+
+/// The file format of the tables of a db, see `Db::set_storage()`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Storage {
+    /// a `.csv` file per table, readable and editable as text
+    Csv,
+    /// a `.rzt` file per table with values in a compact binary encoding, checked with a
+    /// CRC-32 on load; faster to load and save than csv
+    Binary,
+}
+
+impl Storage {
+    /// the extension of table files, without the dot
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Storage::Csv => "csv",
+            Storage::Binary => "rzt",
+        }
+    }
+
+    pub fn backend(&self) -> Box<dyn StorageBackend> {
+        match self {
+            Storage::Csv => Box::new(CsvStorage),
+            Storage::Binary => Box::new(BinaryStorage),
+        }
+    }
+
+    pub(crate) fn all() -> [Storage; 2] {
+        [Storage::Csv, Storage::Binary]
+    }
+}
+
+/// Reads and writes table files, one per table, named after the table with the extension
+/// of the backend. Set with `Db::set_backend()` or `Db::load_with_backend()`; the tables of
+/// other backends are written with `Table::column_specs()` and `Table::rows()` and read
+/// with `Table::from_parts()`.
+/// ```
+/// use rzdb::{Data, Db, Error, StorageBackend, Table};
+///
+/// /// the csv format with a `.txt` extension
+/// struct TextStorage;
+///
+/// impl StorageBackend for TextStorage {
+///     fn extension(&self) -> &str {
+///         "txt"
+///     }
+///     fn load(&self, full_name: &str) -> Result<Table, Error> {
+///         Table::load(full_name)
+///     }
+///     fn save(&self, table: &Table, filename: &str) -> Result<(), Error> {
+///         table.write(filename)
+///     }
+/// }
+///
+/// let mut db = Db::create("test_storage_backend", "~/.local/rzdb").unwrap();
+/// db.set_backend(Box::new(TextStorage));
+/// db.create_or_replace_table("notes").unwrap();
+/// db.create_column("notes", "text").unwrap();
+/// db.insert_data("notes", vec![Data::parse("hello")]).unwrap();
+/// db.save().unwrap();
+///
+/// let db = Db::load_with_backend("test_storage_backend", "~/.local/rzdb", Box::new(TextStorage)).unwrap();
+/// assert_eq!(db.get_storage(), None);
+/// assert_eq!(db.select_at("notes", 0, 0).unwrap(), Data::parse("hello"));
+/// ```
+pub trait StorageBackend {
+    /// the extension of table files, without the dot
+    fn extension(&self) -> &str;
+
+    /// loads a table, named after the file without its extension
+    fn load(&self, full_name: &str) -> Result<Table, Error>;
+
+    fn save(&self, table: &Table, filename: &str) -> Result<(), Error>;
+}
+
+struct CsvStorage;
+
+impl StorageBackend for CsvStorage {
+    fn extension(&self) -> &str {
+        Storage::Csv.extension()
+    }
+
+    fn load(&self, full_name: &str) -> Result<Table, Error> {
+        Table::load(full_name)
+    }
+
+    fn save(&self, table: &Table, filename: &str) -> Result<(), Error> {
+        table.write(filename)
+    }
+}
+
+const BINARY_MAGIC: &[u8; 4] = b"RZDB";
+const BINARY_VERSION: u64 = 1;
+
+/// Binary table files: the magic `RZDB`, the format version, the column names and types,
+/// the row count, the rows, and the CRC-32 of everything before it. Numbers are LEB128
+/// varints, signed ones zigzag encoded, floats 8 bytes little endian and strings their
+/// byte length followed by UTF-8. Every value starts with a tag byte.
+struct BinaryStorage;
+
+const TAG_EMPTY: u8 = 0;
+const TAG_STRING: u8 = 1;
+const TAG_INT: u8 = 2;
+const TAG_FLOAT: u8 = 3;
+const TAG_DECIMAL: u8 = 4;
+const TAG_FALSE: u8 = 5;
+const TAG_TRUE: u8 = 6;
+const TAG_DATE: u8 = 7;
+const TAG_TIME: u8 = 8;
+const TAG_DATE_TIME: u8 = 9;
+const TAG_DURATION: u8 = 10;
+const TAG_BLOB: u8 = 11;
+const TAG_JOIN: u8 = 12;
+const TAG_LIST: u8 = 13;
+
+impl StorageBackend for BinaryStorage {
+    fn extension(&self) -> &str {
+        Storage::Binary.extension()
+    }
+
+    fn load(&self, full_name: &str) -> Result<Table, Error> {
+        let bytes = std::fs::read(full_name)?;
+        let load_error = |message: String| Error::ParseError {
+            line: None,
+            column: None,
+            message: format!("Table::load({}): {}", full_name, message),
+        };
+        if bytes.len() < BINARY_MAGIC.len() + 4 || &bytes[..BINARY_MAGIC.len()] != BINARY_MAGIC {
+            return Err(load_error("not a binary table file".to_string()));
+        }
+        let (content, checksum) = bytes.split_at(bytes.len() - 4);
+        let mut reader = Reader {
+            bytes: content,
+            pos: BINARY_MAGIC.len(),
+        };
+        let version = reader.varint().map_err(load_error)?;
+        if version > BINARY_VERSION {
+            return Err(load_error(format!(
+                "unsupported file format version {} (supported up to {})",
+                version, BINARY_VERSION
+            )));
+        }
+        let checksum = u32::from_le_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]);
+        if crc32(content) != checksum {
+            return Err(load_error(
+                "checksum mismatch, the file is damaged".to_string(),
+            ));
+        }
+
+        let name = table_name(full_name, self.extension());
+        let table = read_table(&mut reader, &name).map_err(load_error)?;
+        if reader.pos != content.len() {
+            return Err(load_error(format!(
+                "unexpected bytes after the rows at byte {}",
+                reader.pos
+            )));
+        }
+        Ok(table)
+    }
+
+    fn save(&self, table: &Table, filename: &str) -> Result<(), Error> {
+        let mut out = BINARY_MAGIC.to_vec();
+        write_varint(&mut out, BINARY_VERSION);
+        let column_names = table.get_column_names();
+        write_varint(&mut out, column_names.len() as u64);
+        for name in &column_names {
+            write_str(&mut out, name);
+        }
+        for spec in table.column_specs() {
+            write_str(&mut out, &spec);
+        }
+        write_varint(&mut out, table.rows().len() as u64);
+        for row in table.rows() {
+            for datum in row.iter() {
+                write_data(&mut out, datum);
+            }
+        }
+        let checksum = crc32(&out);
+        out.extend_from_slice(&checksum.to_le_bytes());
+
+        // write to a temporary file first, so that a failed save keeps the old file
+        let tmp_filename = format!("{}.tmp", filename);
+        std::fs::write(&tmp_filename, out)?;
+        std::fs::rename(&tmp_filename, filename)?;
+        Ok(())
+    }
+}
+
+/// the file name of a path without directory and extension
+fn table_name(full_name: &str, extension: &str) -> String {
+    let file_name = full_name.rsplit('/').next().unwrap_or(full_name);
+    file_name
+        .strip_suffix(&format!(".{}", extension))
+        .unwrap_or(file_name)
+        .to_string()
+}
+
+fn read_table(reader: &mut Reader, name: &str) -> Result<Table, String> {
+    let column_count = reader.len()?;
+    let mut column_names = vec![];
+    for _ in 0..column_count {
+        column_names.push(reader.string()?);
+    }
+    let mut column_specs = vec![];
+    for _ in 0..column_count {
+        column_specs.push(reader.string()?);
+    }
+    let row_count = reader.len()?;
+    let mut rows = vec![];
+    for _ in 0..row_count {
+        let mut row = Row::new();
+        for _ in 0..column_count {
+            row.add(reader.data(0)?);
+        }
+        rows.push(row);
+    }
+    Table::from_parts(name, column_names, &column_specs, rows).map_err(|e| e.to_string())
+}
+
+fn write_varint(out: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        out.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn write_signed(out: &mut Vec<u8>, n: i64) {
+    write_varint(out, ((n << 1) ^ (n >> 63)) as u64);
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    write_varint(out, s.len() as u64);
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn write_data(out: &mut Vec<u8>, datum: &Data) {
+    match datum {
+        Data::Empty => out.push(TAG_EMPTY),
+        Data::String(s) => {
+            out.push(TAG_STRING);
+            write_str(out, s);
+        }
+        Data::Int(n) => {
+            out.push(TAG_INT);
+            write_signed(out, *n);
+        }
+        Data::Float(n) => {
+            out.push(TAG_FLOAT);
+            out.extend_from_slice(&n.to_le_bytes());
+        }
+        Data::Decimal(n) => {
+            out.push(TAG_DECIMAL);
+            out.extend_from_slice(&n.mantissa().to_le_bytes());
+            write_varint(out, n.scale().into());
+        }
+        Data::Bool(false) => out.push(TAG_FALSE),
+        Data::Bool(true) => out.push(TAG_TRUE),
+        Data::Date(date) => {
+            out.push(TAG_DATE);
+            write_varint(out, date.year().into());
+            out.push(date.month());
+            out.push(date.day());
+        }
+        Data::Time(time) => {
+            out.push(TAG_TIME);
+            write_varint(out, time.seconds().into());
+        }
+        Data::DateTime(date_time) => {
+            out.push(TAG_DATE_TIME);
+            write_signed(out, date_time.seconds());
+        }
+        Data::Duration(duration) => {
+            out.push(TAG_DURATION);
+            write_signed(out, duration.seconds());
+        }
+        Data::Blob(blob) => {
+            out.push(TAG_BLOB);
+            write_str(out, &blob.to_string());
+        }
+        Data::Join(join) => {
+            out.push(TAG_JOIN);
+            write_varint(out, join.ids.len() as u64);
+            for id in &join.ids {
+                write_signed(out, *id);
+            }
+        }
+        Data::List(items) => {
+            out.push(TAG_LIST);
+            write_varint(out, items.len() as u64);
+            for item in items {
+                write_data(out, item);
+            }
+        }
+    }
+}
+
+/// nesting of lists deeper than this is rejected instead of overflowing the stack
+const MAX_DEPTH: usize = 128;
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("{} at byte {}", message, self.pos)
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<&[u8], String> {
+        if self.bytes.len() - self.pos < n {
+            return Err(self.error("unexpected end of file"));
+        }
+        self.pos += n;
+        Ok(&self.bytes[self.pos - n..self.pos])
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn varint(&mut self) -> Result<u64, String> {
+        let mut n = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            n |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        Err(self.error("invalid number"))
+    }
+
+    fn signed(&mut self) -> Result<i64, String> {
+        let n = self.varint()?;
+        Ok((n >> 1) as i64 ^ -((n & 1) as i64))
+    }
+
+    /// a count of items, each taking at least a byte
+    fn len(&mut self) -> Result<usize, String> {
+        let n = self.varint()?;
+        if n > (self.bytes.len() - self.pos) as u64 {
+            return Err(self.error("invalid length"));
+        }
+        Ok(n as usize)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.len()?;
+        let start = self.pos;
+        let bytes = self.bytes(len)?.to_vec();
+        String::from_utf8(bytes).map_err(|_| {
+            self.pos = start;
+            self.error("invalid UTF-8")
+        })
+    }
+
+    fn data(&mut self, depth: usize) -> Result<Data, String> {
+        let start = self.pos;
+        let tag = self.byte()?;
+        let invalid = |reader: &mut Reader, what: &str| {
+            reader.pos = start;
+            reader.error(&format!("invalid {}", what))
+        };
+        Ok(match tag {
+            TAG_EMPTY => Data::Empty,
+            TAG_STRING => Data::String(self.string()?),
+            TAG_INT => Data::Int(self.signed()?),
+            TAG_FLOAT => {
+                let bytes = self.bytes(8)?;
+                Data::Float(f64::from_le_bytes(bytes.try_into().unwrap_or_default()))
+            }
+            TAG_DECIMAL => {
+                let bytes = self.bytes(16)?;
+                let mantissa = i128::from_le_bytes(bytes.try_into().unwrap_or_default());
+                let scale = self.varint()?;
+                match u32::try_from(scale) {
+                    Ok(scale) => Data::Decimal(Decimal::new(mantissa, scale)),
+                    Err(_) => return Err(invalid(self, "decimal")),
+                }
+            }
+            TAG_FALSE => Data::Bool(false),
+            TAG_TRUE => Data::Bool(true),
+            TAG_DATE => {
+                let year = u16::try_from(self.varint()?).map_err(|_| invalid(self, "date"))?;
+                let month = self.byte()?;
+                let day = self.byte()?;
+                Data::Date(Date::new(year, month, day).map_err(|_| invalid(self, "date"))?)
+            }
+            TAG_TIME => match u32::try_from(self.varint()?) {
+                Ok(seconds) if seconds < 24 * 3600 => Data::Time(Time::new(seconds)),
+                _ => return Err(invalid(self, "time")),
+            },
+            TAG_DATE_TIME => Data::DateTime(DateTime::from_seconds(self.signed()?)),
+            TAG_DURATION => Data::Duration(Duration::from_seconds(self.signed()?)),
+            TAG_BLOB => {
+                let blob = self.string()?;
+                Data::Blob(Blob::parse(&blob).map_err(|_| invalid(self, "blob"))?)
+            }
+            TAG_JOIN => {
+                let len = self.len()?;
+                let mut ids = vec![];
+                for _ in 0..len {
+                    ids.push(self.signed()?);
+                }
+                Data::Join(Join::new(ids))
+            }
+            TAG_LIST if depth < MAX_DEPTH => {
+                let len = self.len()?;
+                let mut items = vec![];
+                for _ in 0..len {
+                    items.push(self.data(depth + 1)?);
+                }
+                Data::List(items)
+            }
+            TAG_LIST => return Err(invalid(self, "list, too deeply nested")),
+            _ => return Err(invalid(self, "value tag")),
+        })
+    }
+}
+
+/// CRC-32 (IEEE 802.3, as in zip and png)
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 1 == 1 {
+                    (crc >> 1) ^ 0xedb8_8320
+                } else {
+                    crc >> 1
+                };
+                bit += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc = TABLE[((crc ^ u32::from(*byte)) & 0xff) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::column_type::ColumnType;
+    use crate::expr::Expr;
+
+    fn temp_file(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("rzdb-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        format!("{}/{}.rzt", dir.to_str().unwrap(), name)
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let mut table = Table::create("binary_round_trip");
+        table.create_column("value").unwrap();
+        table
+            .create_typed_column("amount", ColumnType::Decimal)
+            .unwrap();
+        table
+            .create_computed_column("double", Expr::parse("amount * 2").unwrap(), true)
+            .unwrap();
+        for datum in [
+            Data::String("{a}, \"quoted\"\nline".to_string()),
+            Data::Int(i64::MIN),
+            Data::Float(-0.5),
+            Data::Bool(true),
+            Data::Date(Date::new(2024, 2, 29).unwrap()),
+            Data::Time(Time::new(86399)),
+            Data::DateTime(DateTime::from_seconds(-1)),
+            Data::Duration(Duration::from_seconds(90)),
+            Data::Blob(Blob::from_bytes(b"abc")),
+            Data::Join(Join::new(vec![0, 300])),
+            Data::List(vec![Data::Empty, Data::List(vec![Data::Int(1)])]),
+            Data::Empty,
+        ] {
+            let amount = Data::Decimal(Decimal::parse("-12.50").unwrap());
+            table.insert_data(vec![datum, amount, Data::Empty]).unwrap();
+        }
+        let filename = temp_file("binary_round_trip");
+        BinaryStorage.save(&table, &filename).unwrap();
+        let loaded = BinaryStorage.load(&filename).unwrap();
+        std::fs::remove_file(&filename).unwrap();
+
+        assert_eq!(loaded.get_name(), "binary_round_trip");
+        assert_eq!(loaded.get_column_names(), table.get_column_names());
+        assert_eq!(loaded.column_specs(), table.column_specs());
+        assert_eq!(loaded.select(), table.select());
+        assert!(!loaded.is_changed());
+    }
+
+    #[test]
+    fn test_failed_conversion_keeps_previous_storage() {
+        let db_dir = std::env::temp_dir().join(format!("rzdb-test-{}", std::process::id()));
+        let db_dir = db_dir.to_str().unwrap();
+        let name = "failed_conversion";
+        let mut db = crate::db::Db::create(name, db_dir).unwrap();
+        for table_name in ["first", "second"] {
+            db.create_table(table_name).unwrap();
+            db.create_column(table_name, "value").unwrap();
+            db.insert_data(table_name, vec![Data::Int(1)]).unwrap();
+        }
+        db.save().unwrap();
+
+        // the binary file of the second table can not be written
+        let db_path = format!("{}/{}", db_dir, name);
+        std::fs::create_dir_all(format!("{}/second.rzt", db_path)).unwrap();
+        db.set_storage(Storage::Binary);
+        assert!(db.save().is_err());
+
+        let loaded = crate::db::Db::load(name, db_dir).unwrap();
+        assert_eq!(loaded.get_storage(), Some(Storage::Csv));
+        for table_name in ["first", "second"] {
+            assert_eq!(loaded.get_row_count(table_name).unwrap(), 1);
+        }
+        std::fs::remove_dir_all(&db_path).unwrap();
+    }
+
+    #[test]
+    fn test_failed_save_writes_blobs_first() {
+        let db_dir = std::env::temp_dir().join(format!("rzdb-test-{}", std::process::id()));
+        let db_dir = db_dir.to_str().unwrap();
+        let name = "failed_blob_save";
+        let mut db = crate::db::Db::create(name, db_dir).unwrap();
+        db.create_table("files").unwrap();
+        db.create_column("files", "content").unwrap();
+        let blob = db.store_text("content");
+        db.insert_data("files", vec![blob.clone()]).unwrap();
+
+        // the table file can not be written, but the blob it references is
+        let db_path = format!("{}/{}", db_dir, name);
+        std::fs::create_dir_all(format!("{}/files.csv", db_path)).unwrap();
+        assert!(db.save().is_err());
+        let Data::Blob(stored) = &blob else {
+            panic!("{:?}", blob)
+        };
+        assert!(std::path::Path::new(&format!("{}/blobs/{}", db_path, stored.hash())).exists());
+
+        std::fs::remove_dir(format!("{}/files.csv", db_path)).unwrap();
+        db.save().unwrap();
+        let loaded = crate::db::Db::load(name, db_dir).unwrap();
+        assert_eq!(loaded.read_text(&blob).unwrap(), "content");
+        std::fs::remove_dir_all(&db_path).unwrap();
+    }
+
+    #[test]
+    fn test_binary_load_errors() {
+        let mut table = Table::create("binary_errors");
+        table.create_column("value").unwrap();
+        table.insert_data(vec![Data::Int(1)]).unwrap();
+        let filename = temp_file("binary_errors");
+        BinaryStorage.save(&table, &filename).unwrap();
+        let bytes = std::fs::read(&filename).unwrap();
+        let load = |bytes: &[u8]| {
+            std::fs::write(&filename, bytes).unwrap();
+            match BinaryStorage.load(&filename) {
+                Err(Error::ParseError { message, .. }) => message,
+                other => panic!("{:?}", other.map(|table| table.select())),
+            }
+        };
+
+        let mut damaged = bytes.clone();
+        let last_value = damaged.len() - 5;
+        damaged[last_value] ^= 1;
+        assert!(load(&damaged).ends_with("checksum mismatch, the file is damaged"));
+        assert!(load(&bytes[..bytes.len() - 1]).ends_with("checksum mismatch, the file is damaged"));
+        assert!(load(b"value\n1\n").ends_with("not a binary table file"));
+
+        let mut newer = b"RZDB\x02".to_vec();
+        newer.extend_from_slice(&crc32(&newer).to_le_bytes());
+        assert!(load(&newer).contains("unsupported file format version 2"));
+
+        // a valid checksum over content that is cut short
+        let mut truncated = bytes[..bytes.len() - 5].to_vec();
+        truncated.extend_from_slice(&crc32(&truncated).to_le_bytes());
+        assert!(load(&truncated).contains("unexpected end of file"));
+
+        let mut bad_tag = bytes[..bytes.len() - 4].to_vec();
+        let tag = bad_tag.len() - 2;
+        bad_tag[tag] = 99;
+        bad_tag.extend_from_slice(&crc32(&bad_tag).to_le_bytes());
+        assert!(load(&bad_tag).contains("invalid value tag"));
+        std::fs::remove_file(&filename).unwrap();
+    }
+}
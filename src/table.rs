@@ -1,113 +1,320 @@
+use crate::aggregate::Aggregate;
+use crate::column_type::ColumnType;
 use crate::condition::Condition;
-use crate::data::Data;
+use crate::csv::{quote_field, CsvReader};
+use crate::data::{split_csv_line, Data};
+use crate::decimal::Decimal;
+use crate::error::{check_index, Error};
+use crate::expr::Expr;
 use crate::row::Row;
-use std::error::Error;
+use crate::time::DateFormat;
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+const VERSION_PREFIX: &str = "#rzdb:";
+const FORMAT_VERSION: u32 = 2;
+const COMPUTED_PREFIX: &str = "computed:";
+const MATERIALIZED_PREFIX: &str = "materialized:";
 
 pub struct Table {
     name: String,
     column_names: Vec<String>,
+    column_types: Vec<ColumnType>,
+    /// the expressions of computed columns, parallel to column_names
+    computed: Vec<Option<Computed>>,
     rows: Vec<Row>,
     changed: bool,
 }
 
+/// A column computed from the other columns of its row. Virtual columns are evaluated when
+/// rows are selected and stored empty; materialized columns store their values, which are
+/// recomputed whenever the row changes.
+#[derive(Clone, PartialEq, Debug)]
+struct Computed {
+    expr: Expr,
+    materialized: bool,
+}
+
+/// an error in a table file, with the line number counting from 1
+fn load_error(full_name: &str, line: usize, message: String) -> Error {
+    Error::ParseError {
+        line: Some(line),
+        column: None,
+        message: format!("Table::load({}): {}", full_name, message),
+    }
+}
+
+/// parses a column type as written by `Table::column_specs()`
+fn parse_column_spec(spec: &str) -> Result<(ColumnType, Option<Computed>), Error> {
+    let computed = |expr: &str, materialized| -> Result<_, Error> {
+        Ok((
+            ColumnType::Any,
+            Some(Computed {
+                expr: Expr::parse(expr)?,
+                materialized,
+            }),
+        ))
+    };
+    if let Some(expr) = spec.strip_prefix(COMPUTED_PREFIX) {
+        computed(expr, false)
+    } else if let Some(expr) = spec.strip_prefix(MATERIALIZED_PREFIX) {
+        computed(expr, true)
+    } else {
+        Ok((ColumnType::parse(spec)?, None))
+    }
+}
+
+/// the fields of a record in a table file with the line it starts on
+type Record = (usize, Vec<String>);
+
 impl Table {
     pub fn create(name: &str) -> Table {
         Table {
             name: name.to_string(),
             column_names: vec![],
+            column_types: vec![],
+            computed: vec![],
             rows: vec![],
             changed: false,
         }
     }
 
-    pub fn load(full_name: &str) -> Result<Table, Box<dyn Error>> {
-        let content = std::fs::read_to_string(full_name)?;
+    /// Loads a table file. Files of the current format start with a version line
+    /// (`#rzdb:2`) followed by the column names, the column types and rows with typed values,
+    /// read as a stream of RFC 4180 csv records, so quoted values may span lines.
+    /// Computed columns have `computed:<expression>` or `materialized:<expression>` as
+    /// their type.
+    /// Files without a version line are from the old untyped format: their values are
+    /// guessed with `Data::parse()`. They are marked as changed, so the next save rewrites
+    /// them in the current format.
+    pub fn load(full_name: &str) -> Result<Table, Error> {
+        let mut reader = BufReader::new(File::open(full_name)?);
+        let mut first_line = String::new();
+        reader.read_line(&mut first_line)?;
+        let first_line = first_line
+            .trim_start_matches('\u{feff}')
+            .trim_end_matches(['\r', '\n'])
+            .to_string();
+
+        let untyped = match first_line.strip_prefix(VERSION_PREFIX) {
+            Some(version) if version.trim() == FORMAT_VERSION.to_string() => false,
+            Some(version) => {
+                return Err(load_error(
+                    full_name,
+                    1,
+                    format!(
+                        "unsupported file format version {} (supported: {})",
+                        version, FORMAT_VERSION,
+                    ),
+                ))
+            }
+            None => true,
+        };
+
+        let mut records: Box<dyn Iterator<Item = Result<Record, Error>>> = if !untyped {
+            let mut csv = CsvReader::starting_at_line(reader, 2);
+            Box::new(std::iter::from_fn(move || {
+                let record = csv.next()?;
+                Some(
+                    record
+                        .map(|fields| (csv.record_line(), fields))
+                        .map_err(|e| e.in_context(&format!("Table::load({})", full_name))),
+                )
+            }))
+        } else {
+            // the first line is already the header of an untyped file
+            Box::new(
+                std::iter::once(Ok(first_line))
+                    .chain(reader.lines())
+                    .zip(1..)
+                    .map(|(line, idx)| Ok((idx, split_csv_line(&line?)))),
+            )
+        };
+        let decode: fn(&str) -> Result<Data, Error> = if untyped {
+            |field| Ok(Data::decode_field(field))
+        } else {
+            Data::decode_typed
+        };
 
-        let lines = content.lines();
-        let mut column_names: Vec<String> = vec![];
+        let column_names: Vec<String> = match records.next().transpose()? {
+            Some((_, fields)) if untyped => fields
+                .iter()
+                .map(|field| Data::decode_field(field).to_string())
+                .collect(),
+            Some((_, fields)) => fields,
+            None => vec![],
+        };
+        let mut column_types = vec![ColumnType::Any; column_names.len()];
+        let mut computed = vec![None; column_names.len()];
+        if !untyped {
+            if let Some((line, fields)) = records.next().transpose()? {
+                for (idx, spec) in fields.iter().enumerate().take(column_names.len()) {
+                    (column_types[idx], computed[idx]) = parse_column_spec(spec)
+                        .map_err(|e| load_error(full_name, line, e.to_string()))?;
+                }
+            }
+        }
 
         // rows
         let mut rows = vec![];
-        for (idx, line) in lines.enumerate() {
+        for record in records {
+            let (line, fields) = record?;
+            let num_columns = column_names.len();
+            if fields.len() > num_columns {
+                return Err(load_error(
+                    full_name,
+                    line,
+                    format!(
+                        "table has {} columns, but the row has {} columns",
+                        num_columns,
+                        fields.len(),
+                    ),
+                ));
+            }
             let mut row = Row::new();
-            let mut data = Data::decode_line(line);
-            if idx == 0 {
-                for datum in data {
-                    column_names.push(datum.to_string());
-                }
-            } else {
-                let num_columns = column_names.len();
-                if data.len() > num_columns {
-                    return Err(Box::new(std::io::Error::new(
-                        std::io::ErrorKind::InvalidData,
-                        format!(
-                            "Table::load({}): table has {} columns, but row nr. {} has {} columns)",
-                            full_name,
-                            data.len(),
-                            idx,
-                            num_columns,
-                        ),
-                    )));
-                }
-                while data.len() < num_columns {
-                    data.push(Data::Empty);
-                }
-                for datum in data {
-                    row.add(datum);
-                }
-                rows.push(row);
+            for field in &fields {
+                row.add(decode(field).map_err(|e| load_error(full_name, line, e.to_string()))?);
+            }
+            for _ in fields.len()..num_columns {
+                row.add(Data::Empty);
             }
+            rows.push(row);
         }
 
-        // table name
-        let name = full_name
-            .split('/')
-            .last()
-            .unwrap()
-            .split(".csv")
-            .next()
-            .unwrap()
+        // table name: the file name without extension
+        let file_name = full_name.rsplit('/').next().unwrap_or(full_name);
+        let name = file_name
+            .rsplit_once('.')
+            .map_or(file_name, |(name, _)| name)
             .to_string();
 
         Ok(Table {
             name,
             column_names,
+            column_types,
+            computed,
             rows,
-            changed: false,
+            changed: untyped,
         })
     }
 
-    pub fn save(&mut self, filename: &str) -> Result<(), Box<dyn Error>> {
-        let mut out = String::new();
+    /// saves the table in the csv format and marks it as unchanged
+    pub fn save(&mut self, filename: &str) -> Result<(), Error> {
+        self.write(filename)?;
+        self.changed = false;
+        Ok(())
+    }
+
+    /// Writes the table in the csv format, see load().
+    pub fn write(&self, filename: &str) -> Result<(), Error> {
+        let mut out = format!("{}{}\n", VERSION_PREFIX, FORMAT_VERSION);
         for (idx, name) in self.get_column_names().iter().enumerate() {
             if idx > 0 {
                 out.push(',');
             }
-            let encoded = Data::String(name.clone()).encode_for_csv();
-            out.push_str(&encoded.to_string());
+            // an empty line would be a table without columns
+            if name.is_empty() {
+                out.push_str("\"\"");
+            } else {
+                out.push_str(&quote_field(name));
+            }
         }
         out.push('\n');
+        let column_specs: Vec<String> = self
+            .column_specs()
+            .iter()
+            .map(|spec| quote_field(spec))
+            .collect();
+        out.push_str(&column_specs.join(","));
+        out.push('\n');
 
-        let all_data = self.select();
-        for row in all_data {
+        // virtual columns are stored empty
+        for row in &self.rows {
             for (idx, value) in row.iter().enumerate() {
                 if idx > 0 {
                     out.push(',');
                 }
-                out.push_str(&value.encode_for_csv());
+                out.push_str(&value.encode_typed());
             }
             out.push('\n');
         }
 
         std::fs::write(filename, out)?;
-        self.changed = false;
         Ok(())
     }
 
+    /// A table from the parts stored in a table file; `column_specs` are the column types
+    /// as written by `column_specs()`. Rows must have a value for every column.
+    pub fn from_parts(
+        name: &str,
+        column_names: Vec<String>,
+        column_specs: &[String],
+        rows: Vec<Row>,
+    ) -> Result<Table, Error> {
+        if column_specs.len() != column_names.len() {
+            return Err(Error::invalid(format!(
+                "Table {}: {} columns, but {} column types",
+                name,
+                column_names.len(),
+                column_specs.len()
+            )));
+        }
+        let mut column_types = vec![];
+        let mut computed = vec![];
+        for spec in column_specs {
+            let (column_type, column_computed) = parse_column_spec(spec)?;
+            column_types.push(column_type);
+            computed.push(column_computed);
+        }
+        Ok(Table {
+            name: name.to_string(),
+            column_names,
+            column_types,
+            computed,
+            rows,
+            changed: false,
+        })
+    }
+
+    /// the column types as stored in table files, `computed:<expression>` or
+    /// `materialized:<expression>` for computed columns
+    pub fn column_specs(&self) -> Vec<String> {
+        self.column_types
+            .iter()
+            .zip(&self.computed)
+            .map(|(column_type, computed)| match computed {
+                Some(Computed {
+                    expr,
+                    materialized: false,
+                }) => format!("{}{}", COMPUTED_PREFIX, expr),
+                Some(Computed {
+                    expr,
+                    materialized: true,
+                }) => format!("{}{}", MATERIALIZED_PREFIX, expr),
+                None => column_type.to_string(),
+            })
+            .collect()
+    }
+
+    /// the stored rows, with virtual columns empty
+    pub fn rows(&self) -> &[Row] {
+        &self.rows
+    }
+
+    /// marks the table as saved, or as to be saved by the next `Db::save()`
+    pub(crate) fn set_changed(&mut self, changed: bool) {
+        self.changed = changed;
+    }
+
     pub fn len(&self) -> usize {
         self.rows.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
     pub fn is_changed(&self) -> bool {
         self.changed
     }
@@ -116,14 +323,14 @@ impl Table {
         self.name.clone()
     }
 
-    pub fn get_column_idx_result(&self, name: &str) -> Result<usize, Box<dyn Error>> {
+    pub fn get_column_idx_result(&self, name: &str) -> Result<usize, Error> {
         if let Some(idx) = self.column_names.iter().position(|n| n == name) {
             Ok(idx)
         } else {
-            Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::NotFound,
-                format!("Column {} not found", name),
-            )))
+            Err(Error::ColumnNotFound {
+                table: self.name.clone(),
+                column: name.to_string(),
+            })
         }
     }
 
@@ -131,14 +338,24 @@ impl Table {
         self.column_names.iter().position(|n| n == name)
     }
 
-    pub fn create_column(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+    pub fn create_column(&mut self, name: &str) -> Result<(), Error> {
+        self.create_typed_column(name, ColumnType::Any)
+    }
+
+    pub fn create_typed_column(
+        &mut self,
+        name: &str,
+        column_type: ColumnType,
+    ) -> Result<(), Error> {
         if self.get_column_idx_option(name).is_some() {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::AlreadyExists,
-                format!("column {} already exists", name),
-            )));
+            return Err(Error::DuplicateColumn {
+                table: self.name.clone(),
+                column: name.to_string(),
+            });
         }
         self.column_names.push(name.to_string());
+        self.column_types.push(column_type);
+        self.computed.push(None);
         for row in &mut self.rows {
             row.add(Data::Empty);
         }
@@ -146,262 +363,483 @@ impl Table {
         Ok(())
     }
 
-    pub fn rename_column(&mut self, old_name: &str, new_name: &str) -> Result<(), Box<dyn Error>> {
+    /// Adds a column computed by an expression over the other columns. Computed columns see
+    /// the computed values of the columns to their left.
+    pub fn create_computed_column(
+        &mut self,
+        name: &str,
+        expr: Expr,
+        materialized: bool,
+    ) -> Result<(), Error> {
+        for column in expr.columns() {
+            if column == name || self.get_column_idx_option(&column).is_none() {
+                return Err(Error::ConstraintViolation {
+                    table: self.name.clone(),
+                    column: name.to_string(),
+                    message: format!("is computed, it can not use column {}", column),
+                });
+            }
+        }
+        self.create_typed_column(name, ColumnType::Any)?;
+        if let Some(computed) = self.computed.last_mut() {
+            *computed = Some(Computed { expr, materialized });
+        }
+        self.refresh_all();
+        Ok(())
+    }
+
+    /// the expression of a computed column
+    pub fn get_column_expression(&self, column_idx: usize) -> Option<Expr> {
+        self.computed
+            .get(column_idx)?
+            .as_ref()
+            .map(|computed| computed.expr.clone())
+    }
+
+    /// Returns the row with the values of the computed columns, evaluated in column order.
+    /// Values that can not be computed, e.g. for a division by zero, are empty.
+    fn evaluate(&self, row: &Row, include_materialized: bool) -> Row {
+        let mut row = row.clone();
+        for (idx, computed) in self.computed.iter().enumerate() {
+            let Some(computed) = computed else {
+                continue;
+            };
+            if computed.materialized && !include_materialized {
+                continue;
+            }
+            let value = computed
+                .expr
+                .eval(&|name: &str| {
+                    self.get_column_idx_option(name)
+                        .and_then(|idx| row.select_at(idx).ok())
+                })
+                .unwrap_or(Data::Empty);
+            let _ = row.set_at(idx, value);
+        }
+        row
+    }
+
+    /// recomputes the materialized columns of a row
+    fn refresh_row(&mut self, row_idx: usize) {
+        if !self.computed.iter().flatten().any(|c| c.materialized) {
+            return;
+        }
+        let Some(row) = self.rows.get(row_idx) else {
+            return;
+        };
+        let evaluated = self.evaluate(row, true);
+        for (idx, computed) in self.computed.iter().enumerate() {
+            if let Some(Computed {
+                materialized: true, ..
+            }) = computed
+            {
+                let value = evaluated.select_at(idx).unwrap_or(Data::Empty);
+                let _ = self.rows[row_idx].set_at(idx, value);
+            }
+        }
+    }
+
+    fn refresh_all(&mut self) {
+        for row_idx in 0..self.rows.len() {
+            self.refresh_row(row_idx);
+        }
+    }
+
+    pub fn rename_column(&mut self, old_name: &str, new_name: &str) -> Result<(), Error> {
         if self.get_column_idx_result(new_name).is_ok() {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::AlreadyExists,
-                format!("column {} already exists", new_name),
-            )));
+            return Err(Error::DuplicateColumn {
+                table: self.name.clone(),
+                column: new_name.to_string(),
+            });
         }
         let idx = self.get_column_idx_result(old_name)?;
         self.column_names[idx] = new_name.to_string();
+        for computed in self.computed.iter_mut().flatten() {
+            computed.expr.rename_column(old_name, new_name);
+        }
         self.changed = true;
         Ok(())
     }
 
-    pub fn insert_empty_row_at(&mut self, index: usize) {
+    pub fn insert_empty_row_at(&mut self, index: usize) -> Result<(), Error> {
+        check_index("Table::insert_empty_row_at", index, self.rows.len() + 1)?;
         let column_count = self.column_count();
         self.rows
             .insert(index, Row::from(vec![Data::Empty; column_count]));
+        self.refresh_row(index);
         self.changed = true;
+        Ok(())
     }
-    pub fn insert_rows_at(&mut self, index: usize, rows: Vec<Row>) {
+    pub fn insert_rows_at(&mut self, index: usize, rows: Vec<Row>) -> Result<(), Error> {
+        check_index("Table::insert_rows_at", index, self.rows.len() + 1)?;
+        if let Some(row) = rows.iter().find(|row| row.len() != self.column_count()) {
+            return Err(Error::ArityMismatch {
+                table: self.name.clone(),
+                expected: self.column_count(),
+                found: row.len(),
+            });
+        }
+        let count = rows.len();
         self.rows.splice(index..index, rows);
+        for row_idx in index..index + count {
+            self.refresh_row(row_idx);
+        }
         self.changed = true;
+        Ok(())
     }
-    pub fn insert_column_at(&mut self, column_name: &str, idx: usize) {
+    pub fn insert_column_at(&mut self, column_name: &str, idx: usize) -> Result<(), Error> {
+        check_index("Table::insert_column_at", idx, self.column_count() + 1)?;
+        if self.get_column_idx_option(column_name).is_some() {
+            return Err(Error::DuplicateColumn {
+                table: self.name.clone(),
+                column: column_name.to_string(),
+            });
+        }
         self.column_names.insert(idx, column_name.to_string());
+        self.column_types.insert(idx, ColumnType::Any);
+        self.computed.insert(idx, None);
         for row in &mut self.rows {
-            row.insert_at(idx, Data::Empty);
+            row.insert_at(idx, Data::Empty)?;
         }
         self.changed = true;
+        Ok(())
     }
 
     pub fn delete_all(&mut self) {
         self.column_names.clear();
+        self.column_types.clear();
+        self.computed.clear();
         self.rows.clear();
         self.changed = true;
     }
 
-    pub fn delete_row(&mut self, row_idx: usize) {
+    pub fn delete_row(&mut self, row_idx: usize) -> Result<(), Error> {
+        check_index("Table::delete_row", row_idx, self.rows.len())?;
         self.rows.remove(row_idx);
         self.changed = true;
+        Ok(())
     }
 
-    pub fn delete_where(&mut self, conditions: &[Condition]) -> Result<(), Box<dyn Error>> {
-        // find the ids that match the columns
-        let mut column_ids = vec![];
-        let column_names = self.get_column_names();
-        for condition in conditions {
-            if let Some(idx) = column_names.iter().position(|x| x == condition.column) {
-                column_ids.push(idx);
-            } else {
-                return Err(Box::new(std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    format!(
-                        "Table::delete_where: column {} not found in table {}",
-                        self.name, condition.column,
-                    ),
-                )));
-            }
-        }
-
-        // find the rows that match the conditions
-        let mut rows_to_delete = vec![];
-        for (idx, row) in self.select().iter().enumerate() {
-            let mut matches = true;
-            for (condition_index, condition) in conditions.iter().enumerate() {
-                let column_id = column_ids[condition_index];
-                let value = row.select_at(column_id).unwrap();
-                if !condition.matches(&value) {
-                    matches = false;
-                    break;
-                }
-            }
-            if matches {
-                rows_to_delete.push(idx);
-            }
-        }
+    pub fn delete_where(&mut self, conditions: &[Condition]) -> Result<(), Error> {
+        let rows_to_delete: Vec<usize> = self
+            .select_where_idx(conditions)?
+            .into_iter()
+            .map(|(idx, _)| idx)
+            .collect();
 
         // delete the rows
         for row_idx in rows_to_delete.iter().rev() {
-            self.delete_row(*row_idx);
+            self.delete_row(*row_idx)?;
         }
 
         Ok(())
     }
 
-    pub fn delete_column(&mut self, column_name: &str) -> Result<(), Box<dyn Error>> {
+    /// deletes a column; fails if a computed column uses it
+    pub fn delete_column(&mut self, column_name: &str) -> Result<(), Error> {
         if let Some(idx) = self.get_column_idx_option(column_name) {
+            let dependents: Vec<String> = self
+                .computed
+                .iter()
+                .zip(&self.column_names)
+                .filter(|(computed, _)| {
+                    computed
+                        .as_ref()
+                        .is_some_and(|c| c.expr.columns().iter().any(|c| c == column_name))
+                })
+                .map(|(_, name)| name.clone())
+                .collect();
+            if !dependents.is_empty() {
+                return Err(Error::ConstraintViolation {
+                    table: self.name.clone(),
+                    column: column_name.to_string(),
+                    message: format!("is used by computed columns: {}", dependents.join(", ")),
+                });
+            }
             for row in &mut self.rows {
-                row.delete(idx);
+                row.delete(idx)?;
             }
             self.column_names.remove(idx);
+            self.column_types.remove(idx);
+            self.computed.remove(idx);
+            self.refresh_all();
             self.changed = true;
             Ok(())
         } else {
-            Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::NotFound,
-                format!("column {} not found", column_name),
-            )))
+            Err(Error::ColumnNotFound {
+                table: self.name.clone(),
+                column: column_name.to_string(),
+            })
         }
     }
 
-    pub fn insert(&mut self, values: Vec<&str>) -> Result<(), Box<dyn Error>> {
-        let mut row = Row::new();
-        for value in &values {
-            row.add_parse(value);
-        }
+    pub fn insert(&mut self, values: Vec<&str>, date_format: &DateFormat) -> Result<(), Error> {
         if self.column_names.len() != values.len() {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!(
-                    "Table::insert({}, {:?}): tried to insert {} items, but have {} columns.",
-                    self.name,
-                    values,
-                    self.column_names.len(),
-                    values.len(),
-                ),
-            )));
+            return Err(Error::ArityMismatch {
+                table: self.name.clone(),
+                expected: self.column_names.len(),
+                found: values.len(),
+            });
         }
+        let row = self.parse_values(&values, date_format)?;
         self.rows.push(row);
+        self.refresh_row(self.rows.len() - 1);
         self.changed = true;
         Ok(())
     }
 
-    pub fn insert_at(&mut self, values: Vec<&str>, idx: usize) -> Result<(), Box<dyn Error>> {
-        let mut row = Row::new();
-        for value in &values {
-            row.add_parse(value);
-        }
+    pub fn insert_at(
+        &mut self,
+        values: Vec<&str>,
+        idx: usize,
+        date_format: &DateFormat,
+    ) -> Result<(), Error> {
         if self.column_names.len() != values.len() {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!(
-                    "Table::insert({}, {:?}): tried to insert {} items, but have {} columns.",
-                    self.name,
-                    values,
-                    self.column_names.len(),
-                    values.len(),
-                ),
-            )));
+            return Err(Error::ArityMismatch {
+                table: self.name.clone(),
+                expected: self.column_names.len(),
+                found: values.len(),
+            });
         }
+        check_index("Table::insert_at", idx, self.rows.len() + 1)?;
+        let row = self.parse_values(&values, date_format)?;
         self.rows.insert(idx, row);
+        self.refresh_row(idx);
         self.changed = true;
         Ok(())
     }
 
-    pub fn insert_data(&mut self, data: Vec<Data>) -> Result<(), Box<dyn Error>> {
+    /// parses text values according to the column types
+    fn parse_values(&self, values: &[&str], date_format: &DateFormat) -> Result<Row, Error> {
+        let mut row = Row::new();
+        for (idx, value) in values.iter().enumerate() {
+            if self.computed[idx].is_some() && !value.is_empty() {
+                return Err(Error::ConstraintViolation {
+                    table: self.name.clone(),
+                    column: self.column_names[idx].clone(),
+                    message: "is computed, its value must be empty".to_string(),
+                });
+            }
+            let datum = self.column_types[idx]
+                .parse_value_with(value, date_format)
+                .map_err(|_| Error::TypeMismatch {
+                    table: self.name.clone(),
+                    column: self.column_names[idx].clone(),
+                    expected: self.column_types[idx].to_string(),
+                    value: value.to_string(),
+                })?;
+            row.add(datum);
+        }
+        Ok(row)
+    }
+
+    /// checks that values match the column types
+    fn check_types(&self, data: &[Data]) -> Result<(), Error> {
+        for (idx, datum) in data.iter().enumerate() {
+            self.check_type(idx, datum)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_type(&self, column_idx: usize, datum: &Data) -> Result<(), Error> {
+        if let Some(Some(_)) = self.computed.get(column_idx) {
+            if *datum != Data::Empty {
+                return Err(Error::ConstraintViolation {
+                    table: self.name.clone(),
+                    column: self.column_names[column_idx].clone(),
+                    message: "is computed, it can not be set".to_string(),
+                });
+            }
+        }
+        if let Some(column_type) = self.column_types.get(column_idx) {
+            if !column_type.accepts(datum) {
+                return Err(Error::TypeMismatch {
+                    table: self.name.clone(),
+                    column: self.column_names[column_idx].clone(),
+                    expected: column_type.to_string(),
+                    value: datum.to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// converts a value to the type of a column where that is lossless, strings are parsed
+    /// with the column type; fails if the column does not accept the result
+    #[cfg(feature = "serde")]
+    pub(crate) fn coerce(
+        &self,
+        column_idx: usize,
+        datum: Data,
+        date_format: &DateFormat,
+    ) -> Result<Data, Error> {
+        let datum = match (&self.column_types[column_idx], datum) {
+            (ColumnType::Float, Data::Int(n)) => Data::Float(n as f64),
+            (ColumnType::Decimal, Data::Int(n)) => Data::Decimal(Decimal::from_int(n)),
+            (ColumnType::Any | ColumnType::String, datum) => datum,
+            (column_type, Data::String(s)) => column_type
+                .parse_value_with(&s, date_format)
+                .map_err(|_| Error::TypeMismatch {
+                    table: self.name.clone(),
+                    column: self.column_names[column_idx].clone(),
+                    expected: column_type.to_string(),
+                    value: s,
+                })?,
+            (_, datum) => datum,
+        };
+        self.check_type(column_idx, &datum)?;
+        Ok(datum)
+    }
+
+    pub fn insert_data(&mut self, data: Vec<Data>) -> Result<(), Error> {
         if self.column_names.len() != data.len() {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!(
-                    "Table::insert({}, {:?}): tried to insert {} items, but have {} columns.",
-                    self.name,
-                    data,
-                    self.column_names.len(),
-                    data.len(),
-                ),
-            )));
+            return Err(Error::ArityMismatch {
+                table: self.name.clone(),
+                expected: self.column_names.len(),
+                found: data.len(),
+            });
         }
+        self.check_types(&data)?;
         self.rows.push(Row::from(data));
+        self.refresh_row(self.rows.len() - 1);
         self.changed = true;
         Ok(())
     }
 
-    pub fn insert_into_at(&mut self, index: usize, rows: Vec<Row>) {
-        self.rows.splice(index..index, rows);
-        self.changed = true;
+    pub fn insert_into_at(&mut self, index: usize, rows: Vec<Row>) -> Result<(), Error> {
+        self.insert_rows_at(index, rows)
     }
 
-    pub fn insert_columns_at(&mut self, index: usize, table: &Table) -> Result<(), Box<dyn Error>> {
+    pub fn insert_columns_at(&mut self, index: usize, table: &Table) -> Result<(), Error> {
+        check_index("Table::insert_columns_at", index, self.column_count() + 1)?;
         // check number of rows is equal
         if self.row_count() != table.row_count() {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!(
-                    "Table::insert_columns_at({}, {:?}): tried to insert {} rows, but have {} rows.",
-                    self.name,
-                    table.name,
-                    self.row_count(),
-                    table.row_count(),
-                ),
-            )));
+            return Err(Error::ArityMismatch {
+                table: table.name.clone(),
+                expected: self.row_count(),
+                found: table.row_count(),
+            });
         }
         // check for duplicates; TODO: use any(), because it looks cooler
         let self_names = self.get_column_names();
         let other_names = table.get_column_names();
         for name in other_names {
             if self_names.contains(&name) {
-                return Err(Box::new(std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    format!(
-                        "Table::insert_columns_at({}, {}): column {} already exists",
-                        self.name, table.name, name
-                    ),
-                )));
+                return Err(Error::DuplicateColumn {
+                    table: self.name.clone(),
+                    column: name.clone(),
+                });
             }
         }
         // insert column headers
         self.column_names
             .splice(index..index, table.get_column_names());
+        self.column_types
+            .splice(index..index, table.column_types.iter().cloned());
+        self.computed
+            .splice(index..index, table.computed.iter().cloned());
         // insert columns
         for (row_index, row) in &mut self.rows.iter_mut().enumerate() {
-            row.insert_columns_at(index, &table.rows[row_index].clone());
+            row.insert_columns_at(index, &table.rows[row_index])?;
         }
+        self.refresh_all();
         self.changed = true;
         Ok(())
     }
 
+    /// sets columns of all rows matching the conditions to the values of expressions,
+    /// evaluated with the row's values before the update; returns the number of updated
+    /// rows. Nothing is changed if any value can not be computed or stored.
+    pub fn update_where(
+        &mut self,
+        assignments: &[(&str, Expr)],
+        conditions: &[Condition],
+    ) -> Result<usize, Error> {
+        let mut targets = vec![];
+        for (column, expr) in assignments {
+            let idx = self.get_column_idx_result(column)?;
+            for name in expr.columns() {
+                self.get_column_idx_result(&name)?;
+            }
+            targets.push((idx, expr));
+        }
+
+        // compute all values first, so that an error does not leave a partial update
+        let mut updates = vec![];
+        for (row_idx, row) in self.select_where_idx(conditions)? {
+            let value = |name: &str| {
+                let idx = self.get_column_idx_option(name)?;
+                row.select_at(idx).ok()
+            };
+            let mut values = vec![];
+            for (column_idx, expr) in &targets {
+                let datum = match (&self.column_types[*column_idx], expr.eval(&value)?) {
+                    (ColumnType::Float, Data::Int(n)) => Data::Float(n as f64),
+                    (ColumnType::Decimal, Data::Int(n)) => Data::Decimal(Decimal::from_int(n)),
+                    (_, datum) => datum,
+                };
+                self.check_type(*column_idx, &datum)?;
+                values.push((*column_idx, datum));
+            }
+            updates.push((row_idx, values));
+        }
+
+        let count = updates.len();
+        for (row_idx, values) in updates {
+            for (column_idx, datum) in values {
+                self.rows[row_idx].set_at(column_idx, datum)?;
+            }
+            self.refresh_row(row_idx);
+        }
+        if count > 0 {
+            self.changed = true;
+        }
+        Ok(count)
+    }
+
     pub fn insert_update_where(
         &mut self,
         values: Vec<&str>,
         conditions: &[Condition],
-    ) -> Result<(), Box<dyn Error>> {
+        date_format: &DateFormat,
+    ) -> Result<(), Error> {
         if self.column_names.len() != values.len() {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!(
-                    "Table::insert({}, {:?}): tried to insert {} items, but have {} columns.",
-                    self.name,
-                    values,
-                    self.column_names.len(),
-                    values.len(),
-                ),
-            )));
+            return Err(Error::ArityMismatch {
+                table: self.name.clone(),
+                expected: self.column_names.len(),
+                found: values.len(),
+            });
         }
         let mut rows_to_update = vec![];
-        for (idx, _row) in self.select_where_idx(conditions).unwrap() {
+        for (idx, _row) in self.select_where_idx(conditions)? {
             rows_to_update.push(idx);
         }
         if rows_to_update.is_empty() {
-            self.insert(values)?;
+            self.insert(values, date_format)?;
         } else {
-            let mut row = Row::new();
-            for value in &values {
-                row.add_parse(value);
-            }
+            let row = self.parse_values(&values, date_format)?;
             for idx in rows_to_update {
                 self.rows[idx] = row.clone();
+                self.refresh_row(idx);
             }
         }
         self.changed = true;
         Ok(())
     }
 
-    pub fn append_rows(&mut self, rows: &mut Vec<Row>) -> Result<(), Box<dyn Error>> {
+    pub fn append_rows(&mut self, rows: &mut Vec<Row>) -> Result<(), Error> {
         if !rows.is_empty() && self.column_count() != rows.first().unwrap().len() {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!(
-                    "Table::append_rows() (table: {}): tried to append {} items, but have {} columns.",
-                    self.name,
-                    self.column_names.len(),
-                    rows.first().unwrap().len(),
-                ),
-            )));
+            return Err(Error::ArityMismatch {
+                table: self.name.clone(),
+                expected: self.column_names.len(),
+                found: rows.first().unwrap().len(),
+            });
         }
+        let start = self.rows.len();
         self.rows.append(rows);
+        for row_idx in start..self.rows.len() {
+            self.refresh_row(row_idx);
+        }
         self.changed = true;
         Ok(())
     }
@@ -409,29 +847,26 @@ impl Table {
     pub fn select(&self) -> Vec<Row> {
         let mut result = vec![];
         for row in &self.rows {
-            result.push(row.clone());
+            result.push(self.evaluate(row, false));
         }
         result
     }
 
-    pub fn select_columns(&self, column_names: &[&str]) -> Result<Vec<Row>, Box<dyn Error>> {
+    pub fn select_columns(&self, column_names: &[&str]) -> Result<Vec<Row>, Error> {
         let mut column_ids = vec![];
         for column_name in column_names {
             if let Some(idx) = self.get_column_idx_option(column_name) {
                 column_ids.push(idx);
             } else {
-                return Err(Box::new(std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    format!(
-                        "Table::select_columns({}): column {} not found.",
-                        self.name, column_name,
-                    ),
-                )));
+                return Err(Error::ColumnNotFound {
+                    table: self.name.clone(),
+                    column: column_name.to_string(),
+                });
             }
         }
 
         let mut result = vec![];
-        for row in &self.rows {
+        for row in self.select() {
             let mut new_row = Row::new();
             for column_id in &column_ids {
                 new_row.add(row.select_at(*column_id).unwrap());
@@ -441,110 +876,73 @@ impl Table {
         Ok(result)
     }
 
-    pub fn select_at(&self, col_idx: usize, row_idx: usize) -> Result<Data, Box<dyn Error>> {
-        if row_idx >= self.rows.len() {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!(
-                    "Table::select_at({}, {}): row index out of bounds ({} rows)",
-                    col_idx,
-                    row_idx,
-                    self.rows.len()
-                ),
-            )));
-        }
+    pub fn select_at(&self, col_idx: usize, row_idx: usize) -> Result<Data, Error> {
+        check_index(
+            &format!("Table {}: row", self.name),
+            row_idx,
+            self.rows.len(),
+        )?;
         let len = self.rows[row_idx].len();
-        if col_idx >= len {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!(
-                    "Table::select_at({}, {}): column index out of bounds ({} columns)",
-                    col_idx, row_idx, len
-                ),
-            )));
+        check_index(&format!("Table {}: column", self.name), col_idx, len)?;
+        if let Some(Some(Computed {
+            materialized: false,
+            ..
+        })) = self.computed.get(col_idx)
+        {
+            return self.evaluate(&self.rows[row_idx], false).select_at(col_idx);
         }
         self.rows[row_idx].select_at(col_idx)
     }
 
-    pub fn select_where(&self, conditions: &[Condition]) -> Result<Vec<Row>, Box<dyn Error>> {
-        // find the ids that match the columns
-        let mut column_ids = vec![];
-        let column_names = self.get_column_names();
-        for condition in conditions {
-            if let Some(idx) = column_names.iter().position(|x| x == condition.column) {
-                column_ids.push(idx);
-            } else {
-                return Err(Box::new(std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    format!(
-                        "Table::select_where({}): column {} not found.",
-                        self.name, condition.column,
-                    ),
-                )));
-            }
-        }
-
-        // find the rows that match the conditions
-        let mut result = vec![];
-        for row in self.select() {
-            let mut matches = true;
-            for (condition_index, condition) in conditions.iter().enumerate() {
-                let column_id = column_ids[condition_index];
-                let value = row.select_at(column_id).unwrap();
-                if !condition.matches(&value) {
-                    matches = false;
-                    break;
-                }
-            }
-            if matches {
-                result.push(row.clone());
-            }
-        }
-        Ok(result)
+    pub fn select_where(&self, conditions: &[Condition]) -> Result<Vec<Row>, Error> {
+        Ok(self
+            .select_where_idx(conditions)?
+            .into_iter()
+            .map(|(_, row)| row)
+            .collect())
     }
 
-    pub fn select_where_idx(
-        &self,
-        conditions: &[Condition],
-    ) -> Result<Vec<(usize, Row)>, Box<dyn Error>> {
-        // find the ids that match the columns
-        let mut column_ids = vec![];
-        let column_names = self.get_column_names();
+    pub fn select_where_idx(&self, conditions: &[Condition]) -> Result<Vec<(usize, Row)>, Error> {
+        // check that the columns used by the conditions exist
         for condition in conditions {
-            if let Some(idx) = column_names.iter().position(|x| x == condition.column) {
-                column_ids.push(idx);
-            } else {
-                return Err(Box::new(std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    format!(
-                        "Table::select_where({}): column {} not found.",
-                        self.name, condition.column,
-                    ),
-                )));
+            for column in condition.columns() {
+                if !self.column_names.contains(&column) {
+                    return Err(Error::ColumnNotFound {
+                        table: self.name.clone(),
+                        column,
+                    });
+                }
             }
         }
 
         // find the rows that match the conditions
         let mut result = vec![];
-        for (index, row) in self.select().iter().enumerate() {
+        for (index, row) in self.select().into_iter().enumerate() {
+            let value = |name: &str| {
+                let idx = self.column_names.iter().position(|x| x == name)?;
+                row.select_at(idx).ok()
+            };
             let mut matches = true;
-            for (condition_index, condition) in conditions.iter().enumerate() {
-                let column_id = column_ids[condition_index];
-                let value = row.select_at(column_id).unwrap();
-                if !condition.matches(&value) {
+            for condition in conditions {
+                if !condition.matches_row(&value)? {
                     matches = false;
                     break;
                 }
             }
             if matches {
-                result.push((index, row.clone()));
+                result.push((index, row));
             }
         }
         Ok(result)
     }
 
-    pub fn get_column_name_at(&self, idx: usize) -> String {
-        self.column_names[idx].clone()
+    pub fn get_column_name_at(&self, idx: usize) -> Result<String, Error> {
+        check_index(
+            &format!("Table {}: column", self.name),
+            idx,
+            self.column_count(),
+        )?;
+        Ok(self.column_names[idx].clone())
     }
 
     pub fn get_column_names(&self) -> Vec<String> {
@@ -559,14 +957,71 @@ impl Table {
         self.column_names.len()
     }
 
-    pub fn set_at(
-        &mut self,
-        row_idx: usize,
-        column_idx: usize,
-        value: Data,
-    ) -> Result<(), Box<dyn Error>> {
+    pub fn set_at(&mut self, row_idx: usize, column_idx: usize, value: Data) -> Result<(), Error> {
+        check_index(
+            &format!("Table {}: row", self.name),
+            row_idx,
+            self.rows.len(),
+        )?;
+        check_index(
+            &format!("Table {}: column", self.name),
+            column_idx,
+            self.column_count(),
+        )?;
+        self.check_type(column_idx, &value)?;
         self.changed = true;
-        self.rows[row_idx].set_at(column_idx, value)
+        self.rows[row_idx].set_at(column_idx, value)?;
+        self.refresh_row(row_idx);
+        Ok(())
+    }
+
+    pub fn sort_by(&mut self, column_name: &str, ascending: bool) -> Result<(), Error> {
+        let idx = self.get_column_idx_result(column_name)?;
+        let mut keyed: Vec<(Data, Row)> = std::mem::take(&mut self.rows)
+            .into_iter()
+            .map(|row| {
+                let key = self
+                    .evaluate(&row, false)
+                    .select_at(idx)
+                    .unwrap_or(Data::Empty);
+                (key, row)
+            })
+            .collect();
+        keyed.sort_by(|(a, _), (b, _)| match (a, b) {
+            (Data::Empty, Data::Empty) => Ordering::Equal,
+            (Data::Empty, _) => Ordering::Greater,
+            (_, Data::Empty) => Ordering::Less,
+            _ => {
+                let ordering = a.sort_cmp(b);
+                if ascending {
+                    ordering
+                } else {
+                    ordering.reverse()
+                }
+            }
+        });
+        self.rows = keyed.into_iter().map(|(_, row)| row).collect();
+        self.changed = true;
+        Ok(())
+    }
+
+    pub fn get_column_types(&self) -> Vec<ColumnType> {
+        self.column_types.clone()
+    }
+
+    /// applies an aggregate function to the values of a column in all rows matching the conditions
+    pub fn aggregate(
+        &self,
+        column_name: &str,
+        aggregate: Aggregate,
+        conditions: &[Condition],
+    ) -> Result<Data, Error> {
+        let column_idx = self.get_column_idx_result(column_name)?;
+        let mut values = vec![];
+        for (_, row) in self.select_where_idx(conditions)? {
+            values.push(row.select_at(column_idx)?);
+        }
+        aggregate.apply(&values)
     }
 }
 
@@ -592,8 +1047,9 @@ impl std::fmt::Display for Table {
         for column_name in &self.column_names {
             column_widths.push(column_name.len());
         }
+        let rows = self.select();
         // get the maximum width of all row values
-        for row in &self.rows {
+        for row in &rows {
             for (i, value) in row.select().iter().enumerate() {
                 let width = column_widths[i];
                 let value = value.to_string();
@@ -619,7 +1075,7 @@ impl std::fmt::Display for Table {
         result.push('\n');
 
         // write row values
-        for row in &self.rows {
+        for row in &rows {
             for (i, value) in row.select().iter().enumerate() {
                 let width = column_widths[i];
                 result.push_str(&pad(&value.to_string(), width + 1));
@@ -630,3 +1086,370 @@ impl std::fmt::Display for Table {
         write!(f, "{}", result)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decimal::Decimal;
+    use crate::join::Join;
+    use crate::time::{Date, DateTime, Duration, Time};
+
+    fn temp_file(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("rzdb-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        format!("{}/{}.csv", dir.to_str().unwrap(), name)
+    }
+
+    #[test]
+    fn test_save_load_round_trip() {
+        let s = |a: &str| Data::String(a.to_string());
+        let mut table = Table::create("round_trip");
+        table.create_column("value").unwrap();
+        table.create_column("1.0").unwrap();
+        table.create_typed_column("done", ColumnType::Bool).unwrap();
+        for datum in [
+            s("12:30"),
+            s("2024-01-01"),
+            s("[1]"),
+            s("{a}"),
+            s(" padded, \"quoted\"\nand \\ escaped "),
+            Data::Float(1.0),
+            Data::Int(1),
+            Data::Date(Date::new(2024, 1, 1).unwrap()),
+            Data::Time(Time::new(12 * 3600 + 30 * 60)),
+            Data::Join(Join::new(vec![1, 2])),
+            Data::List(vec![s("1"), Data::Int(1), Data::Empty]),
+            Data::Empty,
+        ] {
+            table
+                .insert_data(vec![datum, Data::Empty, Data::Bool(true)])
+                .unwrap();
+        }
+        let filename = temp_file("round_trip");
+        table.save(&filename).unwrap();
+        let loaded = Table::load(&filename).unwrap();
+        std::fs::remove_file(&filename).unwrap();
+
+        assert_eq!(loaded.get_name(), "round_trip");
+        assert_eq!(loaded.get_column_names(), table.get_column_names());
+        assert_eq!(loaded.get_column_types(), table.get_column_types());
+        assert_eq!(loaded.select(), table.select());
+        assert!(!loaded.is_changed());
+    }
+
+    #[test]
+    fn test_computed_columns() {
+        let date_format = DateFormat::default();
+        let mut table = Table::create("computed");
+        table.create_column("qty").unwrap();
+        table.create_column("price").unwrap();
+        table
+            .create_computed_column("total", Expr::parse("qty * price").unwrap(), true)
+            .unwrap();
+        table
+            .create_computed_column(
+                "unit, \"quoted\"",
+                Expr::parse("if(total > 10, 'big', 'small')").unwrap(),
+                false,
+            )
+            .unwrap();
+        table
+            .insert(vec!["3", "2.50", "", ""], &date_format)
+            .unwrap();
+        table.insert(vec!["1", "", "", ""], &date_format).unwrap();
+        table.insert(vec!["5", "4", "", ""], &date_format).unwrap();
+
+        let filename = temp_file("computed");
+        table.save(&filename).unwrap();
+        let content = std::fs::read_to_string(&filename).unwrap();
+        let mut loaded = Table::load(&filename).unwrap();
+        std::fs::remove_file(&filename).unwrap();
+
+        // the virtual column is stored empty, the materialized one with its values
+        assert!(content.contains("f:7.5,\n"));
+        assert_eq!(loaded.select(), table.select());
+        assert_eq!(
+            loaded.select_at(3, 0).unwrap(),
+            Data::String("small".into())
+        );
+        assert_eq!(loaded.select_at(2, 1).unwrap(), Data::Empty);
+        assert_eq!(
+            loaded.select_at(3, 1).unwrap(),
+            Data::String("small".into())
+        );
+
+        loaded.rename_column("price", "unit price").unwrap();
+        assert_eq!(
+            loaded.get_column_expression(2).unwrap().to_string(),
+            "qty * \"unit price\""
+        );
+        loaded.set_at(1, 1, Data::Int(20)).unwrap();
+        loaded.sort_by("unit, \"quoted\"", true).unwrap();
+        let rows = loaded.select();
+        assert_eq!(rows[0].select_at(2).unwrap(), Data::Int(20));
+        assert_eq!(rows[0].select_at(3).unwrap(), Data::String("big".into()));
+    }
+
+    #[test]
+    fn test_delete_column_used_by_computed() {
+        let mut table = Table::create("delete_computed");
+        table.create_column("a").unwrap();
+        table
+            .create_computed_column("b", Expr::parse("a * 2").unwrap(), true)
+            .unwrap();
+        table
+            .create_computed_column("c", Expr::parse("b + a").unwrap(), false)
+            .unwrap();
+        table
+            .insert_data(vec![Data::Int(3), Data::Empty, Data::Empty])
+            .unwrap();
+
+        match table.delete_column("a") {
+            Err(Error::ConstraintViolation {
+                column, message, ..
+            }) => {
+                assert_eq!(column, "a");
+                assert_eq!(message, "is used by computed columns: b, c");
+            }
+            other => panic!("{:?}", other),
+        }
+        assert!(table.delete_column("b").is_err());
+        assert_eq!(table.select_at(1, 0).unwrap(), Data::Int(6));
+
+        // once nothing uses them, the columns can be deleted
+        table.delete_column("c").unwrap();
+        table.delete_column("b").unwrap();
+        table.delete_column("a").unwrap();
+        assert_eq!(table.column_count(), 0);
+    }
+
+    #[test]
+    fn test_expression_conditions() {
+        let date_format = DateFormat::default();
+        let mut table = Table::create("conditions");
+        table.create_column("name").unwrap();
+        table.create_column("age").unwrap();
+        table.insert(vec!["Ann", "17"], &date_format).unwrap();
+        table.insert(vec!["bob", "42"], &date_format).unwrap();
+        table.insert(vec!["Carl", ""], &date_format).unwrap();
+
+        let names = |rows: Vec<Row>| -> Vec<String> {
+            rows.iter()
+                .map(|row| row.select_at(0).unwrap().to_string())
+                .collect()
+        };
+        let adults = Condition::parse("age >= 18 or upper(name) = 'CARL'").unwrap();
+        assert_eq!(
+            names(table.select_where(&[adults]).unwrap()),
+            ["bob", "Carl"]
+        );
+        let mixed = [
+            Condition::parse("not (age > 40)").unwrap(),
+            Condition::not_null("age"),
+        ];
+        assert_eq!(names(table.select_where(&mixed).unwrap()), ["Ann"]);
+        let unknown = Condition::parse("height > 1").unwrap();
+        assert!(table.select_where(&[unknown]).is_err());
+
+        table
+            .delete_where(&[Condition::parse("length(name) = 3").unwrap()])
+            .unwrap();
+        assert_eq!(names(table.select()), ["Carl"]);
+    }
+
+    #[test]
+    fn test_update_where() {
+        let date_format = DateFormat::default();
+        let mut table = Table::create("update");
+        table.create_column("qty").unwrap();
+        table
+            .create_typed_column("price", ColumnType::Float)
+            .unwrap();
+        table
+            .create_computed_column("total", Expr::parse("qty * price").unwrap(), true)
+            .unwrap();
+        table.insert(vec!["2", "1.5", ""], &date_format).unwrap();
+        table.insert(vec!["4", "", ""], &date_format).unwrap();
+        table.set_at(1, 0, Data::String("x".into())).unwrap();
+
+        // the values of the row before the update are used, ints fit into float columns
+        let swap = [
+            ("qty", Expr::parse("qty + 1").unwrap()),
+            ("price", Expr::parse("qty * 2").unwrap()),
+        ];
+        let first = [Condition::parse("qty = 2").unwrap()];
+        assert_eq!(table.update_where(&swap, &first).unwrap(), 1);
+        assert_eq!(table.select_at(0, 0).unwrap(), Data::Int(3));
+        assert_eq!(table.select_at(1, 0).unwrap(), Data::Float(4.0));
+        assert_eq!(table.select_at(2, 0).unwrap(), Data::Float(12.0));
+
+        // the second row can not be computed, so nothing is changed
+        let double = [("price", Expr::parse("price + qty * 2").unwrap())];
+        assert!(table.update_where(&double, &[]).is_err());
+        assert_eq!(table.select_at(1, 0).unwrap(), Data::Float(4.0));
+
+        let computed = [("total", Expr::parse("1").unwrap())];
+        assert!(table.update_where(&computed, &[]).is_err());
+        let unknown = [("qty", Expr::parse("weight").unwrap())];
+        assert!(table.update_where(&unknown, &[]).is_err());
+    }
+
+    #[test]
+    fn test_no_panics_on_random_operations() {
+        use crate::aggregate::Aggregate;
+        use crate::data::tests::random_text;
+        let date_format = DateFormat::default();
+        let mut seed = 7;
+        let next = |seed: &mut u64, n: usize| {
+            *seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (*seed >> 33) as usize % n
+        };
+        let extremes = [
+            Data::Int(i64::MAX),
+            Data::Int(i64::MIN),
+            Data::Duration(Duration::from_seconds(i64::MAX)),
+            Data::Duration(Duration::from_seconds(i64::MIN)),
+            Data::Date(Date::new(1, 1, 1).unwrap()),
+            Data::Date(Date::new(9999, 12, 31).unwrap()),
+            Data::DateTime(DateTime::parse("0001-01-01T00:00:00Z").unwrap()),
+            Data::DateTime(DateTime::parse("9999-12-31T23:59:59Z").unwrap()),
+            Data::Decimal(Decimal::new(i128::MAX, 2)),
+            Data::Decimal(Decimal::new(i128::MIN, 0)),
+        ];
+        let mut table = Table::create("random");
+        for step in 0..3000 {
+            // indices are mostly valid, sometimes just out of range
+            let width = table.column_count();
+            let a = next(&mut seed, table.row_count() + 2);
+            let b = next(&mut seed, width + 2);
+            let text = random_text(&mut seed);
+            let name = format!("c{}", next(&mut seed, 6));
+            let values: Vec<&str> = vec![text.as_str(); width];
+            let _ = match next(&mut seed, 32) {
+                0..=2 => table.insert_at(values, a, &date_format),
+                3..=4 => table.insert(values, &date_format),
+                5 => table.insert_empty_row_at(a),
+                6..=10 => table.delete_row(a),
+                11..=12 => table.insert_column_at(&name, b),
+                13 => table.delete_column(&name),
+                14..=15 => table.set_at(a, b, Data::parse(&text)),
+                16 => table.select_at(b, a).map(|_| ()),
+                17 => table.get_column_name_at(b).map(|_| ()),
+                18 => table.insert_rows_at(a, vec![Row::from(vec![Data::Int(1); b])]),
+                19 => table.sort_by(&name, b % 2 == 0),
+                20 => table.rename_column(&name, &format!("c{}", b)),
+                21 => Condition::parse(&format!("{} {}", name, text))
+                    .and_then(|condition| table.delete_where(&[condition])),
+                22 => Expr::parse(&format!("{} {}", name, text))
+                    .and_then(|expr| table.update_where(&[(&name, expr)], &[]).map(|_| ())),
+                23..=24 => Expr::parse(&text)
+                    .and_then(|expr| table.create_computed_column(&name, expr, b % 2 == 0)),
+                25 => table.select_columns(&[&name]).map(|_| ()),
+                26 => {
+                    let aggregate = [Aggregate::Sum, Aggregate::Avg, Aggregate::Max][b % 3];
+                    table.aggregate(&name, aggregate, &[]).map(|_| ())
+                }
+                27 => table.insert_columns_at(b, &Table::create("empty")),
+                // values at the limits of their types, and arithmetic on them
+                28 => table.set_at(a, b, extremes[next(&mut seed, extremes.len())].clone()),
+                29 => Expr::parse(&format!("{} {} c{}", name, ["+", "-", "*"][b % 3], a % 6))
+                    .or_else(|_| Expr::parse(&format!("abs({})", name)))
+                    .and_then(|expr| table.update_where(&[(&name, expr)], &[]).map(|_| ())),
+                _ => {
+                    let filename = temp_file("random");
+                    std::fs::write(&filename, format!("{}\n{}\n{}", name, text, text)).unwrap();
+                    let _ = Table::load(&filename);
+                    table.save(&filename).unwrap();
+                    let loaded = Table::load(&filename);
+                    std::fs::remove_file(&filename).unwrap();
+                    let loaded = loaded.unwrap_or_else(|e| panic!("step {}: {:?}", step, e));
+                    assert_eq!(loaded.get_column_names(), table.get_column_names());
+                    assert_eq!(loaded.select(), table.select(), "step {}", step);
+                    Ok(())
+                }
+            };
+            let _ = table.to_string();
+        }
+    }
+
+    #[test]
+    fn test_load_untyped() {
+        let filename = temp_file("untyped");
+        std::fs::write(&filename, "name,value\nnoon,12:30\ncount,3\n").unwrap();
+        let loaded = Table::load(&filename).unwrap();
+        std::fs::remove_file(&filename).unwrap();
+
+        assert_eq!(loaded.get_column_names(), vec!["name", "value"]);
+        assert_eq!(
+            loaded.select_at(1, 0).unwrap(),
+            Data::Time(Time::new(45000))
+        );
+        assert_eq!(loaded.select_at(1, 1).unwrap(), Data::Int(3));
+        // old files are rewritten in the typed format on the next save
+        assert!(loaded.is_changed());
+    }
+
+    #[test]
+    fn test_load_multiline_values() {
+        let s = |a: &str| Data::String(a.to_string());
+        let filename = temp_file("multiline");
+        std::fs::write(
+            &filename,
+            "\u{feff}#rzdb:2\r\nname,\"a \"\"note\"\"\"\r\nstring,any\r\n\
+             s:first,\"s:two\r\nlines, \\n not escaped\"\r\n\
+             \r\n\
+             s:last,\"l:{\"\"s:x\ny\"\",i:1}\"",
+        )
+        .unwrap();
+        let mut loaded = Table::load(&filename).unwrap();
+
+        assert_eq!(loaded.get_column_names(), vec!["name", "a \"note\""]);
+        assert_eq!(
+            loaded.select(),
+            vec![
+                Row::from(vec![s("first"), s("two\r\nlines, \\n not escaped")]),
+                Row::from(vec![Data::Empty, Data::Empty]),
+                Row::from(vec![s("last"), Data::List(vec![s("x\ny"), Data::Int(1)])]),
+            ]
+        );
+        assert!(!loaded.is_changed());
+
+        // line breaks are written as they are
+        loaded.save(&filename).unwrap();
+        let content = std::fs::read_to_string(&filename).unwrap();
+        let reloaded = Table::load(&filename).unwrap();
+        std::fs::remove_file(&filename).unwrap();
+        assert!(content.contains("\"s:two\r\nlines, \\n not escaped\""));
+        assert_eq!(reloaded.select(), loaded.select());
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let filename = temp_file("load_errors");
+        let position = |content: &str| {
+            std::fs::write(&filename, content).unwrap();
+            match Table::load(&filename) {
+                Err(Error::ParseError { line, column, .. }) => (line, column),
+                other => panic!("{:?}", other.map(|table| table.to_string())),
+            }
+        };
+        assert_eq!(
+            position("#rzdb:2\na,b\nany,any\ns:1,\"s:2\n\"x\n"),
+            (Some(5), Some(2))
+        );
+        assert_eq!(
+            position("#rzdb:2\na,b\nany,any\ns:1,s:2\n\n\"s:3,s:4\n"),
+            (Some(6), Some(1))
+        );
+        assert_eq!(
+            position("#rzdb:2\na,b\nany,any\n\"s:1\ns:2\",i:x\n"),
+            (Some(4), None)
+        );
+        assert_eq!(position("#rzdb:2\na\nany\ns:1,s:2\n"), (Some(4), None));
+        assert_eq!(position("#rzdb:3\na\n"), (Some(1), None));
+        assert_eq!(position("#rzdb:5\na\n"), (Some(1), None));
+        std::fs::remove_file(&filename).unwrap();
+    }
+}
@@ -1,17 +1,21 @@
-use std::error::Error;
+use crate::error::Error;
+use std::sync::OnceLock;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+mod tz;
+pub use tz::{PosixRule, ZoneInfo};
+
 #[derive(Clone, PartialEq)]
 pub struct Timestamp {
     timestamp: u64,
 }
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, PartialEq, PartialOrd, Debug)]
 pub struct Time {
     seconds: u32,
 }
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, PartialEq, PartialOrd, Debug)]
 pub struct Date {
     year: u16,
     month: u8,
@@ -22,146 +26,628 @@ pub struct DateDays {
     days: u32,
 }
 
-impl Date {
-    pub fn new(year: u16, month: u8, day: u8) -> Date {
-        Date { year, month, day }
-    }
-    pub fn parse(s: &str) -> Result<Date, Box<dyn Error>> {
-        if let Ok(date) = Date::parse_iso(s) {
-            Ok(date)
-        } else if let Ok(date) = Date::parse_us(s) {
-            Ok(date)
-        } else if let Ok(date) = Date::parse_eu(s) {
-            Ok(date)
-        } else {
-            Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!("Not a date: {}", s),
-            )))
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+/// Error of date parsing: the text does not look like a date at all, or it looks like a
+/// date that does not exist in the calendar, e.g. `2023-02-29`.
+#[derive(Clone, PartialEq, Debug)]
+pub enum DateError {
+    NotADate(String),
+    InvalidDate(String),
+}
+
+impl std::fmt::Display for DateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DateError::NotADate(s) => write!(f, "Not a date: {}", s),
+            DateError::InvalidDate(s) => write!(f, "Invalid date: {}", s),
         }
     }
-    pub fn parse_iso(s: &str) -> Result<Date, Box<dyn Error>> {
-        let mut parts = s.split('-');
-        let year = parts.next().ok_or("Not a date")?.parse::<u16>()?;
-        let month = parts.next().ok_or("Not a date")?.parse::<u8>()?;
-        let day = parts.next().ok_or("Not a date")?.parse::<u8>()?;
+}
+
+impl std::error::Error for DateError {}
+
+/// Iterator over the days from a start date up to, but not including, an end date.
+pub struct DateRange {
+    next: i64,
+    end: i64,
+}
+
+/// How dates are read from and written as text. The input formats are tried in order, the
+/// output format is used to display dates. Format strings know the fields:
+/// - `%Y` year with four digits
+/// - `%y` year with two or four digits, two digit years below the pivot year are in the
+///   2000s, others in the 1900s; an empty year is the current year
+/// - `%m`, `%d` month and day, one or two digits; written with two digits, `%-m` and `%-d`
+///   are written without padding
+/// - `%b`, `%B` month name such as `Jan` or `January`, both are read by either field
+/// - `%%` a percent sign
+#[derive(Clone, PartialEq, Debug)]
+pub struct DateFormat {
+    inputs: Vec<Vec<FormatItem>>,
+    output: Vec<FormatItem>,
+    pivot_year: u16,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum FormatItem {
+    Literal(char),
+    Year,
+    ShortYear,
+    Month { pad: bool },
+    Day { pad: bool },
+    MonthName,
+    FullMonthName,
+}
+
+const MONTH_NAMES: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+/// A signed length of time in seconds, e.g. worked hours. Unlike Time it is not limited to
+/// a day.
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug)]
+pub struct Duration {
+    seconds: i64,
+}
+
+/// A point in time, stored as seconds since 1970-01-01 00:00:00 UTC.
+#[derive(Clone, PartialEq, PartialOrd, Debug)]
+pub struct DateTime {
+    seconds: i64,
+}
+
+/// The zone used to display a DateTime.
+#[derive(Clone, PartialEq, Debug)]
+pub enum TimeZone {
+    Utc,
+    /// a fixed offset to UTC in seconds, e.g. 3600 for +01:00
+    Fixed(i32),
+    /// a zone with daylight saving time rules, read from a TZif file or a POSIX TZ string
+    Zone(Box<ZoneInfo>),
+}
+
+const SECONDS_PER_DAY: i64 = 60 * 60 * 24;
+
+/// days since 1970-01-01 of a date in the proleptic gregorian calendar
+fn days_from_civil(year: i64, month: u8, day: u8) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// (year, month, day) of a day counted from 1970-01-01
+fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u8;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+impl Date {
+    /// creates a date after checking that it exists in the calendar
+    /// ```
+    /// use rzdb::time::Date;
+    /// assert_eq!(Date::new(2024, 2, 29).unwrap().to_string(), "2024-02-29");
+    /// assert!(Date::new(2023, 2, 29).is_err());
+    /// assert!(Date::new(2024, 13, 1).is_err());
+    /// ```
+    pub fn new(year: u16, month: u8, day: u8) -> Result<Date, DateError> {
         if Date::validate_date(year, month, day) {
             Ok(Date { year, month, day })
         } else {
-            Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!("Not a date: {}", s),
+            Err(DateError::InvalidDate(format!(
+                "{:04}-{:02}-{:02}",
+                year, month, day
             )))
         }
     }
+    pub fn year(&self) -> u16 {
+        self.year
+    }
+    pub fn month(&self) -> u8 {
+        self.month
+    }
+    pub fn day(&self) -> u8 {
+        self.day
+    }
+    /// Parses a date as ISO (`2024-01-31`), US (`1/31/2024`) or European (`31.1.2024`,
+    /// `31.1.24` or `31.1.` for the current year) date.
+    /// ```
+    /// use rzdb::time::{Date, DateError};
+    /// assert_eq!(Date::parse("1969-07-20").unwrap(), Date::new(1969, 7, 20).unwrap());
+    /// assert!(matches!(Date::parse("2023-02-29"), Err(DateError::InvalidDate(_))));
+    /// assert!(matches!(Date::parse("12:30"), Err(DateError::NotADate(_))));
+    /// ```
+    pub fn parse(s: &str) -> Result<Date, DateError> {
+        let mut error = DateError::NotADate(s.to_string());
+        for parse in [Date::parse_iso, Date::parse_us, Date::parse_eu] {
+            match parse(s) {
+                Ok(date) => return Ok(date),
+                Err(e @ DateError::InvalidDate(_)) => error = e,
+                Err(DateError::NotADate(_)) => {}
+            }
+        }
+        Err(error)
+    }
 
-    pub fn parse_us(s: &str) -> Result<Date, Box<dyn Error>> {
-        let mut parts = s.split('/');
-        let month = parts.next().ok_or("Not a date")?.parse::<u8>()?;
-        let day = parts.next().ok_or("Not a date")?.parse::<u8>()?;
-        let year = parts.next().ok_or("Not a date")?.parse::<u16>()?;
-        if Date::validate_date(year, month, day) {
-            Ok(Date { year, month, day })
+    /// splits a date into three parts of digits, the last part may be empty if allowed
+    fn split_parts(s: &str, separator: char, empty_last: bool) -> Result<[&str; 3], DateError> {
+        let parts: Vec<&str> = s.split(separator).collect();
+        let valid = parts.len() == 3
+            && parts.iter().enumerate().all(|(idx, part)| {
+                part.chars().all(|c| c.is_ascii_digit())
+                    && (!part.is_empty() || (idx == 2 && empty_last))
+                    && part.len() <= 4
+            });
+        if valid {
+            Ok([parts[0], parts[1], parts[2]])
         } else {
-            Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!("Not a date: {}", s),
-            )))
+            Err(DateError::NotADate(s.to_string()))
         }
     }
-    pub fn parse_eu(s: &str) -> Result<Date, Box<dyn Error>> {
-        let mut parts = s.split('.');
-        let day = parts.next().ok_or("Not a date")?.parse::<u8>()?;
-        let month = parts.next().ok_or("Not a date")?.parse::<u8>()?;
-        let year_as_str = parts.next().ok_or("Not a date")?;
-        let mut year = if year_as_str.is_empty() {
-            Date::today().year
-        } else {
-            year_as_str.parse::<u16>()?
-        };
 
-        if year < 40 {
-            year += 2000;
-        } else if year < 100 {
-            year += 1900;
+    /// builds a date from already split parts, checking the calendar
+    fn from_parts(s: &str, year: &str, month: &str, day: &str) -> Result<Date, DateError> {
+        // the parts only contain up to 4 digits, so they always fit
+        let year = year.parse::<u16>().unwrap_or(0);
+        let month = month.parse::<u8>().unwrap_or(0);
+        let day = day.parse::<u8>().unwrap_or(0);
+        Date::new(year, month, day).map_err(|_| DateError::InvalidDate(s.to_string()))
+    }
+
+    pub fn parse_iso(s: &str) -> Result<Date, DateError> {
+        let [year, month, day] = Date::split_parts(s, '-', false)?;
+        if year.len() != 4 || month.len() > 2 || day.len() > 2 {
+            return Err(DateError::NotADate(s.to_string()));
         }
-        if Date::validate_date(year, month, day) {
-            Ok(Date { year, month, day })
-        } else {
-            Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!("Not a date: {}", s),
-            )))
+        Date::from_parts(s, year, month, day)
+    }
+
+    pub fn parse_us(s: &str) -> Result<Date, DateError> {
+        let [month, day, year] = Date::split_parts(s, '/', false)?;
+        if year.len() != 4 || month.len() > 2 || day.len() > 2 {
+            return Err(DateError::NotADate(s.to_string()));
         }
+        Date::from_parts(s, year, month, day)
     }
+
+    pub fn parse_eu(s: &str) -> Result<Date, DateError> {
+        let [day, month, year] = Date::split_parts(s, '.', true)?;
+        if month.len() > 2 || day.len() > 2 || year.len() == 3 {
+            return Err(DateError::NotADate(s.to_string()));
+        }
+        let year = match year.len() {
+            0 => Date::today().year,
+            // two digit years: 00 to 39 are 20xx, 40 to 99 are 19xx
+            1 | 2 => {
+                let year = year.parse::<u16>().unwrap_or(0);
+                if year < 40 {
+                    year + 2000
+                } else {
+                    year + 1900
+                }
+            }
+            _ => year.parse::<u16>().unwrap_or(0),
+        };
+        Date::from_parts(s, &year.to_string(), month, day)
+    }
+
+    /// years 1 to 9999 of the gregorian calendar
     fn validate_date(year: u16, month: u8, day: u8) -> bool {
-        //year >= 1970 && year < 2999 && month > 0 && month < 13 && day > 0 && day < 32
-        (1970..3000).contains(&year) && (0..13).contains(&month) && (0..32).contains(&day)
+        (1..=9999).contains(&year)
+            && (1..=12).contains(&month)
+            && day >= 1
+            && day <= Date::days_in_month(year, month)
     }
+
+    /// the current date in the system's zone
     pub fn today() -> Date {
-        let now = SystemTime::now();
-        let since_the_epoch = now.duration_since(UNIX_EPOCH).unwrap();
-        let seconds = since_the_epoch.as_secs();
-        let days = seconds / (60 * 60 * 24);
-        let mut remaining_days = days;
-        let mut year = 1970;
-        let mut month = 1;
-        let mut day = 1;
-
-        while remaining_days > 0 {
-            let days_in_year = if Timestamp::is_leap_year(year) {
-                366
-            } else {
-                365
+        DateTime::now().to_date_time(local_zone()).0
+    }
+
+    pub fn is_leap_year(year: u16) -> bool {
+        Timestamp::is_leap_year(year as u64)
+    }
+
+    pub fn days_in_month(year: u16, month: u8) -> u8 {
+        match month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if Date::is_leap_year(year) => 29,
+            2 => 28,
+            _ => 0,
+        }
+    }
+
+    /// number of days since 1970-01-01, negative for earlier dates
+    pub fn days_since_epoch(&self) -> i64 {
+        days_from_civil(self.year as i64, self.month, self.day)
+    }
+
+    /// None if the day is not between the years 1 and 9999
+    pub fn from_days_since_epoch(days: i64) -> Option<Date> {
+        let range = days_from_civil(1, 1, 1)..=days_from_civil(9999, 12, 31);
+        range.contains(&days).then(|| Date::from_days(days))
+    }
+
+    /// a date from a day that is known to be between the years 1 and 9999
+    fn from_days(days: i64) -> Date {
+        let (year, month, day) = civil_from_days(days);
+        Date {
+            year: year as u16,
+            month,
+            day,
+        }
+    }
+
+    /// None if the result is not between the years 1 and 9999
+    /// ```
+    /// use rzdb::time::Date;
+    /// let date = |y, m, d| Date::new(y, m, d).unwrap();
+    /// assert_eq!(date(2024, 2, 28).checked_add_days(2), Some(date(2024, 3, 1)));
+    /// assert_eq!(date(2024, 1, 1).checked_add_days(-1), Some(date(2023, 12, 31)));
+    /// assert_eq!(date(1, 1, 1).checked_add_days(-1), None);
+    /// assert_eq!(date(9999, 12, 31).checked_add_days(1), None);
+    /// assert_eq!(date(2024, 1, 1).checked_add_days(i64::MAX), None);
+    /// ```
+    pub fn checked_add_days(&self, days: i64) -> Option<Date> {
+        Date::from_days_since_epoch(self.days_since_epoch().checked_add(days)?)
+    }
+
+    /// adds calendar months; the day is clamped to the length of the resulting month.
+    /// None if the result is not between the years 1 and 9999
+    /// ```
+    /// use rzdb::time::Date;
+    /// let date = |y, m, d| Date::new(y, m, d).unwrap();
+    /// assert_eq!(date(2024, 1, 31).checked_add_months(1), Some(date(2024, 2, 29)));
+    /// assert_eq!(date(2024, 11, 15).checked_add_months(3), Some(date(2025, 2, 15)));
+    /// assert_eq!(date(2024, 3, 31).checked_add_months(-13), Some(date(2023, 2, 28)));
+    /// assert_eq!(date(9999, 12, 1).checked_add_months(1), None);
+    /// assert_eq!(date(2024, 1, 1).checked_add_years(i64::MAX), None);
+    /// ```
+    pub fn checked_add_months(&self, months: i64) -> Option<Date> {
+        let total = (self.year as i64 * 12 + (self.month as i64 - 1)).checked_add(months)?;
+        let year = u16::try_from(total.div_euclid(12)).ok()?;
+        let month = (total.rem_euclid(12) + 1) as u8;
+        let day = self.day.min(Date::days_in_month(year, month));
+        Date::new(year, month, day).ok()
+    }
+
+    pub fn checked_add_years(&self, years: i64) -> Option<Date> {
+        self.checked_add_months(years.checked_mul(12)?)
+    }
+
+    /// number of days from `other` to this date, negative if `other` is later
+    /// ```
+    /// use rzdb::time::Date;
+    /// assert_eq!(Date::new(2024, 3, 1).unwrap().days_since(&Date::new(2024, 2, 1).unwrap()), 29);
+    /// ```
+    pub fn days_since(&self, other: &Date) -> i64 {
+        self.days_since_epoch() - other.days_since_epoch()
+    }
+
+    /// adds the whole days of a duration, a part of a day moves a date back to the day
+    /// before; None if the result is not between the years 1 and 9999
+    /// ```
+    /// use rzdb::time::{Date, Duration};
+    /// let date = |y, m, d| Date::new(y, m, d).unwrap();
+    /// let day = date(2024, 3, 1);
+    /// assert_eq!(day.checked_add_duration(&Duration::parse("1d 12h").unwrap()), Some(date(2024, 3, 2)));
+    /// assert_eq!(day.checked_add_duration(&Duration::parse("-2h").unwrap()), Some(date(2024, 2, 29)));
+    /// ```
+    pub fn checked_add_duration(&self, duration: &Duration) -> Option<Date> {
+        self.checked_add_days(duration.seconds().div_euclid(SECONDS_PER_DAY))
+    }
+
+    pub fn weekday(&self) -> Weekday {
+        // 1970-01-01 was a thursday
+        match (self.days_since_epoch() + 3).rem_euclid(7) {
+            0 => Weekday::Monday,
+            1 => Weekday::Tuesday,
+            2 => Weekday::Wednesday,
+            3 => Weekday::Thursday,
+            4 => Weekday::Friday,
+            5 => Weekday::Saturday,
+            _ => Weekday::Sunday,
+        }
+    }
+
+    /// day of the year, starting with 1 for January 1st
+    pub fn day_of_year(&self) -> u16 {
+        (self.days_since_epoch() - days_from_civil(self.year as i64, 1, 1) + 1) as u16
+    }
+
+    /// ISO 8601 week as (year, week); the week belongs to the year of its thursday
+    /// ```
+    /// use rzdb::time::Date;
+    /// assert_eq!(Date::new(2024, 12, 30).unwrap().iso_week(), (2025, 1));
+    /// assert_eq!(Date::new(2021, 1, 3).unwrap().iso_week(), (2020, 53));
+    /// assert_eq!(Date::new(2024, 6, 15).unwrap().iso_week(), (2024, 24));
+    /// assert_eq!(Date::new(9999, 12, 31).unwrap().iso_week(), (9999, 52));
+    /// assert_eq!(Date::new(1, 1, 1).unwrap().iso_week(), (1, 1));
+    /// ```
+    pub fn iso_week(&self) -> (u16, u8) {
+        // counted in days, so that no date needs to be built for the thursday
+        let thursday = self.days_since_epoch() + 3 - self.weekday().number_from_monday() as i64 + 1;
+        let (year, _, _) = civil_from_days(thursday);
+        let week = (thursday - days_from_civil(year, 1, 1)) / 7 + 1;
+        (year as u16, week as u8)
+    }
+
+    pub fn first_of_month(&self) -> Date {
+        Date { day: 1, ..*self }
+    }
+
+    pub fn last_of_month(&self) -> Date {
+        Date {
+            day: Date::days_in_month(self.year, self.month),
+            ..*self
+        }
+    }
+
+    /// the days from this date up to, but not including, `end`
+    /// ```
+    /// use rzdb::time::Date;
+    /// let days: Vec<String> = Date::new(2024, 2, 28).unwrap()
+    ///     .range_to(&Date::new(2024, 3, 2).unwrap())
+    ///     .map(|d| d.to_string())
+    ///     .collect();
+    /// assert_eq!(days, vec!["2024-02-28", "2024-02-29", "2024-03-01"]);
+    /// ```
+    pub fn range_to(&self, end: &Date) -> DateRange {
+        DateRange {
+            next: self.days_since_epoch(),
+            end: end.days_since_epoch(),
+        }
+    }
+}
+
+impl Weekday {
+    /// 1 for monday up to 7 for sunday
+    pub fn number_from_monday(&self) -> u8 {
+        *self as u8 + 1
+    }
+}
+
+impl Iterator for DateRange {
+    type Item = Date;
+
+    fn next(&mut self) -> Option<Date> {
+        if self.next >= self.end {
+            return None;
+        }
+        self.next += 1;
+        Some(Date::from_days(self.next - 1))
+    }
+}
+
+impl std::fmt::Display for Date {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl DateFormat {
+    /// Creates a date format from format strings such as `%d.%m.%Y`, with a pivot year of 40.
+    /// ```
+    /// use rzdb::time::{Date, DateFormat};
+    /// let format = DateFormat::new(&["%d.%m.%y", "%Y-%m-%d"], "%-d. %B %Y").unwrap();
+    /// assert_eq!(format.parse("3.4.24").unwrap(), Date::new(2024, 4, 3).unwrap());
+    /// assert_eq!(format.parse("2024-04-03").unwrap(), Date::new(2024, 4, 3).unwrap());
+    /// assert!(format.parse("4/3/2024").is_err());
+    /// assert_eq!(format.format(&Date::new(2024, 4, 3).unwrap()), "3. April 2024");
+    /// assert!(DateFormat::new(&["%d.%m.%q"], "%Y").is_err());
+    /// ```
+    pub fn new(inputs: &[&str], output: &str) -> Result<DateFormat, Error> {
+        let mut parsed_inputs = vec![];
+        for input in inputs {
+            parsed_inputs.push(DateFormat::parse_format(input)?);
+        }
+        Ok(DateFormat {
+            inputs: parsed_inputs,
+            output: DateFormat::parse_format(output)?,
+            pivot_year: 40,
+        })
+    }
+
+    /// two digit years below the pivot year are read as 20xx, others as 19xx
+    /// ```
+    /// use rzdb::time::{Date, DateFormat};
+    /// let format = DateFormat::new(&["%m/%d/%y"], "%m/%d/%Y").unwrap().with_pivot_year(70);
+    /// assert_eq!(format.parse("7/20/69").unwrap(), Date::new(2069, 7, 20).unwrap());
+    /// assert_eq!(format.parse("7/20/70").unwrap(), Date::new(1970, 7, 20).unwrap());
+    /// assert_eq!(format.format(&Date::new(1970, 7, 20).unwrap()), "07/20/1970");
+    /// ```
+    pub fn with_pivot_year(mut self, pivot_year: u16) -> DateFormat {
+        self.pivot_year = pivot_year.min(100);
+        self
+    }
+
+    fn parse_format(format: &str) -> Result<Vec<FormatItem>, Error> {
+        let gen_error = || Error::parse(format!("Invalid date format: {}", format));
+        let mut items = vec![];
+        let mut chars = format.chars();
+        while let Some(ch) = chars.next() {
+            if ch != '%' {
+                items.push(FormatItem::Literal(ch));
+                continue;
+            }
+            let item = match chars.next() {
+                Some('%') => FormatItem::Literal('%'),
+                Some('Y') => FormatItem::Year,
+                Some('y') => FormatItem::ShortYear,
+                Some('m') => FormatItem::Month { pad: true },
+                Some('d') => FormatItem::Day { pad: true },
+                Some('b') => FormatItem::MonthName,
+                Some('B') => FormatItem::FullMonthName,
+                Some('-') => match chars.next() {
+                    Some('m') => FormatItem::Month { pad: false },
+                    Some('d') => FormatItem::Day { pad: false },
+                    _ => return Err(gen_error()),
+                },
+                _ => return Err(gen_error()),
             };
-            if remaining_days >= days_in_year {
-                remaining_days -= days_in_year;
-                year += 1;
-            } else {
-                break;
+            items.push(item);
+        }
+        Ok(items)
+    }
+
+    /// Parses a date with the first matching input format. If a format matches, but the
+    /// date does not exist, the error is `DateError::InvalidDate`.
+    pub fn parse(&self, s: &str) -> Result<Date, DateError> {
+        let mut error = DateError::NotADate(s.to_string());
+        for items in &self.inputs {
+            match self.parse_items(s, items) {
+                Ok(date) => return Ok(date),
+                Err(e @ DateError::InvalidDate(_)) => error = e,
+                Err(DateError::NotADate(_)) => {}
             }
         }
+        Err(error)
+    }
 
-        while remaining_days > 0 {
-            let days_in_month = match month {
-                1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
-                4 | 6 | 9 | 11 => 30,
-                2 => {
-                    if Timestamp::is_leap_year(year) {
-                        29
+    fn parse_items(&self, s: &str, items: &[FormatItem]) -> Result<Date, DateError> {
+        let not_a_date = || DateError::NotADate(s.to_string());
+        // takes up to max digits from the start of rest
+        let digits = |rest: &str, max: usize| -> usize {
+            rest.chars()
+                .take(max)
+                .take_while(|c| c.is_ascii_digit())
+                .count()
+        };
+        let (mut year, mut month, mut day) = (None, None, None);
+        let mut rest = s;
+        for item in items {
+            match item {
+                FormatItem::Literal(ch) => {
+                    rest = rest.strip_prefix(*ch).ok_or_else(not_a_date)?;
+                }
+                FormatItem::Year | FormatItem::ShortYear => {
+                    let len = digits(rest, 4);
+                    year = Some(match (item, len) {
+                        (_, 4) => rest[..4].parse::<u16>().map_err(|_| not_a_date())?,
+                        (FormatItem::ShortYear, 0) => Date::today().year,
+                        (FormatItem::ShortYear, 1 | 2) => {
+                            let year = rest[..len].parse::<u16>().map_err(|_| not_a_date())?;
+                            if year < self.pivot_year {
+                                year + 2000
+                            } else {
+                                year + 1900
+                            }
+                        }
+                        _ => return Err(not_a_date()),
+                    });
+                    rest = &rest[len..];
+                }
+                FormatItem::Month { .. } | FormatItem::Day { .. } => {
+                    let len = digits(rest, 2);
+                    if len == 0 {
+                        return Err(not_a_date());
+                    }
+                    let value = rest[..len].parse::<u8>().map_err(|_| not_a_date())?;
+                    if let FormatItem::Month { .. } = item {
+                        month = Some(value);
                     } else {
-                        28
+                        day = Some(value);
                     }
+                    rest = &rest[len..];
                 }
-                _ => panic!("invalid month"),
-            };
-            if remaining_days >= days_in_month {
-                remaining_days -= days_in_month;
-                month += 1;
-            } else {
-                break;
+                FormatItem::MonthName | FormatItem::FullMonthName => {
+                    let len = rest
+                        .chars()
+                        .take_while(|c| c.is_alphabetic())
+                        .map(|c| c.len_utf8())
+                        .sum::<usize>();
+                    let name = rest[..len].to_lowercase();
+                    let idx = MONTH_NAMES
+                        .iter()
+                        .position(|m| {
+                            let m = m.to_lowercase();
+                            m == name || (name.len() == 3 && m.starts_with(&name))
+                        })
+                        .ok_or_else(not_a_date)?;
+                    month = Some(idx as u8 + 1);
+                    rest = &rest[len..];
+                }
+            }
+        }
+        match (rest.is_empty(), year, month, day) {
+            (true, Some(year), Some(month), Some(day)) => {
+                Date::new(year, month, day).map_err(|_| DateError::InvalidDate(s.to_string()))
             }
+            _ => Err(not_a_date()),
         }
+    }
 
-        day += remaining_days;
-        Date {
-            year: year as u16,
-            month: month as u8,
-            day: day as u8,
+    /// formats a date with the output format
+    pub fn format(&self, date: &Date) -> String {
+        let mut out = String::new();
+        for item in &self.output {
+            match item {
+                FormatItem::Literal(ch) => out.push(*ch),
+                FormatItem::Year => out.push_str(&format!("{:04}", date.year)),
+                FormatItem::ShortYear => out.push_str(&format!("{:02}", date.year % 100)),
+                FormatItem::Month { pad: true } => out.push_str(&format!("{:02}", date.month)),
+                FormatItem::Month { pad: false } => out.push_str(&date.month.to_string()),
+                FormatItem::Day { pad: true } => out.push_str(&format!("{:02}", date.day)),
+                FormatItem::Day { pad: false } => out.push_str(&date.day.to_string()),
+                FormatItem::MonthName => {
+                    out.push_str(&MONTH_NAMES[date.month as usize - 1][..3]);
+                }
+                FormatItem::FullMonthName => {
+                    out.push_str(MONTH_NAMES[date.month as usize - 1]);
+                }
+            }
         }
+        out
     }
 }
 
-impl std::fmt::Display for Date {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+/// The formats of `Date::parse()`: ISO, US with a four digit year and European dates, shown
+/// as ISO dates.
+impl Default for DateFormat {
+    fn default() -> DateFormat {
+        DateFormat::new(&["%Y-%m-%d", "%m/%d/%Y", "%d.%m.%y"], "%Y-%m-%d").unwrap()
     }
 }
 
 impl DateDays {
     pub fn today() -> DateDays {
         let now = SystemTime::now();
-        let since_the_epoch = now.duration_since(UNIX_EPOCH).unwrap();
+        let since_the_epoch = now.duration_since(UNIX_EPOCH).unwrap_or_default();
         let seconds = since_the_epoch.as_secs();
         let days = seconds / (60 * 60 * 24);
         DateDays { days: days as u32 }
@@ -170,48 +656,10 @@ impl DateDays {
 
 impl std::fmt::Display for DateDays {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let mut remaining_days = self.days;
-        let mut year = 1970;
-        let mut month = 1;
-        let mut day = 1;
-
-        while remaining_days > 0 {
-            let days_in_year = if Timestamp::is_leap_year(year) {
-                366
-            } else {
-                365
-            };
-            if remaining_days >= days_in_year {
-                remaining_days -= days_in_year;
-                year += 1;
-            } else {
-                break;
-            }
-        }
-
-        while remaining_days > 0 {
-            let days_in_month = match month {
-                1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
-                4 | 6 | 9 | 11 => 30,
-                2 => {
-                    if Timestamp::is_leap_year(year) {
-                        29
-                    } else {
-                        28
-                    }
-                }
-                _ => panic!("invalid month"),
-            };
-            if remaining_days >= days_in_month {
-                remaining_days -= days_in_month;
-                month += 1;
-            } else {
-                break;
-            }
+        match Date::from_days_since_epoch(self.days as i64) {
+            Some(date) => write!(f, "{}", date),
+            None => write!(f, "{} days", self.days),
         }
-
-        day += remaining_days;
-        write!(f, "{}-{:02}-{:02}", year, month, day)
     }
 }
 
@@ -220,40 +668,61 @@ impl Time {
         Time { seconds }
     }
 
+    /// seconds since midnight
+    pub fn seconds(&self) -> u32 {
+        self.seconds
+    }
+
     pub fn now() -> Time {
         Timestamp::now().as_time()
     }
 
-    pub fn parse(s: &str) -> Result<Time, Box<dyn Error>> {
+    pub fn parse(s: &str) -> Result<Time, Error> {
+        let not_a_time = || Error::parse(format!("Not a time: {}", s));
         let mut parts = s.split(':');
-        let hours = parts.next().ok_or("Not a time")?.parse::<u8>()?;
-        let minutes = parts.next().ok_or("Not a time")?.parse::<u8>()?;
-        let seconds = if let Some(part) = parts.next() {
-            part.parse::<u8>()?
-        } else {
-            0
+        let mut part = || -> Result<Option<u8>, Error> {
+            parts
+                .next()
+                .map(|part| part.parse::<u8>().map_err(|_| not_a_time()))
+                .transpose()
         };
-        if parts.next().is_some() {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!("Not a time: {}", s),
-            )));
+        let hours = part()?.ok_or_else(not_a_time)?;
+        let minutes = part()?.ok_or_else(not_a_time)?;
+        let seconds = part()?.unwrap_or(0);
+        if part()?.is_some() {
+            return Err(not_a_time());
         }
         if Time::validate_time(hours, minutes, seconds) {
             Ok(Time {
                 seconds: hours as u32 * 3600 + minutes as u32 * 60 + seconds as u32,
             })
         } else {
-            Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!("Not a time: {}", s),
-            )))
+            Err(Error::parse(format!("Not a time: {}", s)))
         }
     }
 
     fn validate_time(hours: u8, minutes: u8, seconds: u8) -> bool {
         hours < 24 && minutes < 60 && seconds < 60
     }
+
+    /// adds a duration, wrapping around midnight
+    /// ```
+    /// use rzdb::time::{Duration, Time};
+    /// let time = Time::parse("22:00").unwrap();
+    /// assert_eq!(time.add_duration(&Duration::parse("3:30").unwrap()), Time::parse("1:30").unwrap());
+    /// assert_eq!(time.add_duration(&Duration::parse("-1d 1h").unwrap()), Time::parse("21:00").unwrap());
+    /// ```
+    pub fn add_duration(&self, duration: &Duration) -> Time {
+        Time {
+            seconds: (self.seconds as i64 + duration.seconds.rem_euclid(SECONDS_PER_DAY))
+                .rem_euclid(SECONDS_PER_DAY) as u32,
+        }
+    }
+
+    /// the duration from another time of the same day to this time
+    pub fn duration_since(&self, other: &Time) -> Duration {
+        Duration::from_seconds(self.seconds as i64 - other.seconds as i64)
+    }
 }
 
 impl std::fmt::Display for Time {
@@ -265,39 +734,546 @@ impl std::fmt::Display for Time {
     }
 }
 
+impl Duration {
+    pub fn from_seconds(seconds: i64) -> Duration {
+        Duration { seconds }
+    }
+
+    pub fn seconds(&self) -> i64 {
+        self.seconds
+    }
+
+    /// Parses `hh:mm[:ss]` with any number of hours, or days, hours, minutes and seconds
+    /// such as `1d 2h 30m` or `90s`; both forms may start with a minus sign.
+    /// ```
+    /// use rzdb::time::Duration;
+    /// assert_eq!(Duration::parse("37:30").unwrap().seconds(), 37 * 3600 + 30 * 60);
+    /// assert_eq!(Duration::parse("-0:30:15").unwrap().seconds(), -(30 * 60 + 15));
+    /// assert_eq!(Duration::parse("1d 2h 30m").unwrap().seconds(), 95400);
+    /// assert_eq!(Duration::parse("-2h15m").unwrap().seconds(), -8100);
+    /// assert!(Duration::parse("12:60").is_err());
+    /// assert!(Duration::parse("30m 2h").is_err());
+    /// assert!(Duration::parse("12").is_err());
+    /// assert_eq!(Duration::parse("-2562047788015215:30:08").unwrap().seconds(), i64::MIN);
+    /// assert!(Duration::parse("2562047788015215:30:08").is_err());
+    /// ```
+    pub fn parse(s: &str) -> Result<Duration, Error> {
+        let gen_error = || -> Error { Error::parse(format!("Not a duration: {}", s)) };
+        let (sign, rest) = match s.strip_prefix('-') {
+            Some(rest) => (-1, rest),
+            None => (1, s),
+        };
+        let seconds = if rest.contains(':') {
+            let parts: Vec<&str> = rest.split(':').collect();
+            let valid = (2..=3).contains(&parts.len())
+                && parts
+                    .iter()
+                    .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+                && parts[1..].iter().all(|part| part.len() == 2);
+            if !valid {
+                return Err(gen_error());
+            }
+            let hours = parts[0].parse::<i128>().map_err(|_| gen_error())?;
+            let minutes = parts[1].parse::<i128>().map_err(|_| gen_error())?;
+            let seconds = match parts.get(2) {
+                Some(part) => part.parse::<i128>().map_err(|_| gen_error())?,
+                None => 0,
+            };
+            if minutes >= 60 || seconds >= 60 {
+                return Err(gen_error());
+            }
+            hours
+                .checked_mul(3600)
+                .and_then(|n| n.checked_add(minutes * 60 + seconds))
+                .ok_or_else(gen_error)?
+        } else {
+            const UNITS: [(char, i64); 4] =
+                [('d', SECONDS_PER_DAY), ('h', 3600), ('m', 60), ('s', 1)];
+            let mut total: i128 = 0;
+            let mut next_unit = 0;
+            let mut rest = rest.trim_start();
+            if rest.is_empty() {
+                return Err(gen_error());
+            }
+            while !rest.is_empty() {
+                let len = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+                let number = rest[..len].parse::<i128>().map_err(|_| gen_error())?;
+                let unit = rest[len..].chars().next().ok_or_else(gen_error)?;
+                // the units must be given from days to seconds, each only once
+                let idx = UNITS[next_unit..]
+                    .iter()
+                    .position(|(name, _)| *name == unit)
+                    .ok_or_else(gen_error)?
+                    + next_unit;
+                total = number
+                    .checked_mul(UNITS[idx].1 as i128)
+                    .and_then(|n| n.checked_add(total))
+                    .ok_or_else(gen_error)?;
+                next_unit = idx + 1;
+                rest = rest[len + 1..].trim_start();
+            }
+            total
+        };
+        // the sign is applied before the range check, so that i64::MIN can be read back
+        let seconds = i64::try_from(sign * seconds).map_err(|_| gen_error())?;
+        Ok(Duration { seconds })
+    }
+
+    /// None on overflow
+    /// ```
+    /// use rzdb::time::Duration;
+    /// let hour = Duration::parse("1h").unwrap();
+    /// assert_eq!(hour.checked_add_duration(&hour).unwrap().to_string(), "02:00:00");
+    /// assert!(Duration::from_seconds(i64::MAX).checked_add_duration(&hour).is_none());
+    /// ```
+    pub fn checked_add_duration(&self, other: &Duration) -> Option<Duration> {
+        self.seconds
+            .checked_add(other.seconds)
+            .map(Duration::from_seconds)
+    }
+
+    /// None for the most negative duration
+    pub fn checked_neg(&self) -> Option<Duration> {
+        self.seconds.checked_neg().map(Duration::from_seconds)
+    }
+}
+
+/// `[-]hh:mm:ss`, hours may exceed 24
+impl std::fmt::Display for Duration {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let sign = if self.seconds < 0 { "-" } else { "" };
+        let total = self.seconds.unsigned_abs();
+        write!(
+            f,
+            "{}{:02}:{:02}:{:02}",
+            sign,
+            total / 3600,
+            (total % 3600) / 60,
+            total % 60
+        )
+    }
+}
+
 impl Timestamp {
     pub fn now() -> Timestamp {
         Timestamp {
             timestamp: SystemTime::now()
                 .duration_since(UNIX_EPOCH)
-                .unwrap()
+                .unwrap_or_default()
                 .as_secs(),
         }
     }
 
     fn is_leap_year(year: u64) -> bool {
-        (year % 4 == 0) && (year % 100 != 0 || year % 400 == 0)
+        year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400))
     }
 
     pub fn to_filename_string(&self) -> String {
-        format!("{}", self).as_str()[0..(4 + 1 + 2 + 1 + 2 + 1 + 2 + 1 + 2)]
+        self.to_filename_string_in(local_zone())
+    }
+
+    /// e.g. `2024-03-01_12.30` for 12:30 in the given zone
+    pub fn to_filename_string_in(&self, zone: &TimeZone) -> String {
+        self.format_in(zone).as_str()[0..(4 + 1 + 2 + 1 + 2 + 1 + 2 + 1 + 2)]
             .to_string()
             .replace(':', ".")
             .replace(' ', "_")
     }
 
+    /// local time of day
     pub fn as_time(&self) -> Time {
-        Time::new(((self.timestamp + 60 * 60 * 2) % (60 * 60 * 24)) as u32)
+        self.as_date_time().to_date_time(local_zone()).1
+    }
+
+    pub fn as_date_time(&self) -> DateTime {
+        DateTime::from_seconds(self.timestamp as i64)
+    }
+
+    /// date and time in the given zone, e.g. `2024-03-01 12:30:00`
+    pub fn format_in(&self, zone: &TimeZone) -> String {
+        let (date, time) = self.as_date_time().to_date_time(zone);
+        format!("{} {}", date, time)
     }
 }
 
 impl std::fmt::Display for Timestamp {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let timestamp_local = self.timestamp + 3600 * 2; // convert to local time
-        let time = Time::new((timestamp_local % (60 * 60 * 24)) as u32);
-        let days = timestamp_local / (60 * 60 * 24);
-        let date = DateDays { days: days as u32 };
+        write!(f, "{}", self.format_in(local_zone()))
+    }
+}
+
+impl DateTime {
+    pub fn from_seconds(seconds: i64) -> DateTime {
+        DateTime { seconds }
+    }
+
+    pub fn from_date_time(date: &Date, time: &Time) -> DateTime {
+        let days = days_from_civil(date.year as i64, date.month, date.day);
+        DateTime {
+            seconds: days * SECONDS_PER_DAY + time.seconds as i64,
+        }
+    }
+
+    pub fn now() -> DateTime {
+        DateTime {
+            seconds: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs() as i64,
+        }
+    }
+
+    /// seconds since 1970-01-01 00:00:00 UTC
+    pub fn seconds(&self) -> i64 {
+        self.seconds
+    }
+
+    /// None if the result is not between the years 1 and 9999 in UTC
+    /// ```
+    /// use rzdb::time::{DateTime, Duration};
+    /// let end = DateTime::parse("9999-12-31T23:59:59Z").unwrap();
+    /// assert!(end.checked_add_duration(&Duration::from_seconds(1)).is_none());
+    /// assert_eq!(end.checked_add_duration(&Duration::from_seconds(-59)), DateTime::parse("9999-12-31T23:59:00Z").ok());
+    /// ```
+    pub fn checked_add_duration(&self, duration: &Duration) -> Option<DateTime> {
+        let seconds = self.seconds.checked_add(duration.seconds())?;
+        let range = days_from_civil(1, 1, 1) * SECONDS_PER_DAY
+            ..days_from_civil(10000, 1, 1) * SECONDS_PER_DAY;
+        range
+            .contains(&seconds)
+            .then(|| DateTime::from_seconds(seconds))
+    }
+
+    /// the duration from another point in time to this one, None on overflow
+    /// ```
+    /// use rzdb::time::{DateTime, Duration};
+    /// let start = DateTime::parse("2024-03-01T08:00:00Z").unwrap();
+    /// let end = DateTime::parse("2024-03-02T17:30:00+01:00").unwrap();
+    /// let duration = end.checked_duration_since(&start).unwrap();
+    /// assert_eq!(duration.to_string(), "32:30:00");
+    /// assert_eq!(start.checked_add_duration(&duration), Some(end));
+    /// let first = DateTime::from_seconds(i64::MIN);
+    /// assert!(DateTime::from_seconds(i64::MAX).checked_duration_since(&first).is_none());
+    /// ```
+    pub fn checked_duration_since(&self, other: &DateTime) -> Option<Duration> {
+        self.seconds
+            .checked_sub(other.seconds)
+            .map(Duration::from_seconds)
+    }
+
+    /// Parses an ISO 8601 date and time, e.g. `2024-03-01T12:30:00+02:00`.
+    /// The separator may be `T` or a space, seconds are optional and fractions of seconds
+    /// are ignored. Without an offset (`Z`, `+hh:mm`, `+hhmm` or `+hh`) the time is UTC.
+    /// ```
+    /// use rzdb::time::DateTime;
+    /// let utc = DateTime::parse("2024-03-01T10:30:00Z").unwrap();
+    /// let local = DateTime::parse("2024-03-01 12:30+02:00").unwrap();
+    /// assert!(utc == local);
+    /// assert_eq!(local.to_string(), "2024-03-01T10:30:00Z");
+    /// assert!(DateTime::parse("2024-03-01").is_err());
+    /// ```
+    pub fn parse(s: &str) -> Result<DateTime, Error> {
+        let not_a_date_time = || -> Error { Error::parse(format!("Not a date and time: {}", s)) };
+        let (date, rest) = s
+            .split_once('T')
+            .or_else(|| s.split_once(' '))
+            .ok_or_else(not_a_date_time)?;
+        let date = Date::parse_iso(date)?;
+
+        // split off the offset
+        let (time, offset) = if let Some(time) = rest.strip_suffix('Z') {
+            (time, 0)
+        } else if let Some(idx) = rest.find(['+', '-']) {
+            let (time, offset) = rest.split_at(idx);
+            let sign = if offset.starts_with('-') { -1 } else { 1 };
+            let digits: String = offset[1..].chars().filter(|c| *c != ':').collect();
+            if (digits.len() != 2 && digits.len() != 4)
+                || !digits.chars().all(|c| c.is_ascii_digit())
+            {
+                return Err(not_a_date_time());
+            }
+            let hours = digits[0..2].parse::<i64>()?;
+            let minutes = if digits.len() == 4 {
+                digits[2..4].parse::<i64>()?
+            } else {
+                0
+            };
+            if hours > 23 || minutes > 59 {
+                return Err(not_a_date_time());
+            }
+            (time, sign * (hours * 3600 + minutes * 60))
+        } else {
+            (rest, 0)
+        };
+        // ignore fractions of seconds
+        let time = time.split('.').next().unwrap_or(time);
+        let time = Time::parse(time)?;
+        let local = DateTime::from_date_time(&date, &time);
+        Ok(DateTime {
+            seconds: local.seconds - offset,
+        })
+    }
+
+    /// date and time of this point in time in the given zone
+    pub fn to_date_time(&self, zone: &TimeZone) -> (Date, Time) {
+        // points in time outside the years 1 to 9999 are shown at the nearest end
+        let local = self
+            .seconds
+            .saturating_add(zone.offset_at(self.seconds) as i64)
+            .clamp(
+                days_from_civil(1, 1, 1) * SECONDS_PER_DAY,
+                days_from_civil(10000, 1, 1) * SECONDS_PER_DAY - 1,
+            );
+        let date = Date::from_days(local.div_euclid(SECONDS_PER_DAY));
+        let time = Time::new(local.rem_euclid(SECONDS_PER_DAY) as u32);
+        (date, time)
+    }
+
+    /// formats this point in time in the given zone, e.g. `2024-03-01 12:30:00 +02:00`
+    /// ```
+    /// use rzdb::time::{DateTime, TimeZone};
+    /// let dt = DateTime::parse("2024-03-01T22:30:00Z").unwrap();
+    /// assert_eq!(dt.format_in(&TimeZone::Fixed(2 * 3600)), "2024-03-02 00:30:00 +02:00");
+    /// assert_eq!(dt.format_in(&TimeZone::Utc), "2024-03-01 22:30:00 +00:00");
+    /// ```
+    pub fn format_in(&self, zone: &TimeZone) -> String {
+        let (date, time) = self.to_date_time(zone);
+        let offset = zone.offset_at(self.seconds);
+        let sign = if offset < 0 { '-' } else { '+' };
+        let offset = offset.abs();
+        format!(
+            "{} {} {}{:02}:{:02}",
+            date,
+            time,
+            sign,
+            offset / 3600,
+            (offset % 3600) / 60
+        )
+    }
+}
+
+impl std::fmt::Display for DateTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let (date, time) = self.to_date_time(&TimeZone::Utc);
+        write!(f, "{}T{}Z", date, time)
+    }
+}
+
+impl TimeZone {
+    /// offset to UTC in seconds at a point in time given in seconds since the epoch
+    pub fn offset_at(&self, seconds: i64) -> i32 {
+        match self {
+            TimeZone::Utc => 0,
+            TimeZone::Fixed(offset) => *offset,
+            TimeZone::Zone(zone) => zone.offset_at(seconds),
+        }
+    }
+
+    /// The zone of the system: the `TZ` environment variable if set, otherwise
+    /// `/etc/localtime`. `TZ` may name a zone in the zoneinfo directory (`Europe/Berlin`),
+    /// an absolute path to a TZif file or a POSIX TZ string (`CET-1CEST,M3.5.0,M10.5.0/3`).
+    /// Falls back to UTC if no zone can be read.
+    pub fn local() -> TimeZone {
+        match std::env::var("TZ") {
+            Ok(tz) => {
+                let tz = tz.strip_prefix(':').unwrap_or(&tz);
+                if tz.is_empty() {
+                    TimeZone::Utc
+                } else {
+                    TimeZone::from_name(tz)
+                        .or_else(|_| TimeZone::from_posix(tz))
+                        .unwrap_or(TimeZone::Utc)
+                }
+            }
+            Err(_) => TimeZone::from_file("/etc/localtime").unwrap_or(TimeZone::Utc),
+        }
+    }
+
+    /// reads a zone from the zoneinfo directory (`TZDIR` or `/usr/share/zoneinfo`),
+    /// or from a TZif file if the name is an absolute path
+    pub fn from_name(name: &str) -> Result<TimeZone, Error> {
+        if name.starts_with('/') {
+            return TimeZone::from_file(name);
+        }
+        if name.split('/').any(|part| part == "..") {
+            return Err(Error::invalid(format!("Invalid zone name: {}", name)));
+        }
+        let dir = std::env::var("TZDIR").unwrap_or_else(|_| "/usr/share/zoneinfo".to_string());
+        TimeZone::from_file(&format!("{}/{}", dir, name))
+    }
+
+    pub fn from_file(filename: &str) -> Result<TimeZone, Error> {
+        let bytes = std::fs::read(filename)?;
+        Ok(TimeZone::Zone(Box::new(ZoneInfo::parse(&bytes)?)))
+    }
+
+    /// a zone defined by a POSIX TZ string, e.g. `CET-1CEST,M3.5.0,M10.5.0/3`
+    /// ```
+    /// use rzdb::time::{DateTime, TimeZone};
+    /// let berlin = TimeZone::from_posix("CET-1CEST,M3.5.0,M10.5.0/3").unwrap();
+    /// let winter = DateTime::parse("2024-01-15T12:00:00Z").unwrap();
+    /// let summer = DateTime::parse("2024-07-15T12:00:00Z").unwrap();
+    /// assert_eq!(winter.format_in(&berlin), "2024-01-15 13:00:00 +01:00");
+    /// assert_eq!(summer.format_in(&berlin), "2024-07-15 14:00:00 +02:00");
+    /// ```
+    pub fn from_posix(rule: &str) -> Result<TimeZone, Error> {
+        Ok(TimeZone::Zone(Box::new(ZoneInfo::from_rule(
+            PosixRule::parse(rule)?,
+        ))))
+    }
+}
+
+/// the system's zone, read once
+pub(crate) fn local_zone() -> &'static TimeZone {
+    static LOCAL_ZONE: OnceLock<TimeZone> = OnceLock::new();
+    LOCAL_ZONE.get_or_init(TimeZone::local)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_civil_days() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11017);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+        for days in [-800_000, -1, 0, 59, 60, 11016, 11017, 19782, 2_932_896] {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+
+    #[test]
+    fn test_date_calendar() {
+        let date = Date::new(2024, 2, 29).unwrap();
+        assert_eq!(date.days_since_epoch(), 19782);
+        assert_eq!(Date::from_days_since_epoch(19782), Some(date.clone()));
+        assert_eq!(
+            Date::from_days_since_epoch(-1),
+            Date::new(1969, 12, 31).ok()
+        );
+        assert_eq!(Date::from_days_since_epoch(i64::MIN), None);
+        assert_eq!(date.weekday(), Weekday::Thursday);
+        assert_eq!(Date::new(1970, 1, 1).unwrap().weekday(), Weekday::Thursday);
+        assert_eq!(Date::new(2024, 3, 3).unwrap().weekday(), Weekday::Sunday);
+        assert_eq!(date.day_of_year(), 60);
+        assert_eq!(Date::new(2023, 12, 31).unwrap().day_of_year(), 365);
+        assert_eq!(date.first_of_month(), Date::new(2024, 2, 1).unwrap());
+        assert_eq!(
+            Date::new(2023, 2, 10).unwrap().last_of_month(),
+            Date::new(2023, 2, 28).unwrap()
+        );
+        assert_eq!(date.checked_add_years(1), Date::new(2025, 2, 28).ok());
+        assert_eq!(
+            Date::new(2024, 1, 1)
+                .unwrap()
+                .range_to(&Date::new(2024, 1, 1).unwrap())
+                .count(),
+            0
+        );
+        assert_eq!(
+            Date::new(2024, 1, 1)
+                .unwrap()
+                .range_to(&Date::new(2025, 1, 1).unwrap())
+                .count(),
+            366
+        );
+        assert_eq!(DateDays { days: 19782 }.to_string(), "2024-02-29");
+    }
+
+    #[test]
+    fn test_date_parse() {
+        let date = |s: &str| Date::parse(s);
+        let invalid = |s: &str| matches!(Date::parse(s), Err(DateError::InvalidDate(_)));
+        let not_a_date = |s: &str| matches!(Date::parse(s), Err(DateError::NotADate(_)));
+        assert_eq!(date("2024-02-29"), Ok(Date::new(2024, 2, 29).unwrap()));
+        assert_eq!(date("2/29/2000"), Ok(Date::new(2000, 2, 29).unwrap()));
+        assert_eq!(date("29.2.2024"), Ok(Date::new(2024, 2, 29).unwrap()));
+        assert_eq!(date("20.7.1969"), Ok(Date::new(1969, 7, 20).unwrap()));
+        assert_eq!(date("1.1.1950"), Ok(Date::new(1950, 1, 1).unwrap()));
+        assert_eq!(date("1.1.50"), Ok(Date::new(1950, 1, 1).unwrap()));
+        assert_eq!(date("1.1.05"), Ok(Date::new(2005, 1, 1).unwrap()));
+        assert_eq!(date("0001-01-01"), Ok(Date::new(1, 1, 1).unwrap()));
+        assert!(invalid("2023-02-29"));
+        assert!(invalid("1900-02-29"));
+        assert!(invalid("2023-02-31"));
+        assert!(invalid("2023-04-31"));
+        assert!(invalid("2023-00-10"));
+        assert!(invalid("2023-01-00"));
+        assert!(invalid("13/1/2023"));
+        assert!(invalid("0000-01-01"));
+        assert!(not_a_date("24-02-29"));
+        assert!(not_a_date("2024-02-29-1"));
+        assert!(not_a_date("2024-02"));
+        assert!(not_a_date("1/1/24"));
+        assert!(not_a_date("1.1.100"));
+        assert!(not_a_date("a.b.c"));
+        assert!(not_a_date("2024-+2-01"));
+        assert!(Date::new(2024, 2, 30).is_err());
+        assert!(Date::new(2024, 2, 29).is_ok());
+    }
+
+    #[test]
+    fn test_date_format() {
+        let default = DateFormat::default();
+        for s in [
+            "2024-02-29",
+            "2/29/2024",
+            "29.2.24",
+            "29.2.1924",
+            "1.1.",
+            "2023-02-29",
+            "24-02-29",
+            "1/1/24",
+            "1.1.100",
+            "12:30",
+            "2024-02-29x",
+        ] {
+            assert_eq!(default.parse(s), Date::parse(s), "{}", s);
+        }
+        let format = DateFormat::new(&["%d %b %Y", "%Y%m%d"], "%d %b %y").unwrap();
+        assert_eq!(
+            format.parse("05 mar 2024"),
+            Ok(Date::new(2024, 3, 5).unwrap())
+        );
+        assert_eq!(
+            format.parse("5 March 2024"),
+            Ok(Date::new(2024, 3, 5).unwrap())
+        );
+        assert_eq!(format.parse("20240305"), Ok(Date::new(2024, 3, 5).unwrap()));
+        assert!(matches!(
+            format.parse("5 Mars 2024"),
+            Err(DateError::NotADate(_))
+        ));
+        assert!(matches!(
+            format.parse("31 Apr 2024"),
+            Err(DateError::InvalidDate(_))
+        ));
+        assert_eq!(format.format(&Date::new(2024, 3, 5).unwrap()), "05 Mar 24");
+        assert_eq!(
+            DateFormat::new(&[], "100%% %m")
+                .unwrap()
+                .format(&Date::new(2024, 3, 5).unwrap()),
+            "100% 03"
+        );
+    }
 
-        write!(f, "{} {}", date, time)
+    #[test]
+    fn test_date_time_parse() {
+        let dt = |s: &str| DateTime::parse(s).unwrap().seconds();
+        assert_eq!(dt("1970-01-01T00:00:00Z"), 0);
+        assert_eq!(dt("1970-01-01T00:00"), 0);
+        assert_eq!(dt("1970-01-01T01:00:00+01:00"), 0);
+        assert_eq!(dt("1970-01-01T00:00:00-0130"), 5400);
+        assert_eq!(dt("2024-02-29T12:00:00.250Z"), 1709208000);
+        assert!(DateTime::parse("2024-02-29T25:00:00Z").is_err());
+        assert!(DateTime::parse("2024-02-29T12:00:00+1").is_err());
+        assert!(DateTime::parse("2024-01-01T12:00+1é1").is_err());
+        assert!(DateTime::parse("2024-01-01T12:00+é").is_err());
+        assert_eq!(
+            crate::data::Data::parse("2024-01-01T12:00+1é1"),
+            crate::data::Data::String("2024-01-01T12:00+1é1".to_string())
+        );
     }
 }
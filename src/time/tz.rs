@@ -0,0 +1,431 @@
+use super::{days_from_civil, SECONDS_PER_DAY};
+use crate::error::Error;
+
+fn gen_error(msg: &str) -> Error {
+    Error::parse(msg)
+}
+
+/// Offsets of a zone as read from a TZif file (see RFC 8536): a table of transitions,
+/// followed by a POSIX TZ rule for all times after the last transition.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ZoneInfo {
+    transitions: Vec<i64>,
+    transition_types: Vec<u8>,
+    offsets: Vec<i32>,
+    rule: Option<PosixRule>,
+}
+
+/// A POSIX TZ string such as `CET-1CEST,M3.5.0,M10.5.0/3`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct PosixRule {
+    /// offset of standard time to UTC in seconds (east positive)
+    std_offset: i32,
+    dst: Option<DstRule>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct DstRule {
+    offset: i32,
+    start: RuleDate,
+    /// local standard time of the switch to daylight saving time in seconds
+    start_time: i32,
+    end: RuleDate,
+    /// local daylight saving time of the switch back to standard time in seconds
+    end_time: i32,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum RuleDate {
+    /// `Jn`: day 1..=365, February 29 is never counted
+    Julian(u16),
+    /// `n`: day 0..=365, February 29 is counted in leap years
+    ZeroBased(u16),
+    /// `Mm.w.d`: day d (0 = Sunday) of week w (5 = last) of month m
+    MonthWeekDay(u8, u8, u8),
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.pos + len > self.bytes.len() {
+            return Err(gen_error("TZif data is truncated"));
+        }
+        let slice = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(slice)
+    }
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+    fn i32(&mut self) -> Result<i32, Error> {
+        let b = self.take(4)?;
+        Ok(i32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+    fn i64(&mut self) -> Result<i64, Error> {
+        let b = self.take(8)?;
+        let mut buf = [0; 8];
+        buf.copy_from_slice(b);
+        Ok(i64::from_be_bytes(buf))
+    }
+}
+
+struct Header {
+    version: u8,
+    isutcnt: usize,
+    isstdcnt: usize,
+    leapcnt: usize,
+    timecnt: usize,
+    typecnt: usize,
+    charcnt: usize,
+}
+
+impl Header {
+    fn read(reader: &mut Reader) -> Result<Header, Error> {
+        if reader.take(4)? != b"TZif" {
+            return Err(gen_error("not a TZif file"));
+        }
+        let version = reader.u8()?;
+        reader.take(15)?;
+        let mut counts = [0; 6];
+        for count in &mut counts {
+            *count = reader.i32()?.max(0) as usize;
+        }
+        Ok(Header {
+            version,
+            isutcnt: counts[0],
+            isstdcnt: counts[1],
+            leapcnt: counts[2],
+            timecnt: counts[3],
+            typecnt: counts[4],
+            charcnt: counts[5],
+        })
+    }
+}
+
+impl ZoneInfo {
+    /// a zone without a transition table
+    pub fn from_rule(rule: PosixRule) -> ZoneInfo {
+        ZoneInfo {
+            transitions: vec![],
+            transition_types: vec![],
+            offsets: vec![rule.std_offset],
+            rule: Some(rule),
+        }
+    }
+
+    /// parses the content of a TZif file, e.g. `/etc/localtime`
+    pub fn parse(bytes: &[u8]) -> Result<ZoneInfo, Error> {
+        let mut reader = Reader { bytes, pos: 0 };
+        let mut header = Header::read(&mut reader)?;
+        let mut time_size = 4;
+        if header.version >= b'2' {
+            // skip the version 1 data block, the version 2 block has 64 bit times
+            reader.take(
+                header.timecnt * 5
+                    + header.typecnt * 6
+                    + header.charcnt
+                    + header.leapcnt * 8
+                    + header.isstdcnt
+                    + header.isutcnt,
+            )?;
+            header = Header::read(&mut reader)?;
+            time_size = 8;
+        }
+
+        let mut transitions = Vec::with_capacity(header.timecnt);
+        for _ in 0..header.timecnt {
+            transitions.push(if time_size == 8 {
+                reader.i64()?
+            } else {
+                reader.i32()? as i64
+            });
+        }
+        let transition_types = reader.take(header.timecnt)?.to_vec();
+        let mut offsets = Vec::with_capacity(header.typecnt);
+        for _ in 0..header.typecnt {
+            offsets.push(reader.i32()?);
+            reader.take(2)?;
+        }
+        if offsets.is_empty() {
+            return Err(gen_error("TZif file without local time types"));
+        }
+        if transition_types
+            .iter()
+            .any(|&idx| idx as usize >= offsets.len())
+        {
+            return Err(gen_error("TZif file with invalid local time type"));
+        }
+        reader.take(
+            header.charcnt + header.leapcnt * (time_size + 4) + header.isstdcnt + header.isutcnt,
+        )?;
+
+        let mut rule = None;
+        if time_size == 8 {
+            let footer = String::from_utf8_lossy(&reader.bytes[reader.pos..]).to_string();
+            let footer = footer.trim_matches('\n');
+            if !footer.is_empty() {
+                rule = Some(PosixRule::parse(footer)?);
+            }
+        }
+
+        Ok(ZoneInfo {
+            transitions,
+            transition_types,
+            offsets,
+            rule,
+        })
+    }
+
+    /// offset to UTC in seconds at a point in time given in seconds since the epoch
+    pub fn offset_at(&self, seconds: i64) -> i32 {
+        if self.transitions.is_empty() || seconds < self.transitions[0] {
+            return match (&self.rule, self.transitions.is_empty()) {
+                (Some(rule), true) => rule.offset_at(seconds),
+                _ => self.offsets[0],
+            };
+        }
+        if seconds >= *self.transitions.last().unwrap() {
+            if let Some(rule) = &self.rule {
+                return rule.offset_at(seconds);
+            }
+        }
+        let idx = match self.transitions.binary_search(&seconds) {
+            Ok(idx) => idx,
+            Err(idx) => idx - 1,
+        };
+        self.offsets[self.transition_types[idx] as usize]
+    }
+}
+
+/// parses `[+-]hh[:mm[:ss]]` and returns the seconds
+fn parse_hms(s: &str) -> Result<i32, Error> {
+    let (sign, s) = match s.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let mut seconds = 0;
+    for (idx, part) in s.split(':').enumerate() {
+        if idx > 2 || part.is_empty() || part.len() > 3 {
+            return Err(gen_error("invalid time in TZ rule"));
+        }
+        let factor = [3600, 60, 1][idx];
+        seconds += part.parse::<i32>()? * factor;
+    }
+    Ok(sign * seconds)
+}
+
+/// splits a zone abbreviation (`CET` or `<+03>`) off the front of a TZ string
+fn split_name(s: &str) -> Result<(&str, &str), Error> {
+    let len = if let Some(rest) = s.strip_prefix('<') {
+        rest.find('>')
+            .ok_or_else(|| gen_error("unterminated zone name"))?
+            + 2
+    } else {
+        s.find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(s.len())
+    };
+    if len < 3 {
+        return Err(gen_error("zone name too short in TZ rule"));
+    }
+    Ok(s.split_at(len))
+}
+
+/// splits an offset or time (`-1`, `+05:30`, `2`) off the front of a TZ string
+fn split_hms(s: &str) -> (&str, &str) {
+    let len = s
+        .char_indices()
+        .find(|(idx, c)| !(c.is_ascii_digit() || *c == ':' || (*idx == 0 && "+-".contains(*c))))
+        .map(|(idx, _)| idx)
+        .unwrap_or(s.len());
+    s.split_at(len)
+}
+
+impl RuleDate {
+    fn parse(s: &str) -> Result<(RuleDate, i32), Error> {
+        let (date, time) = match s.split_once('/') {
+            Some((date, time)) => (date, parse_hms(time)?),
+            None => (s, 2 * 3600),
+        };
+        let rule_date = if let Some(day) = date.strip_prefix('J') {
+            RuleDate::Julian(day.parse()?)
+        } else if let Some(mwd) = date.strip_prefix('M') {
+            let parts: Vec<&str> = mwd.split('.').collect();
+            if parts.len() != 3 {
+                return Err(gen_error("invalid Mm.w.d rule"));
+            }
+            let (month, week, day) = (parts[0].parse()?, parts[1].parse()?, parts[2].parse()?);
+            if !(1..=12).contains(&month) || !(1..=5).contains(&week) || day > 6 {
+                return Err(gen_error("invalid Mm.w.d rule"));
+            }
+            RuleDate::MonthWeekDay(month, week, day)
+        } else {
+            RuleDate::ZeroBased(date.parse()?)
+        };
+        Ok((rule_date, time))
+    }
+
+    /// days since the epoch of this rule's day in the given year
+    fn days_in_year(&self, year: i64) -> i64 {
+        let leap = super::Timestamp::is_leap_year(year.rem_euclid(400) as u64);
+        let jan_1 = days_from_civil(year, 1, 1);
+        match self {
+            RuleDate::Julian(day) => {
+                let day = *day as i64;
+                jan_1 + day - 1 + if leap && day >= 60 { 1 } else { 0 }
+            }
+            RuleDate::ZeroBased(day) => jan_1 + *day as i64,
+            RuleDate::MonthWeekDay(month, week, weekday) => {
+                let first = days_from_civil(year, *month, 1);
+                let first_weekday = (first + 4).rem_euclid(7);
+                let mut day = first + (*weekday as i64 - first_weekday).rem_euclid(7);
+                day += (*week as i64 - 1) * 7;
+                let next_month = if *month == 12 {
+                    days_from_civil(year + 1, 1, 1)
+                } else {
+                    days_from_civil(year, month + 1, 1)
+                };
+                while day >= next_month {
+                    day -= 7;
+                }
+                day
+            }
+        }
+    }
+}
+
+impl PosixRule {
+    /// parses a POSIX TZ string, e.g. `CET-1CEST,M3.5.0,M10.5.0/3` or `<+0330>-3:30`
+    pub fn parse(s: &str) -> Result<PosixRule, Error> {
+        let (_, rest) = split_name(s)?;
+        let (std_offset, rest) = split_hms(rest);
+        // POSIX offsets are west positive
+        let std_offset = -parse_hms(std_offset)?;
+        if rest.is_empty() {
+            return Ok(PosixRule {
+                std_offset,
+                dst: None,
+            });
+        }
+        let (_, rest) = split_name(rest)?;
+        let (dst_offset, rest) = split_hms(rest);
+        let dst_offset = if dst_offset.is_empty() {
+            std_offset + 3600
+        } else {
+            -parse_hms(dst_offset)?
+        };
+        let rules: Vec<&str> = rest.split(',').collect();
+        let (start, end) = match rules.as_slice() {
+            // default rules of the US
+            [""] => ("M3.2.0", "M11.1.0"),
+            ["", start, end] => (*start, *end),
+            _ => return Err(gen_error("invalid daylight saving time rule")),
+        };
+        let (start, start_time) = RuleDate::parse(start)?;
+        let (end, end_time) = RuleDate::parse(end)?;
+        Ok(PosixRule {
+            std_offset,
+            dst: Some(DstRule {
+                offset: dst_offset,
+                start,
+                start_time,
+                end,
+                end_time,
+            }),
+        })
+    }
+
+    pub fn offset_at(&self, seconds: i64) -> i32 {
+        let dst = match &self.dst {
+            Some(dst) => dst,
+            None => return self.std_offset,
+        };
+        let local = seconds + self.std_offset as i64;
+        let (year, _, _) = super::civil_from_days(local.div_euclid(SECONDS_PER_DAY));
+        let start = dst.start.days_in_year(year) * SECONDS_PER_DAY + dst.start_time as i64
+            - self.std_offset as i64;
+        let end =
+            dst.end.days_in_year(year) * SECONDS_PER_DAY + dst.end_time as i64 - dst.offset as i64;
+        let in_dst = if start < end {
+            seconds >= start && seconds < end
+        } else {
+            // southern hemisphere: daylight saving time spans the turn of the year
+            seconds < end || seconds >= start
+        };
+        if in_dst {
+            dst.offset
+        } else {
+            self.std_offset
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> i64 {
+        super::super::DateTime::parse(s).unwrap().seconds()
+    }
+
+    #[test]
+    fn test_posix_rule() {
+        let berlin = PosixRule::parse("CET-1CEST,M3.5.0,M10.5.0/3").unwrap();
+        assert_eq!(berlin.offset_at(utc("2024-01-15T12:00:00Z")), 3600);
+        assert_eq!(berlin.offset_at(utc("2024-03-31T00:59:59Z")), 3600);
+        assert_eq!(berlin.offset_at(utc("2024-03-31T01:00:00Z")), 7200);
+        assert_eq!(berlin.offset_at(utc("2024-10-27T00:59:59Z")), 7200);
+        assert_eq!(berlin.offset_at(utc("2024-10-27T01:00:00Z")), 3600);
+
+        let sydney = PosixRule::parse("AEST-10AEDT,M10.1.0,M4.1.0/3").unwrap();
+        assert_eq!(sydney.offset_at(utc("2024-01-15T12:00:00Z")), 11 * 3600);
+        assert_eq!(sydney.offset_at(utc("2024-07-15T12:00:00Z")), 10 * 3600);
+
+        let new_york = PosixRule::parse("EST5EDT,M3.2.0,M11.1.0").unwrap();
+        assert_eq!(new_york.offset_at(utc("2024-03-10T06:59:59Z")), -5 * 3600);
+        assert_eq!(new_york.offset_at(utc("2024-03-10T07:00:00Z")), -4 * 3600);
+
+        let tehran = PosixRule::parse("<+0330>-3:30").unwrap();
+        assert_eq!(tehran.offset_at(0), 3 * 3600 + 1800);
+
+        assert!(PosixRule::parse("X-1").is_err());
+        assert!(PosixRule::parse("CET-1CEST,M13.5.0,M10.5.0").is_err());
+    }
+
+    #[test]
+    fn test_tzif() {
+        // version 2 file with one transition at 1000 from type 0 (+1h) to type 1 (+2h)
+        // and a footer without daylight saving time
+        let block = |time_size: usize| {
+            let mut out = b"TZif2".to_vec();
+            out.extend([0; 15]);
+            for count in [0i32, 0, 0, 1, 2, 4] {
+                out.extend(count.to_be_bytes());
+            }
+            if time_size == 8 {
+                out.extend(1000i64.to_be_bytes());
+            } else {
+                out.extend(1000i32.to_be_bytes());
+            }
+            out.push(1);
+            out.extend(3600i32.to_be_bytes());
+            out.extend([0, 0]);
+            out.extend(7200i32.to_be_bytes());
+            out.extend([0, 0]);
+            out.extend(b"AB\0\0");
+            out
+        };
+        let mut bytes = block(4);
+        bytes.extend(block(8));
+        bytes.extend(b"\nXYZ-3\n");
+        let zone = ZoneInfo::parse(&bytes).unwrap();
+        assert_eq!(zone.offset_at(-5000), 3600);
+        assert_eq!(zone.offset_at(999), 3600);
+        assert_eq!(zone.offset_at(1000), 3 * 3600);
+        assert!(ZoneInfo::parse(&bytes[..40]).is_err());
+        assert!(ZoneInfo::parse(b"not a zone file").is_err());
+    }
+}
@@ -0,0 +1,284 @@
+//! Typed tables, usually derived with `#[derive(RzdbTable)]`.
+
+use crate::column_type::ColumnType;
+use crate::condition::Condition;
+use crate::data::Data;
+use crate::db::Db;
+use crate::decimal::Decimal;
+use crate::error::Error;
+use crate::time::{Date, DateTime, Duration, Time};
+
+/// A struct stored as the rows of a table, with a column for every field.
+/// `#[derive(RzdbTable)]` implements it for structs with named fields of `FieldType`s,
+/// and adds constants with the column names, so that typos are compile errors.
+/// ```
+/// use rzdb::{Condition, ConditionType, Data, Db, RzdbTable};
+///
+/// #[derive(RzdbTable, PartialEq, Debug)]
+/// #[rzdb(table = "derive_tasks")]
+/// struct Task {
+///     title: String,
+///     hours: f64,
+///     done: bool,
+///     #[rzdb(column = "labels")]
+///     tags: Vec<String>,
+///     note: Option<String>,
+/// }
+///
+/// let mut db = Db::create("test", "~/.local/rzdb").unwrap();
+/// db.drop_table(Task::TABLE_NAME).ok();
+/// Task::create_table(&mut db).unwrap();
+/// assert_eq!(db.get_column_names(Task::TABLE_NAME).unwrap(), vec!["title", "hours", "done", "labels", "note"]);
+/// let task = Task {
+///     title: "write docs".to_string(),
+///     hours: 2.5,
+///     done: false,
+///     tags: vec!["docs".to_string()],
+///     note: None,
+/// };
+/// task.insert(&mut db).unwrap();
+/// let open = Condition::new(Task::DONE, Data::Bool(false), ConditionType::Equal);
+/// assert_eq!(Task::select(&db, &[open]).unwrap(), vec![task]);
+/// assert_eq!(Task::TAGS, "labels");
+/// ```
+pub trait RzdbTable: Sized {
+    const TABLE_NAME: &'static str;
+    /// the names and types of the columns, in the order of the fields
+    const COLUMNS: &'static [(&'static str, ColumnType)];
+
+    /// the values of the fields, sequences as `Data::List`
+    fn to_row(&self) -> Vec<Data>;
+
+    /// a struct from the values of its columns, joins as `Data::List`
+    fn from_row(row: Vec<Data>) -> Result<Self, Error>;
+
+    fn create_table(db: &mut Db) -> Result<(), Error> {
+        db.create_table_for::<Self>()
+    }
+
+    fn insert(&self, db: &mut Db) -> Result<(), Error> {
+        db.insert_typed(self)
+    }
+
+    fn select(db: &Db, conditions: &[Condition]) -> Result<Vec<Self>, Error> {
+        db.select_typed(conditions)
+    }
+}
+
+/// The types of fields of an `RzdbTable`, with their column type. Sequences are stored as
+/// joins in `Any` columns, `Option` fields as empty values.
+pub trait FieldType: Sized {
+    const COLUMN_TYPE: ColumnType;
+
+    fn to_data(&self) -> Data;
+
+    /// None if the value does not have the type
+    fn from_data(datum: Data) -> Option<Self>;
+
+    /// the value of a column, for derived `from_row()`
+    fn from_column(datum: Option<Data>, table: &str, column: &str) -> Result<Self, Error> {
+        let datum = datum.unwrap_or(Data::Empty);
+        let value = datum.to_string();
+        Self::from_data(datum).ok_or_else(|| Error::TypeMismatch {
+            table: table.to_string(),
+            column: column.to_string(),
+            expected: Self::COLUMN_TYPE.to_string(),
+            value,
+        })
+    }
+}
+
+impl FieldType for String {
+    const COLUMN_TYPE: ColumnType = ColumnType::String;
+
+    fn to_data(&self) -> Data {
+        Data::String(self.clone())
+    }
+
+    /// empty strings may be stored as empty values
+    fn from_data(datum: Data) -> Option<String> {
+        match datum {
+            Data::String(s) => Some(s),
+            Data::Empty => Some(String::new()),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! int_field_type {
+    ($($ty:ty),*) => {
+        $(
+            impl FieldType for $ty {
+                const COLUMN_TYPE: ColumnType = ColumnType::Int;
+
+                fn to_data(&self) -> Data {
+                    Data::Int(i64::from(*self))
+                }
+
+                fn from_data(datum: Data) -> Option<$ty> {
+                    match datum {
+                        Data::Int(n) => <$ty>::try_from(n).ok(),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+int_field_type!(i8, i16, i32, i64, u8, u16, u32);
+
+impl FieldType for f64 {
+    const COLUMN_TYPE: ColumnType = ColumnType::Float;
+
+    fn to_data(&self) -> Data {
+        Data::Float(*self)
+    }
+
+    fn from_data(datum: Data) -> Option<f64> {
+        match datum {
+            Data::Float(n) => Some(n),
+            _ => None,
+        }
+    }
+}
+
+impl FieldType for f32 {
+    const COLUMN_TYPE: ColumnType = ColumnType::Float;
+
+    fn to_data(&self) -> Data {
+        Data::Float(f64::from(*self))
+    }
+
+    fn from_data(datum: Data) -> Option<f32> {
+        f64::from_data(datum).map(|n| n as f32)
+    }
+}
+
+macro_rules! data_field_type {
+    ($($ty:ident),*) => {
+        $(
+            impl FieldType for $ty {
+                const COLUMN_TYPE: ColumnType = ColumnType::$ty;
+
+                fn to_data(&self) -> Data {
+                    Data::$ty(self.clone())
+                }
+
+                fn from_data(datum: Data) -> Option<$ty> {
+                    match datum {
+                        Data::$ty(value) => Some(value),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+data_field_type!(Decimal, Date, Time, DateTime, Duration);
+
+impl FieldType for bool {
+    const COLUMN_TYPE: ColumnType = ColumnType::Bool;
+
+    fn to_data(&self) -> Data {
+        Data::Bool(*self)
+    }
+
+    fn from_data(datum: Data) -> Option<bool> {
+        match datum {
+            Data::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+}
+
+impl<T: FieldType> FieldType for Option<T> {
+    const COLUMN_TYPE: ColumnType = T::COLUMN_TYPE;
+
+    fn to_data(&self) -> Data {
+        match self {
+            Some(value) => value.to_data(),
+            None => Data::Empty,
+        }
+    }
+
+    fn from_data(datum: Data) -> Option<Option<T>> {
+        match datum {
+            Data::Empty => Some(None),
+            datum => T::from_data(datum).map(Some),
+        }
+    }
+}
+
+impl<T: FieldType> FieldType for Vec<T> {
+    const COLUMN_TYPE: ColumnType = ColumnType::Any;
+
+    fn to_data(&self) -> Data {
+        Data::List(self.iter().map(FieldType::to_data).collect())
+    }
+
+    fn from_data(datum: Data) -> Option<Vec<T>> {
+        match datum {
+            Data::List(items) => items.into_iter().map(T::from_data).collect(),
+            Data::Empty => Some(vec![]),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip<T: FieldType + PartialEq + std::fmt::Debug>(value: T) {
+        let datum = value.to_data();
+        assert!(T::COLUMN_TYPE.accepts(&datum), "{:?}", datum);
+        assert_eq!(T::from_data(datum), Some(value));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        round_trip("text".to_string());
+        round_trip(-5i8);
+        round_trip(u32::MAX);
+        round_trip(i64::MIN);
+        round_trip(1.5f32);
+        round_trip(true);
+        round_trip(Decimal::parse("12.30").unwrap());
+        round_trip(Time::parse("12:30").unwrap());
+        round_trip(Some(3i64));
+        round_trip(None::<i64>);
+        round_trip(vec![Some(1i64), None]);
+        round_trip(Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_from_column() {
+        assert_eq!(String::from_data(Data::Empty), Some(String::new()));
+        assert_eq!(u8::from_data(Data::Int(256)), None);
+        assert_eq!(f64::from_data(Data::Int(1)), None);
+        assert_eq!(
+            Vec::<i64>::from_data(Data::List(vec![Data::Bool(true)])),
+            None
+        );
+        match i32::from_column(Some(Data::Bool(true)), "tasks", "hours") {
+            Err(Error::TypeMismatch {
+                table,
+                column,
+                expected,
+                value,
+            }) => assert_eq!(
+                (table, column, expected, value),
+                (
+                    "tasks".to_string(),
+                    "hours".to_string(),
+                    "int".to_string(),
+                    "true".to_string()
+                )
+            ),
+            other => panic!("{:?}", other),
+        }
+        assert!(i64::from_column(None, "tasks", "id").is_err());
+    }
+}